@@ -0,0 +1,459 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+use crate::engine::{Action, ClearType, Engine, Input};
+use crate::tetrominos::Tetromino;
+use crate::utilities::{lowest_avaliable_position, Cell};
+
+// a computer player. it looks at every placement the current piece and the hold piece can reach
+// with the same moves a player has, scores the board each one leaves behind and plays the best
+// one all at once, as often as its speed allows.
+//
+// the whole placement is sent on a single tick so gravity can't move the piece between the moves
+// it found, this needs an engine with instant soft drop
+
+pub struct Bot {
+    piece_interval: Duration,
+    next_placement: Duration, // engine time the next piece is played at
+}
+
+impl Bot {
+    // weights of the board evaluation, lines and attack are rewarded and a tall or messy stack
+    // is punished
+
+    const HEIGHT_WEIGHT: f64 = -0.51;
+    const LINES_WEIGHT: f64 = 0.76;
+    const HOLES_WEIGHT: f64 = -0.9;
+    const BUMPINESS_WEIGHT: f64 = -0.18;
+    const ATTACK_WEIGHT: f64 = 1.5;
+    const T_SLOT_WEIGHT: f64 = 0.8;
+    const DANGER_WEIGHT: f64 = -2.0;
+
+    const SAFE_HEIGHT: u32 = 10; // below this the stack is low enough to build t-spin setups
+    const PERFECT_CLEAR_BONUS: f64 = 10.0;
+
+    const MOVES: [Action; 6] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::RotateClockwise,
+        Action::RotateCounterClockwise,
+        Action::Rotate180,
+        Action::SoftDrop,
+    ];
+
+    pub fn new(pieces_per_second: f64) -> Self {
+        Self {
+            piece_interval: Duration::from_secs_f64(1.0 / pieces_per_second.max(0.1)),
+            next_placement: Duration::ZERO,
+        }
+    }
+
+    // start over for a new engine, its time starts again from zero
+
+    pub fn reset(&mut self) {
+        self.next_placement = Duration::ZERO;
+    }
+
+    // inputs for the engine's next tick, nothing until it's time for the next piece
+
+    pub fn inputs(&mut self, engine: &Engine) -> Vec<Input> {
+        if !engine.state.run || engine.now() < self.next_placement {
+            return Vec::new();
+        }
+
+        self.next_placement = engine.now() + self.piece_interval;
+
+        let mut actions = Self::plan(engine);
+        actions.push(Action::HardDrop);
+
+        actions
+            .into_iter()
+            .flat_map(|action| [Input::Press(action), Input::Release(action)])
+            .collect()
+    }
+
+    // the moves to the best placement, starting with a hold if the other piece does better
+
+    pub fn plan(engine: &Engine) -> Vec<Action> {
+        Self::best(engine)
+            .map(|(_, actions)| actions)
+            .unwrap_or_default()
+    }
+
+    // where the best placement locks, the piece is the hold piece when holding does better
+
+    pub fn hint(engine: &Engine) -> Option<Tetromino> {
+        Self::best(engine).map(|(placement, _)| placement)
+    }
+
+    fn best(engine: &Engine) -> Option<(Tetromino, Vec<Action>)> {
+        let state = &engine.state;
+        let mut candidates = vec![(state.current_tetromino.clone(), Vec::new())];
+
+        if engine.can_hold() {
+            let other = match &state.hold {
+                Some(hold) => Some(hold.shape.clone()),
+                None => state
+                    .bag
+                    .preview(1)
+                    .first()
+                    .map(|tetromino| tetromino.shape.clone()),
+            };
+
+            if let Some(shape) = other {
+                let mut tetromino = Tetromino::new(shape);
+                tetromino.position = tetromino.spawn_position();
+                candidates.push((tetromino, vec![Action::Hold]));
+            }
+        }
+
+        let mut best: Option<(f64, Tetromino, Vec<Action>)> = None;
+
+        for (start, prefix) in candidates {
+            for (placement, path) in Self::placements(&start, &state.map) {
+                let score = Self::evaluate(&state.map, &placement);
+
+                if best
+                    .as_ref()
+                    .is_none_or(|(best_score, ..)| score > *best_score)
+                {
+                    let mut actions = prefix.clone();
+                    actions.extend(path);
+                    best = Some((score, placement, actions));
+                }
+            }
+        }
+
+        best.map(|(_, placement, actions)| (placement, actions))
+    }
+
+    // every spot the tetromino can lock in together with the moves that get it there, found by
+    // trying every move from every position reached so far
+
+    fn placements(start: &Tetromino, map: &[[Cell; 10]; 20]) -> Vec<(Tetromino, Vec<Action>)> {
+        let mut seen = HashSet::from([(start.position, start.rotation)]);
+        let mut landed = HashSet::new();
+        let mut queue = VecDeque::from([(start.clone(), Vec::new())]);
+        let mut placements = Vec::new();
+
+        while let Some((tetromino, path)) = queue.pop_front() {
+            // a spot reached with a rotation can be a t-spin so it's kept apart from the same
+            // spot reached with a move
+
+            let lowest = lowest_avaliable_position(&tetromino, map);
+
+            if landed.insert((lowest.position, lowest.rotation, lowest.last_kick.is_some())) {
+                let mut landing_path = path.clone();
+
+                if lowest.position != tetromino.position {
+                    landing_path.push(Action::SoftDrop);
+                }
+
+                placements.push((lowest, landing_path));
+            }
+
+            for action in Self::MOVES {
+                let mut next = tetromino.clone();
+
+                let moved = match action {
+                    Action::MoveLeft => {
+                        next.left(map);
+                        next.position != tetromino.position
+                    }
+                    Action::MoveRight => {
+                        next.right(map);
+                        next.position != tetromino.position
+                    }
+                    Action::RotateClockwise => next.srs_rotate(false, map),
+                    Action::RotateCounterClockwise => next.srs_rotate(true, map),
+                    Action::Rotate180 => next.rotate_180(map),
+                    _ => {
+                        next = lowest_avaliable_position(&tetromino, map);
+                        next.position != tetromino.position
+                    }
+                };
+
+                if moved && seen.insert((next.position, next.rotation)) {
+                    let mut next_path = path.clone();
+                    next_path.push(action);
+                    queue.push_back((next, next_path));
+                }
+            }
+        }
+
+        placements
+    }
+
+    // how good the map is after locking the tetromino and clearing lines, topping out is the
+    // worst there is
+
+    fn evaluate(map: &[[Cell; 10]; 20], tetromino: &Tetromino) -> f64 {
+        let t_spin = tetromino.t_spin(map);
+        let mut map = *map;
+
+        for point in tetromino.grid.iter() {
+            let x = point[0] + tetromino.position[0];
+            let y = point[1] + tetromino.position[1];
+
+            if y < 0 {
+                return f64::MIN;
+            }
+
+            map[y as usize][x as usize] = Cell {
+                color: None,
+                occupied: true,
+            };
+        }
+
+        let mut lines = 0;
+        let mut rows: Vec<[Cell; 10]> = Vec::new();
+
+        for row in map.iter() {
+            if row.iter().all(|cell| cell.occupied) {
+                lines += 1;
+            } else {
+                rows.push(*row);
+            }
+        }
+
+        let empty_row = [Cell {
+            color: None,
+            occupied: false,
+        }; 10];
+
+        let mut cleared = [empty_row; 20];
+        cleared[lines as usize..].copy_from_slice(&rows);
+
+        let heights: Vec<u32> = (0..10)
+            .map(|x| {
+                cleared
+                    .iter()
+                    .position(|row| row[x].occupied)
+                    .map_or(0, |y| (20 - y) as u32)
+            })
+            .collect();
+
+        let holes: u32 = (0..10)
+            .map(|x| {
+                let top = 20 - heights[x] as usize;
+                cleared[top..].iter().filter(|row| !row[x].occupied).count() as u32
+            })
+            .sum();
+
+        let bumpiness: u32 = heights
+            .windows(2)
+            .map(|pair| pair[0].abs_diff(pair[1]))
+            .sum();
+
+        let aggregate_height: u32 = heights.iter().sum();
+        let max_height = heights.iter().copied().max().unwrap_or(0);
+
+        let clear_type = ClearType {
+            lines,
+            t_spin,
+            perfect_clear: lines > 0 && aggregate_height == 0,
+        };
+
+        let mut score = Self::HEIGHT_WEIGHT * aggregate_height as f64
+            + Self::LINES_WEIGHT * lines as f64
+            + Self::HOLES_WEIGHT * holes as f64
+            + Self::BUMPINESS_WEIGHT * bumpiness as f64
+            + Self::ATTACK_WEIGHT * clear_type.attack() as f64;
+
+        if clear_type.perfect_clear {
+            score += Self::PERFECT_CLEAR_BONUS;
+        }
+
+        if max_height > Self::SAFE_HEIGHT {
+            score += Self::DANGER_WEIGHT * (max_height - Self::SAFE_HEIGHT) as f64;
+        } else {
+            score += Self::T_SLOT_WEIGHT * Self::t_slots(&cleared) as f64;
+        }
+
+        score
+    }
+
+    // spots a t can spin into for a double, an empty three wide gap with a single empty cell
+    // below its middle and an overhang covering one side of it
+
+    fn t_slots(map: &[[Cell; 10]; 20]) -> u32 {
+        let occupied = |x: i32, y: i32| {
+            !(0..10).contains(&x) || y >= 20 || (y >= 0 && map[y as usize][x as usize].occupied)
+        };
+
+        let mut slots = 0;
+
+        for y in 1..19 {
+            for x in 1..9 {
+                let gap = !occupied(x - 1, y) && !occupied(x, y) && !occupied(x + 1, y);
+                let bottom =
+                    occupied(x - 1, y + 1) && !occupied(x, y + 1) && occupied(x + 1, y + 1);
+                let overhang = occupied(x - 1, y - 1) != occupied(x + 1, y - 1);
+
+                if gap && bottom && overhang && !occupied(x, y - 1) {
+                    slots += 1;
+                }
+            }
+        }
+
+        slots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{EngineConfig, TICK};
+    use crate::puzzle::Puzzle;
+    use crate::tetrominos::Shape;
+    use crate::utilities::Gamemode;
+
+    fn config(seed: u64) -> EngineConfig {
+        EngineConfig {
+            insta_das: true,
+            insta_softdrop: true,
+            repeat_delay: Duration::from_millis(100),
+            repeat_interval: Duration::ZERO,
+            soft_drop_interval: Duration::ZERO,
+            fall_interval: Duration::from_millis(1000),
+            init_level: 1,
+            seed,
+            fixed_seed: true,
+            dig_messiness: 0,
+            dig_rise_pieces: 0,
+            dig_rise_interval: Duration::ZERO,
+            puzzle: None,
+        }
+    }
+
+    fn engine(seed: u64) -> Engine {
+        Engine::new(config(seed), Gamemode::Classic)
+    }
+
+    // the tetris puzzle, but an o has to be played before the i
+
+    fn puzzle_engine(allow_hold: bool) -> Engine {
+        let mut puzzle = Puzzle::load("puzzles/01_tetris.txt").unwrap();
+        puzzle.queue = vec![Shape::O, Shape::I];
+        puzzle.allow_hold = allow_hold;
+        puzzle.piece_limit = None;
+
+        let config = EngineConfig {
+            puzzle: Some(puzzle),
+            ..config(1)
+        };
+
+        Engine::new(config, Gamemode::Puzzle)
+    }
+
+    // fill a row of the map, leaving the given columns empty
+
+    fn fill_row(engine: &mut Engine, row: usize, holes: &[usize]) {
+        for (column, cell) in engine.state.map[row].iter_mut().enumerate() {
+            *cell = Cell {
+                color: None,
+                occupied: !holes.contains(&column),
+            };
+        }
+    }
+
+    fn spawn(engine: &mut Engine, shape: Shape) {
+        let mut tetromino = Tetromino::new(shape);
+        tetromino.position = tetromino.spawn_position();
+        engine.state.current_tetromino = tetromino;
+    }
+
+    // empty cells with an occupied cell somewhere above them
+
+    fn holes(engine: &Engine) -> usize {
+        (0..10)
+            .map(|x| {
+                let map = &engine.state.map;
+                let top = map.iter().position(|row| row[x].occupied).unwrap_or(20);
+                map[top..].iter().filter(|row| !row[x].occupied).count()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn takes_a_tetris_from_a_well() {
+        let mut engine = engine(1);
+
+        for row in 15..20 {
+            fill_row(&mut engine, row, &[9]);
+        }
+
+        fill_row(&mut engine, 15, &[0, 1, 2, 3, 4, 9]);
+        spawn(&mut engine, Shape::I);
+
+        let hint = Bot::hint(&engine).unwrap();
+
+        assert!(hint.shape == Shape::I);
+        assert!(hint
+            .grid
+            .iter()
+            .all(|point| point[0] + hint.position[0] == 9));
+
+        let inputs = Bot::new(10.0).inputs(&engine);
+        engine.tick(&inputs);
+
+        assert_eq!(engine.state.lines_cleared, 4);
+    }
+
+    #[test]
+    fn only_holds_when_the_engine_allows_it() {
+        let engine = puzzle_engine(true);
+        assert!(engine.state.current_tetromino.shape == Shape::O);
+        assert!(Bot::hint(&engine).unwrap().shape == Shape::I);
+        assert!(Bot::plan(&engine).first() == Some(&Action::Hold));
+
+        let engine = puzzle_engine(false);
+        assert!(Bot::hint(&engine).unwrap().shape == Shape::O);
+        assert!(!Bot::plan(&engine).contains(&Action::Hold));
+
+        // with an empty hold and nothing left in the queue there is nothing to swap with
+
+        let mut engine = puzzle_engine(true);
+        engine.state.bag.next_tetromino();
+        assert!(Bot::hint(&engine).unwrap().shape == Shape::O);
+    }
+
+    #[test]
+    fn does_not_cover_holes() {
+        for shape in [Shape::O, Shape::S, Shape::Z, Shape::L, Shape::J, Shape::T] {
+            let mut engine = engine(1);
+
+            fill_row(&mut engine, 19, &[4]);
+            spawn(&mut engine, shape);
+
+            let inputs = Bot::new(10.0).inputs(&engine);
+            engine.tick(&inputs);
+
+            assert_eq!(engine.state.pieces_placed, 1);
+            assert_eq!(holes(&engine), 0);
+        }
+    }
+
+    // plays a long game on its own, doubles as a benchmark of how fast placements are found
+
+    #[test]
+    fn plays_a_long_game_without_topping_out() {
+        const PIECES: u32 = 200;
+
+        let mut engine = engine(7);
+        let mut bot = Bot::new(1000.0);
+
+        while engine.state.run && engine.state.pieces_placed < PIECES {
+            assert!(
+                engine.now() < TICK * 100 * PIECES,
+                "the bot stopped playing"
+            );
+
+            let inputs = bot.inputs(&engine);
+            engine.tick(&inputs);
+        }
+
+        assert!(engine.state.run);
+        assert!(engine.state.lines_cleared >= PIECES * 4 / 10 - 10);
+    }
+}
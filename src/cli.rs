@@ -0,0 +1,457 @@
+use std::path::PathBuf;
+
+use crate::utilities::{Gamemode, Settings};
+
+// the command line can skip the menu and go straight to a game, a replay or the leaderboards.
+// everything it changes about the settings only lasts for the session
+
+pub const USAGE: &str = "\
+usage: rustris [options]
+
+  --mode MODE        start a game right away, one of classic, sprint, ultra, dig, master, zen,
+                     finesse or puzzle, or a score file name like sprint40
+  --lines N          lines to clear in a sprint (40)
+  --minutes N        length of an ultra in minutes (2)
+  --garbage N        garbage lines to clear in a dig (10)
+  --puzzle FILE      puzzle to play, starts puzzle mode if no mode is given
+  --seed N           play every game of the session with this seed
+  --set KEY=VALUE    change a setting for this session only, the keys are the ones in
+                     settings.txt
+  --profile NAME     use the settings of a profile, it starts from the defaults the first time
+  --replay FILE      watch a replay
+  --scores [MODE]    print the leaderboards, or only the one of a mode, and exit
+  --data-dir DIR     keep settings, scores and replays in DIR
+  --help             print this and exit";
+
+pub enum Launch {
+    Menu,
+    Game(Gamemode),
+    Replay(String),           // path of the replay file
+    Scores(Option<Gamemode>), // every leaderboard when no mode is given
+    Help,
+}
+
+pub struct Arguments {
+    pub launch: Launch,
+    pub data_dir: Option<PathBuf>,
+    pub profile: Option<String>,
+    pub seed: Option<u64>,
+    pub puzzle: Option<String>,
+    pub overrides: Vec<(String, String)>, // settings from --set in the order they were given
+}
+
+impl Arguments {
+    // options can be followed by their value as the next argument or after an equals sign
+
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut arguments = Self {
+            launch: Launch::Menu,
+            data_dir: None,
+            profile: None,
+            seed: None,
+            puzzle: None,
+            overrides: Vec::new(),
+        };
+
+        let mut mode = None;
+        let mut lines = None;
+        let mut minutes = None;
+        let mut garbage = None;
+        let mut replay = None;
+        let mut scores = None;
+
+        let mut args = args.peekable();
+
+        while let Some(arg) = args.next() {
+            let (option, inline_value) = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with("--") => {
+                    (option.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or(format!("{} needs a value", option))
+            };
+
+            match option.as_str() {
+                "--mode" => mode = Some(value()?),
+                "--lines" => lines = Some(parse_number(&option, &value()?)?),
+                "--minutes" => minutes = Some(parse_number(&option, &value()?)?),
+                "--garbage" => garbage = Some(parse_number(&option, &value()?)?),
+                "--puzzle" => arguments.puzzle = Some(value()?),
+                "--seed" => arguments.seed = Some(parse_number(&option, &value()?)?),
+                "--replay" => replay = Some(value()?),
+                "--data-dir" => arguments.data_dir = Some(PathBuf::from(value()?)),
+                "--profile" => {
+                    let profile = value()?;
+
+                    // the name becomes a file name so it can't point anywhere else
+
+                    let valid = !profile.is_empty()
+                        && profile
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+                    if !valid {
+                        return Err(format!(
+                            "profile names can only use letters, digits, - and _, not \"{}\"",
+                            profile
+                        ));
+                    }
+
+                    arguments.profile = Some(profile);
+                }
+                "--set" => {
+                    let setting = value()?;
+                    let (key, value) = setting.split_once('=').ok_or(format!(
+                        "expected --set KEY=VALUE but found \"{}\"",
+                        setting
+                    ))?;
+
+                    arguments
+                        .overrides
+                        .push((key.trim().to_string(), value.trim().to_string()));
+                }
+
+                // the mode of --scores is optional, it's only taken if the next argument isn't
+                // another option
+                "--scores" => {
+                    let mode = match inline_value {
+                        Some(mode) => Some(mode),
+                        None => args.next_if(|next| !next.starts_with("--")),
+                    };
+
+                    scores = Some(mode);
+                }
+                "--help" | "-h" => arguments.launch = Launch::Help,
+                _ => return Err(format!("unknown option \"{}\"", arg)),
+            }
+        }
+
+        if matches!(arguments.launch, Launch::Help) {
+            return Ok(arguments);
+        }
+
+        if mode.is_none() && arguments.puzzle.is_some() {
+            mode = Some("puzzle".to_string());
+        }
+
+        if mode.is_none() && (lines.is_some() || minutes.is_some() || garbage.is_some()) {
+            return Err("--lines, --minutes and --garbage need a --mode".to_string());
+        }
+
+        let launches = [mode.is_some(), replay.is_some(), scores.is_some()];
+
+        if launches.iter().filter(|launch| **launch).count() > 1 {
+            return Err("only one of --mode, --replay and --scores can be given".to_string());
+        }
+
+        if let Some(mode) = mode {
+            let game_mode = parse_gamemode(&mode, lines, minutes, garbage)?;
+
+            if game_mode == Gamemode::Puzzle && arguments.puzzle.is_none() {
+                return Err("puzzle mode needs a --puzzle file".to_string());
+            }
+
+            arguments.launch = Launch::Game(game_mode);
+        } else if let Some(replay) = replay {
+            arguments.launch = Launch::Replay(replay);
+        } else if let Some(mode) = scores {
+            let game_mode = match mode {
+                Some(mode) => Some(parse_gamemode(&mode, None, None, None)?),
+                None => None,
+            };
+
+            arguments.launch = Launch::Scores(game_mode);
+        }
+
+        Ok(arguments)
+    }
+
+    // the settings of the profile with everything the command line changes for this session
+
+    pub fn settings(&self) -> Result<Settings, String> {
+        let mut settings = Settings::new(self.profile.as_deref());
+
+        for (key, value) in &self.overrides {
+            settings.set_for_session(key, value)?;
+        }
+
+        if self.seed.is_some() {
+            settings.seed = self.seed;
+        }
+
+        if self.puzzle.is_some() {
+            settings.puzzle = self.puzzle.clone();
+        }
+
+        Ok(settings)
+    }
+}
+
+// a mode by its plain name with the goal from the other options, or by its id like sprint40
+
+fn parse_gamemode(
+    mode: &str,
+    lines: Option<u32>,
+    minutes: Option<u32>,
+    garbage: Option<u32>,
+) -> Result<Gamemode, String> {
+    let game_mode = match mode {
+        "sprint" => Gamemode::Sprint(lines.unwrap_or(40)),
+        "ultra" => Gamemode::Ultra(minutes.unwrap_or(2)),
+        "dig" => Gamemode::Dig(garbage.unwrap_or(10)),
+        _ => Gamemode::from_id(mode).ok_or(format!("unknown mode \"{}\"", mode))?,
+    };
+
+    // an id like sprint40 already has its goal, only the plain names take one
+
+    let goal_used = match mode {
+        "sprint" => minutes.is_none() && garbage.is_none(),
+        "ultra" => lines.is_none() && garbage.is_none(),
+        "dig" => lines.is_none() && minutes.is_none(),
+        _ => lines.is_none() && minutes.is_none() && garbage.is_none(),
+    };
+
+    if !goal_used {
+        return Err(match game_mode {
+            Gamemode::Sprint(_) | Gamemode::Ultra(_) | Gamemode::Dig(_) => format!(
+                "{} already has a goal, use the mode without it to choose one",
+                mode
+            ),
+            _ => format!("{} doesn't take that goal", mode),
+        });
+    }
+
+    match game_mode {
+        Gamemode::Versus => Err("versus can only be started from the menu".to_string()),
+        Gamemode::Sprint(0) | Gamemode::Ultra(0) | Gamemode::Dig(0) => {
+            Err(format!("{} needs a goal above zero", mode))
+        }
+        _ => Ok(game_mode),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number \"{}\" for {}", value, option))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Arguments, String> {
+        Arguments::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn parse_mode(args: &[&str]) -> Result<Gamemode, String> {
+        match parse(args)?.launch {
+            Launch::Game(game_mode) => Ok(game_mode),
+            _ => Err("no game was launched".to_string()),
+        }
+    }
+
+    #[test]
+    fn no_arguments_open_the_menu() {
+        let arguments = parse(&[]).unwrap();
+
+        assert!(matches!(arguments.launch, Launch::Menu));
+        assert!(arguments.data_dir.is_none());
+        assert!(arguments.profile.is_none());
+        assert!(arguments.seed.is_none());
+        assert!(arguments.overrides.is_empty());
+    }
+
+    #[test]
+    fn modes_are_given_by_name_or_id() {
+        let cases = [
+            (vec!["--mode", "classic"], Gamemode::Classic),
+            (vec!["--mode", "sprint"], Gamemode::Sprint(40)),
+            (
+                vec!["--mode", "sprint", "--lines", "20"],
+                Gamemode::Sprint(20),
+            ),
+            (vec!["--mode=sprint", "--lines=100"], Gamemode::Sprint(100)),
+            (vec!["--mode", "sprint40"], Gamemode::Sprint(40)),
+            (vec!["--mode", "ultra"], Gamemode::Ultra(2)),
+            (
+                vec!["--mode", "ultra", "--minutes", "5"],
+                Gamemode::Ultra(5),
+            ),
+            (vec!["--mode", "ultra3"], Gamemode::Ultra(3)),
+            (vec!["--mode", "dig"], Gamemode::Dig(10)),
+            (vec!["--mode", "dig", "--garbage", "18"], Gamemode::Dig(18)),
+            (vec!["--mode", "master"], Gamemode::Master),
+            (vec!["--mode", "zen"], Gamemode::Zen),
+            (vec!["--mode", "finesse"], Gamemode::Finesse),
+        ];
+
+        for (args, expected) in cases {
+            let game_mode = parse_mode(&args).unwrap();
+
+            assert!(
+                game_mode == expected,
+                "{:?} started {}",
+                args,
+                game_mode.id()
+            );
+        }
+    }
+
+    #[test]
+    fn goals_that_dont_fit_the_mode_are_rejected() {
+        let cases = [
+            vec!["--mode", "sprint40", "--lines", "20"],
+            vec!["--mode", "ultra2", "--minutes", "5"],
+            vec!["--mode", "dig10", "--garbage", "5"],
+            vec!["--mode", "sprint", "--minutes", "5"],
+            vec!["--mode", "ultra", "--lines", "40"],
+            vec!["--mode", "classic", "--garbage", "5"],
+            vec!["--mode", "sprint", "--lines", "0"],
+            vec!["--lines", "40"],
+        ];
+
+        for args in cases {
+            assert!(parse(&args).is_err(), "{:?} was accepted", args);
+        }
+
+        assert_eq!(
+            parse(&["--mode", "sprint40", "--lines", "20"])
+                .err()
+                .unwrap(),
+            "sprint40 already has a goal, use the mode without it to choose one"
+        );
+    }
+
+    #[test]
+    fn some_modes_cant_be_launched() {
+        assert!(parse(&["--mode", "versus"]).is_err());
+        assert!(parse(&["--mode", "tetris"]).is_err());
+        assert!(parse(&["--mode", "puzzle"]).is_err());
+    }
+
+    #[test]
+    fn a_puzzle_starts_puzzle_mode() {
+        let arguments = parse(&["--puzzle", "puzzles/01_tetris.txt"]).unwrap();
+
+        assert!(matches!(arguments.launch, Launch::Game(Gamemode::Puzzle)));
+        assert_eq!(arguments.puzzle.as_deref(), Some("puzzles/01_tetris.txt"));
+    }
+
+    #[test]
+    fn session_options_are_kept() {
+        let arguments = parse(&[
+            "--seed",
+            "1234",
+            "--profile",
+            "practice_2",
+            "--data-dir",
+            "/tmp/rustris",
+            "--set",
+            "insta_das = false",
+            "--set=cpu_speed=30",
+        ])
+        .unwrap();
+
+        assert!(matches!(arguments.launch, Launch::Menu));
+        assert_eq!(arguments.seed, Some(1234));
+        assert_eq!(arguments.profile.as_deref(), Some("practice_2"));
+        assert_eq!(arguments.data_dir, Some(PathBuf::from("/tmp/rustris")));
+        assert_eq!(
+            arguments.overrides,
+            [
+                ("insta_das".to_string(), "false".to_string()),
+                ("cpu_speed".to_string(), "30".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn replays_and_scores_are_launched() {
+        let arguments = parse(&["--replay", "replay/last.txt"]).unwrap();
+        assert!(matches!(arguments.launch, Launch::Replay(path) if path == "replay/last.txt"));
+
+        let arguments = parse(&["--scores"]).unwrap();
+        assert!(matches!(arguments.launch, Launch::Scores(None)));
+
+        let arguments = parse(&["--scores", "sprint40"]).unwrap();
+        assert!(matches!(
+            arguments.launch,
+            Launch::Scores(Some(Gamemode::Sprint(40)))
+        ));
+
+        // the mode of --scores is optional so another option can follow it
+
+        let arguments = parse(&["--scores", "--data-dir", "scores"]).unwrap();
+        assert!(matches!(arguments.launch, Launch::Scores(None)));
+        assert_eq!(arguments.data_dir, Some(PathBuf::from("scores")));
+
+        assert!(parse(&["--replay", "last.txt", "--mode", "zen"]).is_err());
+        assert!(parse(&["--scores", "--mode", "zen"]).is_err());
+    }
+
+    #[test]
+    fn help_wins_over_everything_else() {
+        assert!(matches!(parse(&["--help"]).unwrap().launch, Launch::Help));
+        assert!(matches!(
+            parse(&["--mode", "zen", "-h"]).unwrap().launch,
+            Launch::Help
+        ));
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        assert_eq!(
+            parse(&["--speed", "fast"]).err().unwrap(),
+            "unknown option \"--speed\""
+        );
+        assert!(parse(&["zen"]).is_err());
+    }
+
+    #[test]
+    fn options_need_their_values() {
+        for option in [
+            "--mode",
+            "--lines",
+            "--minutes",
+            "--garbage",
+            "--puzzle",
+            "--seed",
+            "--set",
+            "--profile",
+            "--replay",
+            "--data-dir",
+        ] {
+            assert_eq!(
+                parse(&[option]).err().unwrap(),
+                format!("{} needs a value", option)
+            );
+        }
+    }
+
+    #[test]
+    fn bad_values_are_rejected() {
+        let cases = [
+            vec!["--seed", "abc"],
+            vec!["--lines", "-4"],
+            vec!["--set", "insta_das"],
+            vec!["--profile", "../other"],
+            vec!["--profile", ""],
+        ];
+
+        for args in cases {
+            assert!(parse(&args).is_err(), "{:?} was accepted", args);
+        }
+
+        assert_eq!(
+            parse(&["--seed", "abc"]).err().unwrap(),
+            "invalid number \"abc\" for --seed"
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use sdl2::controller::GameController;
+use sdl2::event::{Event, EventWatch, EventWatchCallback};
+
+// sdl only sends the buttons of controllers that have been opened. an event watch sees every
+// controller being plugged in or removed the moment it happens, whichever screen is reading the
+// events, so controllers can come and go at any time. the returned watch has to be kept alive for
+// as long as controllers should work
+
+pub fn watch(
+    sdl_context: &sdl2::Sdl,
+) -> Result<EventWatch<'static, impl EventWatchCallback>, String> {
+    let subsystem = sdl_context.game_controller()?;
+    let events = sdl_context.event()?;
+
+    // controllers that were plugged in before the game started are opened right away, sdl may
+    // still send an added event for them once the watch is in place
+
+    let mut controllers: Vec<GameController> = (0..subsystem.num_joysticks()?)
+        .filter(|index| subsystem.is_game_controller(*index))
+        .filter_map(|index| subsystem.open(index).ok())
+        .collect();
+
+    Ok(events.add_event_watch(move |event| match event {
+        Event::ControllerDeviceAdded { which, .. } => {
+            // opening a controller twice hands out the same one, the extra handle is dropped
+            // again so a single removal closes it
+
+            let Ok(controller) = subsystem.open(which) else {
+                return;
+            };
+
+            if controllers
+                .iter()
+                .all(|opened| opened.instance_id() != controller.instance_id())
+            {
+                controllers.push(controller);
+            }
+        }
+        Event::ControllerDeviceRemoved { which, .. } => {
+            controllers.retain(|controller| controller.instance_id() != which);
+        }
+        _ => {}
+    }))
+}
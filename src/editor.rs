@@ -0,0 +1,348 @@
+use sdl2::{
+    event::Event,
+    keyboard::{Mod, Scancode},
+    mouse::MouseButton,
+    rect::Rect,
+};
+
+use crate::paths;
+use crate::puzzle::{shape_name, Puzzle};
+use crate::tetrominos::{Shape, Tetromino};
+use crate::utilities::{
+    render_bg, render_text, Binding, Cell, KeyBindings, MenuInput, Theme, GARBAGE_COLOR,
+};
+
+// what the editor screen was left with
+
+pub enum EditorExit {
+    Back,
+    Play,
+}
+
+pub struct Editor<'a> {
+    font: sdl2::ttf::Font<'a, 'static>,
+    small_font: sdl2::ttf::Font<'a, 'static>,
+    canvas: &'a mut sdl2::render::Canvas<sdl2::video::Window>,
+    event_pump: &'a mut sdl2::EventPump,
+    theme: &'a Theme,
+    key_bindings: &'a KeyBindings,
+    puzzle: Puzzle,
+    brush: Option<Shape>, // none paints garbage
+    message: String,
+}
+
+impl<'a> Editor<'a> {
+    const CELL_SIZE: u32 = 40;
+    const GRID_WIDTH: u32 = 10;
+    const GRID_HEIGHT: u32 = 20;
+
+    const BRUSHES: [Option<Shape>; 8] = [
+        Some(Shape::I),
+        Some(Shape::O),
+        Some(Shape::T),
+        Some(Shape::S),
+        Some(Shape::Z),
+        Some(Shape::J),
+        Some(Shape::L),
+        None,
+    ];
+
+    // the board being edited is kept with the player's puzzles
+
+    pub fn file_path() -> String {
+        paths::data_file("puzzles/custom.txt")
+    }
+
+    pub fn new(
+        ttf_context: &'a sdl2::ttf::Sdl2TtfContext,
+        canvas: &'a mut sdl2::render::Canvas<sdl2::video::Window>,
+        event_pump: &'a mut sdl2::EventPump,
+        theme: &'a Theme,
+        key_bindings: &'a KeyBindings,
+    ) -> Result<Self, String> {
+        let font_path = paths::font();
+        let font = ttf_context.load_font(&font_path, 22)?;
+        let small_font = ttf_context.load_font(&font_path, 16)?;
+
+        // carry on with the last saved board if there is one
+
+        let file_path = Self::file_path();
+        let puzzle = Puzzle::load(&file_path).unwrap_or_else(|_| Puzzle::empty(&file_path));
+
+        Ok(Self {
+            font,
+            small_font,
+            canvas,
+            event_pump,
+            theme,
+            key_bindings,
+            puzzle,
+            brush: Some(Shape::T),
+            message: String::new(),
+        })
+    }
+
+    pub fn run(&mut self) -> EditorExit {
+        self.render();
+
+        loop {
+            let event = self.event_pump.wait_event();
+
+            if let Event::Quit { .. } = event {
+                return EditorExit::Back;
+            }
+
+            match self.key_bindings.menu_input(&event) {
+                Some(MenuInput::Back) => return EditorExit::Back,
+                Some(MenuInput::Confirm) => {
+                    // the board is saved first so the game loads it from the file
+
+                    match self.puzzle.save(&Self::file_path()) {
+                        Ok(()) => return EditorExit::Play,
+                        Err(e) => self.message = format!("Failed to save: {}", e),
+                    }
+
+                    self.render();
+                    continue;
+                }
+                _ => {}
+            }
+
+            match event {
+                Event::MouseButtonDown {
+                    mouse_btn, x, y, ..
+                } => match mouse_btn {
+                    MouseButton::Left => self.paint(x, y, true),
+                    MouseButton::Right => self.paint(x, y, false),
+                    _ => {}
+                },
+                Event::MouseMotion {
+                    mousestate, x, y, ..
+                } => {
+                    if mousestate.left() {
+                        self.paint(x, y, true);
+                    } else if mousestate.right() {
+                        self.paint(x, y, false);
+                    } else {
+                        continue;
+                    }
+                }
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    keymod,
+                    ..
+                } => self.handle_key(scancode, keymod),
+                _ => continue,
+            }
+
+            self.render();
+        }
+    }
+
+    fn handle_key(&mut self, scancode: Scancode, keymod: Mod) {
+        let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+
+        let piece = match scancode {
+            Scancode::I => Some(Shape::I),
+            Scancode::O => Some(Shape::O),
+            Scancode::T => Some(Shape::T),
+            Scancode::S => Some(Shape::S),
+            Scancode::Z => Some(Shape::Z),
+            Scancode::J => Some(Shape::J),
+            Scancode::L => Some(Shape::L),
+            _ => None,
+        };
+
+        match (scancode, piece) {
+            (Scancode::S, _) if ctrl => {
+                let file_path = Self::file_path();
+
+                self.message = match self.puzzle.save(&file_path) {
+                    Ok(()) => format!("Saved to {}", file_path),
+                    Err(e) => format!("Failed to save: {}", e),
+                };
+            }
+            (Scancode::L, _) if ctrl => {
+                let file_path = Self::file_path();
+
+                self.message = match Puzzle::load(&file_path) {
+                    Ok(puzzle) => {
+                        self.puzzle = puzzle;
+                        format!("Loaded {}", file_path)
+                    }
+                    Err(e) => format!("Failed to load: {}", e),
+                };
+            }
+            (_, Some(shape)) => self.puzzle.queue.push(shape),
+            (Scancode::Backspace, _) => {
+                self.puzzle.queue.pop();
+            }
+            (Scancode::Tab, _) => {
+                let index = Self::BRUSHES
+                    .iter()
+                    .position(|brush| *brush == self.brush)
+                    .unwrap_or(0);
+                self.brush = Self::BRUSHES[(index + 1) % Self::BRUSHES.len()].clone();
+            }
+            (Scancode::H, _) => {
+                // cycle through no hold piece and then every piece
+
+                let index = Self::BRUSHES
+                    .iter()
+                    .take(7)
+                    .position(|brush| *brush == self.puzzle.hold);
+                self.puzzle.hold = match index {
+                    None => Some(Shape::I),
+                    Some(6) => None,
+                    Some(index) => Self::BRUSHES[index + 1].clone(),
+                };
+            }
+            (Scancode::Delete, _) => {
+                self.puzzle.map = Puzzle::empty(&Self::file_path()).map;
+            }
+            _ => {}
+        }
+    }
+
+    // fill or empty the cell under the mouse, clicks outside the board are ignored
+
+    fn paint(&mut self, x: i32, y: i32, fill: bool) {
+        let box_width: u32 = Self::CELL_SIZE * Self::GRID_WIDTH;
+        let box_height: u32 = Self::CELL_SIZE * Self::GRID_HEIGHT;
+        let x_offset: i32 = ((self.canvas.window().size().0 / 2) - (box_width / 2)) as i32;
+        let y_offset: i32 = (self.canvas.window().size().1 - box_height) as i32;
+
+        if x < x_offset || y < y_offset {
+            return;
+        }
+
+        let column = ((x - x_offset) / Self::CELL_SIZE as i32) as usize;
+        let row = ((y - y_offset) / Self::CELL_SIZE as i32) as usize;
+
+        if column >= Self::GRID_WIDTH as usize || row >= Self::GRID_HEIGHT as usize {
+            return;
+        }
+
+        let color = match &self.brush {
+            Some(shape) => Tetromino::new(shape.clone()).color,
+            None => GARBAGE_COLOR,
+        };
+
+        self.puzzle.map[row][column] = Cell {
+            color: if fill { Some(color) } else { None },
+            occupied: fill,
+        };
+    }
+
+    fn render(&mut self) {
+        render_bg(
+            self.canvas,
+            self.theme.bg_color_1,
+            self.theme.bg_color_2,
+            Self::CELL_SIZE,
+            Self::GRID_WIDTH,
+            Self::GRID_HEIGHT,
+        );
+
+        let box_width: u32 = Self::CELL_SIZE * Self::GRID_WIDTH;
+        let box_height: u32 = Self::CELL_SIZE * Self::GRID_HEIGHT;
+        let x_offset: i32 = ((self.canvas.window().size().0 / 2) - (box_width / 2)) as i32;
+        let y_offset: i32 = (self.canvas.window().size().1 - box_height) as i32;
+
+        for (y, row) in self.puzzle.map.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                if cell.occupied {
+                    self.canvas.set_draw_color(cell.color.unwrap());
+
+                    let pos_x: i32 = x as i32 * Self::CELL_SIZE as i32 + x_offset;
+                    let pos_y: i32 = y as i32 * Self::CELL_SIZE as i32 + y_offset;
+
+                    let rect: Rect = Rect::new(pos_x, pos_y, Self::CELL_SIZE, Self::CELL_SIZE);
+
+                    let _ = self.canvas.fill_rect(rect);
+                }
+            }
+        }
+
+        // the brush, hold piece and queue go on the left, the controls on the right
+
+        let brush = match &self.brush {
+            Some(shape) => shape_name(shape),
+            None => "Garbage",
+        };
+
+        let queue: Vec<&str> = self.puzzle.queue.iter().map(shape_name).collect();
+
+        let lines = [
+            format!("Brush: {}", brush),
+            format!(
+                "Hold: {}",
+                self.puzzle.hold.as_ref().map_or("-", shape_name)
+            ),
+            "Queue:".to_string(),
+        ];
+
+        let mut y = 40;
+
+        for line in lines.iter() {
+            let _ = render_text(self.canvas, &self.font, self.theme.text_color, line, 40, y);
+            y += 40;
+        }
+
+        for pieces in queue.chunks(7) {
+            let _ = render_text(
+                self.canvas,
+                &self.font,
+                self.theme.text_color,
+                &pieces.join(" "),
+                40,
+                y,
+            );
+            y += 30;
+        }
+
+        let controls = [
+            "Left click: paint".to_string(),
+            "Right click: erase".to_string(),
+            "Tab: next brush".to_string(),
+            "I O T S Z J L: queue".to_string(),
+            "Backspace: remove".to_string(),
+            "H: hold piece".to_string(),
+            "Delete: clear board".to_string(),
+            "Ctrl+S: save".to_string(),
+            "Ctrl+L: load".to_string(),
+            format!(
+                "{}: play from here",
+                self.key_bindings.names(Binding::MenuConfirm)
+            ),
+            format!("{}: back", self.key_bindings.names(Binding::MenuBack)),
+        ];
+
+        let mut y = 40;
+
+        for control in controls.iter() {
+            let _ = render_text(
+                self.canvas,
+                &self.small_font,
+                self.theme.text_color,
+                control,
+                720,
+                y,
+            );
+            y += 26;
+        }
+
+        if !self.message.is_empty() {
+            let _ = render_text(
+                self.canvas,
+                &self.small_font,
+                self.theme.text_color,
+                &self.message,
+                40,
+                760,
+            );
+        }
+
+        self.canvas.present();
+    }
+}
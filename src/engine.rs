@@ -0,0 +1,2302 @@
+use crate::finesse;
+use crate::puzzle::{Puzzle, PuzzleGoal};
+use crate::tetrominos::{Bag, TSpin, Tetromino};
+use crate::utilities::{
+    has_colided, left_most_position, lowest_avaliable_position, right_most_position, Cell,
+    Gamemode, Keystate, Lockdelay, GARBAGE_COLOR,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::time::Duration;
+
+// the engine advances in fixed steps so that the same inputs on the same ticks always produce the
+// same game, no matter how fast the frontend is rendering
+
+pub const TICK: Duration = Duration::from_millis(1);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    Rotate180,
+    Hold,
+    QuickReset,
+    Undo,
+}
+
+impl Action {
+    pub const ALL: [Action; 10] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateClockwise,
+        Action::RotateCounterClockwise,
+        Action::Rotate180,
+        Action::Hold,
+        Action::QuickReset,
+        Action::Undo,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::SoftDrop => "soft_drop",
+            Action::HardDrop => "hard_drop",
+            Action::RotateClockwise => "rotate_clockwise",
+            Action::RotateCounterClockwise => "rotate_counter_clockwise",
+            Action::Rotate180 => "rotate_180",
+            Action::Hold => "hold",
+            Action::QuickReset => "quick_reset",
+            Action::Undo => "undo",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Input {
+    Press(Action),
+    Release(Action),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameEvent {
+    Moved,
+    Held,
+    PieceLocked,
+    Cleared(ClearType),
+    Reset,
+    GameOver,
+    GoalReached,
+    GarbageRose,
+    Undone,
+    StackCleared,      // zen mode topped out and carries on with an empty map
+    Attack(u32),       // garbage lines sent to the opponent after cancelling incoming garbage
+    FinesseFault(u32), // presses the locked piece took beyond the fewest it needed
+}
+
+// what a locked piece cleared, a t-spin counts even when it clears no lines
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ClearType {
+    pub lines: u32,
+    pub t_spin: TSpin,
+    pub perfect_clear: bool,
+}
+
+impl ClearType {
+    pub fn name(&self) -> String {
+        let lines = match self.lines {
+            0 => "",
+            1 => "Single",
+            2 => "Double",
+            3 => "Triple",
+            _ => "Tetris",
+        };
+
+        match self.t_spin {
+            TSpin::None => lines.to_string(),
+            TSpin::Mini => format!("Mini T-Spin {}", lines).trim().to_string(),
+            TSpin::Full => format!("T-Spin {}", lines).trim().to_string(),
+        }
+    }
+
+    // tetrises and t-spins that clear lines keep a back to back chain going
+
+    pub fn is_difficult(&self) -> bool {
+        self.lines == 4 || (self.lines > 0 && self.t_spin != TSpin::None)
+    }
+
+    // guideline score before it's multiplied by the level
+
+    pub fn score(&self) -> u32 {
+        match (self.t_spin, self.lines) {
+            (TSpin::None, 1) => 100,
+            (TSpin::None, 2) => 300,
+            (TSpin::None, 3) => 500,
+            (TSpin::None, 4) => 800,
+            (TSpin::Mini, 0) => 100,
+            (TSpin::Mini, 1) => 200,
+            (TSpin::Mini, 2) => 400,
+            (TSpin::Full, 0) => 400,
+            (TSpin::Full, 1) => 800,
+            (TSpin::Full, 2) => 1200,
+            (TSpin::Full, 3) => 1600,
+            _ => 0,
+        }
+    }
+
+    // guideline garbage sent before the back to back, combo and perfect clear bonuses
+
+    pub fn attack(&self) -> u32 {
+        match (self.t_spin, self.lines) {
+            (TSpin::None, 2) => 1,
+            (TSpin::None, 3) => 2,
+            (TSpin::None, 4) => 4,
+            (TSpin::Mini, 2) => 1,
+            (TSpin::Full, 1) => 2,
+            (TSpin::Full, 2) => 4,
+            (TSpin::Full, 3) => 6,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EngineConfig {
+    pub insta_das: bool,
+    pub insta_softdrop: bool,
+    pub repeat_delay: Duration,
+    pub repeat_interval: Duration,
+    pub soft_drop_interval: Duration,
+    pub fall_interval: Duration,
+    pub init_level: u32,
+    pub seed: u64,
+    pub fixed_seed: bool, // a fixed seed replays the same piece sequence on every reset
+    pub dig_messiness: u32, // chance in percent that the hole moves between garbage rows
+    pub dig_rise_pieces: u32, // a garbage row rises every this many pieces, 0 to turn it off
+    pub dig_rise_interval: Duration, // a garbage row rises this often, zero to turn it off
+    pub puzzle: Option<Puzzle>,
+}
+
+pub struct GameState {
+    pub run: bool,
+    pub game_mode: Gamemode,
+    pub map: [[Cell; 10]; 20],
+    pub level: u32,
+    pub bag: Bag,
+    pub current_tetromino: Tetromino,
+    pub hold: Option<Tetromino>,
+    pub score: u32,
+    pub lines_cleared: u32,
+    pub game_timer: Duration, // logical time the current game was started at
+    pub fall_timer: Duration,
+    pub fall_interval: Duration,
+    pub level_fall_interval: Duration,
+    pub is_holding: bool,
+    pub lock_delay: Lockdelay,
+    pub combo: Option<u32>, // none until a lock clears lines, then counts the following clears
+    pub back_to_back: Option<u32>, // same as the combo but for tetrises and t-spins in a row
+    pub max_combo: u32,
+    pub max_back_to_back: u32,
+    pub perfect_clears: u32,
+    pub pieces_placed: u32,
+    pub key_presses: u32,
+    pub clear_types: HashMap<ClearType, u32>, // how often each clear happened, perfect clears aside
+    pub garbage_rows: u32, // garbage rows left on the map, they are always the bottom rows
+    pub garbage_spawned: u32,
+    pub garbage_cleared: u32,
+    pub garbage_hole: usize,
+    pub garbage_timer: Duration,
+    pub garbage_rng: StdRng,
+    pub section_times: Vec<Duration>, // how long each finished section of master mode took
+    pub history: Vec<Snapshot>,
+    pub puzzle_goals_met: Vec<bool>,
+    pub incoming_garbage: Vec<u32>, // attacks waiting to rise in versus, oldest first
+    pub lines_sent: u32,
+    pub finesse_faults: u32,
+    pub piece_inputs: u32, // moves and rotations pressed since the current piece spawned
+    pub piece_soft_dropped: bool,
+}
+
+// what zen mode goes back to when the last placement is undone
+
+pub struct Snapshot {
+    pub map: [[Cell; 10]; 20],
+    pub bag: Bag,
+    pub hold: Option<Tetromino>,
+    pub current_tetromino: Tetromino,
+    pub score: u32,
+    pub lines_cleared: u32,
+    pub combo: Option<u32>,
+    pub back_to_back: Option<u32>,
+    pub max_combo: u32,
+    pub max_back_to_back: u32,
+    pub perfect_clears: u32,
+    pub pieces_placed: u32,
+    pub key_presses: u32,
+    pub clear_types: HashMap<ClearType, u32>,
+}
+
+pub struct Engine {
+    pub state: GameState,
+    pub config: EngineConfig,
+    key_states: HashMap<Action, Keystate>,
+    now: Duration,
+    events: Vec<GameEvent>,
+}
+
+impl Engine {
+    const DIG_ROWS: u32 = 10; // garbage rows kept on the map in dig mode
+    const UNDO_LIMIT: usize = 100;
+
+    // extra garbage for each clear in a combo, combos longer than the table send the last entry
+
+    const COMBO_ATTACK: [u32; 12] = [0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4, 4];
+    const COMBO_ATTACK_MAX: u32 = 5;
+    const PERFECT_CLEAR_ATTACK: u32 = 10;
+
+    const FRAME: Duration = Duration::from_nanos(16_666_667);
+    const MASTER_LAST_LEVEL: u32 = 999;
+    const MASTER_SECTION_TARGET: Duration = Duration::from_secs(45);
+
+    // level the gravity starts at and the gravity in 256ths of a row per frame
+
+    const MASTER_GRAVITY: [(u32, u32); 30] = [
+        (0, 4),
+        (30, 6),
+        (35, 8),
+        (40, 10),
+        (50, 12),
+        (60, 16),
+        (70, 32),
+        (80, 48),
+        (90, 64),
+        (100, 80),
+        (120, 96),
+        (140, 112),
+        (160, 128),
+        (170, 144),
+        (200, 4),
+        (220, 32),
+        (230, 64),
+        (233, 96),
+        (236, 128),
+        (239, 160),
+        (243, 192),
+        (247, 224),
+        (251, 256),
+        (300, 512),
+        (330, 768),
+        (360, 1024),
+        (400, 1280),
+        (420, 1024),
+        (450, 768),
+        (500, 5120),
+    ];
+
+    // level the lock delay starts at and the lock delay in frames
+
+    const MASTER_LOCK_DELAY: [(u32, u32); 5] =
+        [(0, 30), (600, 26), (700, 22), (800, 18), (900, 15)];
+
+    const MASTER_GRADES: [&'static str; 21] = [
+        "9", "8", "7", "6", "5", "4", "3", "2", "1", "S1", "S2", "S3", "S4", "S5", "S6", "S7",
+        "S8", "S9", "M", "MM", "GM",
+    ];
+
+    pub fn new(config: EngineConfig, game_mode: Gamemode) -> Self {
+        let map = [[Cell {
+            color: None,
+            occupied: false,
+        }; 10]; 20];
+        let mut bag = Bag::new(config.seed);
+        let current_tetromino = bag.next_tetromino();
+
+        let level = Self::start_level(&config, game_mode);
+
+        let lock_delay = Lockdelay {
+            lock_delay_timer: Duration::ZERO,
+            lock_delay_duration: Self::lock_delay_duration(game_mode, level),
+            is_in_delay: false,
+            moves_done: 0,
+            ammount_fallen: 0,
+        };
+
+        let level_fall_interval = Self::fall_duration(game_mode, level);
+
+        let key_states = HashMap::from([
+            (Action::MoveLeft, Keystate::default()),
+            (Action::MoveRight, Keystate::default()),
+            (Action::SoftDrop, Keystate::default()),
+        ]);
+
+        let mut engine = Engine {
+            state: GameState {
+                run: true,
+                game_mode,
+                map,
+                bag,
+                level,
+                lines_cleared: 0,
+                current_tetromino,
+                hold: None,
+                score: 0,
+                game_timer: Duration::ZERO,
+                fall_timer: Duration::ZERO,
+                is_holding: false,
+                fall_interval: config.fall_interval,
+                level_fall_interval,
+                lock_delay,
+                combo: None,
+                back_to_back: None,
+                max_combo: 0,
+                max_back_to_back: 0,
+                perfect_clears: 0,
+                pieces_placed: 0,
+                key_presses: 0,
+                clear_types: HashMap::new(),
+                garbage_rows: 0,
+                garbage_spawned: 0,
+                garbage_cleared: 0,
+                garbage_hole: 0,
+                garbage_timer: Duration::ZERO,
+                garbage_rng: StdRng::seed_from_u64(config.seed),
+                section_times: Vec::new(),
+                history: Vec::new(),
+                puzzle_goals_met: Vec::new(),
+                incoming_garbage: Vec::new(),
+                lines_sent: 0,
+                finesse_faults: 0,
+                piece_inputs: 0,
+                piece_soft_dropped: false,
+            },
+            config,
+            key_states,
+            now: Duration::ZERO,
+            events: Vec::new(),
+        };
+
+        if let Gamemode::Dig(goal) = game_mode {
+            engine.fill_garbage(goal);
+        }
+
+        engine.setup_puzzle();
+
+        engine
+    }
+
+    // current logical time of the engine
+
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    // number of ticks the engine has advanced since it was created
+
+    pub fn ticks(&self) -> u64 {
+        (self.now.as_nanos() / TICK.as_nanos()) as u64
+    }
+
+    // time that has transpired since the current game was started
+
+    pub fn elapsed(&self) -> Duration {
+        self.now - self.state.game_timer
+    }
+
+    // advance the simulation by one tick, applying the inputs first, and return everything that
+    // happened during it
+
+    pub fn tick(&mut self, inputs: &[Input]) -> Vec<GameEvent> {
+        self.now += TICK;
+
+        if self.state.run {
+            self.update(inputs);
+        } else if inputs.contains(&Input::Press(Action::QuickReset)) {
+            // a finished game can still be started over
+            self.quick_reset_game();
+        }
+
+        std::mem::take(&mut self.events)
+    }
+
+    fn update(&mut self, inputs: &[Input]) {
+        // timed modes end as soon as the time is up, before anything else happens in the tick
+
+        if let Some(time_limit) = self.state.game_mode.time_limit() {
+            self.check_time_limit_game_over_state(time_limit);
+
+            if !self.state.run {
+                return;
+            }
+        }
+
+        let moved = self.handle_input(inputs);
+
+        if !self.state.run {
+            return;
+        }
+
+        if let Gamemode::Dig(goal) = self.state.game_mode {
+            let interval = self.config.dig_rise_interval;
+
+            if !interval.is_zero() && self.now - self.state.garbage_timer >= interval {
+                self.state.garbage_timer = self.now;
+                self.rise_garbage(goal);
+            }
+
+            if !self.state.run {
+                return;
+            }
+        }
+
+        // store the position after input so a fall can be detected for the lock delay
+
+        let previous_position = self.state.current_tetromino.position;
+
+        if self.now - self.state.fall_timer >= self.state.fall_interval && !self.is_against_stack()
+        {
+            if self.state.fall_interval.is_zero() {
+                // at 20g the tetromino drops straight onto the stack
+
+                let lowest =
+                    lowest_avaliable_position(&self.state.current_tetromino, &self.state.map);
+                self.state.current_tetromino.move_to(lowest.position);
+            } else {
+                self.state.current_tetromino.fall();
+            }
+            self.events.push(GameEvent::Moved);
+            self.state.fall_timer = self.now;
+        }
+
+        // set the lock delay timer here if the tetromino is touching the ground
+
+        if self.is_against_stack() && !self.state.lock_delay.is_in_delay {
+            self.state.lock_delay.is_in_delay = true;
+            self.state.lock_delay.lock_delay_timer = self.now;
+        }
+
+        // if in lock delay and the tetromino has moved than increase the move counter
+
+        let current_position = self.state.current_tetromino.position;
+
+        if (moved || previous_position != current_position)
+            && self.state.lock_delay.is_in_delay
+            && self.state.lock_delay.moves_done < 15
+        {
+            self.state.lock_delay.moves_done += 1;
+            self.state.lock_delay.lock_delay_timer = self.now;
+            self.state.fall_timer = self.now;
+
+            // if the y position is lager then increase the y position and restart the lock delay
+            // if the tetromino has fallen more than 3 spaces.
+
+            if previous_position[1] < current_position[1] {
+                if let Some(new_value) = self.state.lock_delay.ammount_fallen.checked_add(1) {
+                    self.state.lock_delay.ammount_fallen = new_value;
+                } else {
+                    self.state.lock_delay.ammount_fallen = 4;
+                }
+
+                if self.state.lock_delay.ammount_fallen > 3 {
+                    self.state.lock_delay.is_in_delay = false;
+                    self.state.lock_delay.moves_done = 0;
+                }
+            }
+        }
+
+        // if in lock delay check if the timer has surpassed and the tetromino is on the stack
+        // then set the tetromino
+
+        let lock_delay = &self.state.lock_delay;
+
+        if lock_delay.is_in_delay
+            && self.now - lock_delay.lock_delay_timer >= lock_delay.lock_delay_duration
+            && self.is_against_stack()
+        {
+            self.set_tetromino();
+        }
+    }
+
+    fn handle_input(&mut self, inputs: &[Input]) -> bool {
+        let mut moved: bool = false;
+
+        for input in inputs {
+            if !self.state.run {
+                break;
+            }
+
+            if let Input::Press(action) = input {
+                if *action != Action::QuickReset && *action != Action::Undo {
+                    self.state.key_presses += 1;
+                }
+
+                match action {
+                    Action::MoveLeft
+                    | Action::MoveRight
+                    | Action::RotateClockwise
+                    | Action::RotateCounterClockwise
+                    | Action::Rotate180 => self.state.piece_inputs += 1,
+                    Action::SoftDrop => self.state.piece_soft_dropped = true,
+                    _ => {}
+                }
+            }
+
+            match *input {
+                Input::Press(action) => match action {
+                    Action::MoveLeft => {
+                        self.press_key(action);
+                        self.state.current_tetromino.left(&self.state.map);
+                        moved = true;
+                    }
+                    Action::MoveRight => {
+                        self.press_key(action);
+                        self.state.current_tetromino.right(&self.state.map);
+                        moved = true;
+                    }
+                    Action::SoftDrop => {
+                        if self.config.insta_softdrop {
+                            self.state.current_tetromino = lowest_avaliable_position(
+                                &self.state.current_tetromino,
+                                &self.state.map,
+                            );
+                            moved = true;
+                            self.state.fall_timer = self.now;
+                        } else {
+                            self.press_key(action);
+                        }
+                    }
+                    Action::HardDrop => {
+                        self.hard_drop();
+                    }
+                    Action::RotateClockwise => {
+                        if self
+                            .state
+                            .current_tetromino
+                            .srs_rotate(false, &self.state.map)
+                        {
+                            moved = true;
+                        }
+                    }
+                    Action::RotateCounterClockwise => {
+                        if self
+                            .state
+                            .current_tetromino
+                            .srs_rotate(true, &self.state.map)
+                        {
+                            moved = true;
+                        }
+                    }
+                    Action::Rotate180 => {
+                        if self.state.current_tetromino.rotate_180(&self.state.map) {
+                            moved = true;
+                        }
+                    }
+                    Action::Hold => {
+                        self.switch_hold_tetromino();
+                    }
+                    Action::QuickReset => {
+                        self.quick_reset_game();
+                    }
+                    Action::Undo => {
+                        self.undo();
+                    }
+                },
+                Input::Release(action) => {
+                    if let Some(state) = self.key_states.get_mut(&action) {
+                        state.is_pressed = false;
+                    }
+                }
+            }
+        }
+
+        if !self.state.run {
+            return moved;
+        }
+
+        let now = self.now;
+        let repeat_delay = self.config.repeat_delay;
+        let repeat_interval = self.config.repeat_interval;
+
+        let left = &self.key_states[&Action::MoveLeft];
+        let right = &self.key_states[&Action::MoveRight];
+
+        if left.is_pressed && !right.is_pressed {
+            let time_since_first_press = now - left.first_press_time;
+            let time_since_last_repeat = now - left.last_repeat_time;
+
+            if time_since_first_press >= repeat_delay {
+                if self.config.insta_das {
+                    let current_tetromino = &mut self.state.current_tetromino;
+                    let new_position = left_most_position(current_tetromino, &self.state.map);
+                    current_tetromino.move_to([new_position.0, new_position.1]);
+                    moved = true;
+                } else if time_since_last_repeat >= repeat_interval {
+                    self.state.current_tetromino.left(&self.state.map);
+                    self.key_states
+                        .get_mut(&Action::MoveLeft)
+                        .unwrap()
+                        .last_repeat_time = now;
+                    moved = true;
+                }
+            }
+        }
+
+        let left = &self.key_states[&Action::MoveLeft];
+        let right = &self.key_states[&Action::MoveRight];
+
+        if right.is_pressed && !left.is_pressed {
+            let time_since_first_press = now - right.first_press_time;
+            let time_since_last_repeat = now - right.last_repeat_time;
+
+            if time_since_first_press >= repeat_delay {
+                if self.config.insta_das {
+                    let current_tetromino = &mut self.state.current_tetromino;
+                    let new_position = right_most_position(current_tetromino, &self.state.map);
+                    current_tetromino.move_to([new_position.0, new_position.1]);
+                    moved = true;
+                } else if time_since_last_repeat >= repeat_interval {
+                    self.state.current_tetromino.right(&self.state.map);
+                    self.key_states
+                        .get_mut(&Action::MoveRight)
+                        .unwrap()
+                        .last_repeat_time = now;
+                    moved = true;
+                }
+            }
+        }
+
+        let soft_drop = &self.key_states[&Action::SoftDrop];
+
+        if soft_drop.is_pressed {
+            let time_since_first_press = now - soft_drop.first_press_time;
+            let time_since_last_repeat = now - soft_drop.last_repeat_time;
+
+            if time_since_first_press >= repeat_delay
+                && time_since_last_repeat >= repeat_interval
+                && self.config.soft_drop_interval < self.state.fall_interval
+            {
+                self.state.fall_interval = self.config.soft_drop_interval;
+            }
+        } else {
+            self.state.fall_interval = self.state.level_fall_interval;
+        }
+
+        if moved {
+            self.events.push(GameEvent::Moved);
+        }
+
+        moved
+    }
+
+    fn press_key(&mut self, action: Action) {
+        let now = self.now;
+
+        if let Some(key_state) = self.key_states.get_mut(&action) {
+            key_state.is_pressed = true;
+            key_state.first_press_time = now;
+        }
+    }
+
+    fn is_against_stack(&self) -> bool {
+        let current_tetromino = &self.state.current_tetromino;
+
+        has_colided(
+            &current_tetromino.grid,
+            &(
+                current_tetromino.position[0],
+                current_tetromino.position[1] + 1,
+            ),
+            &self.state.map,
+        )
+    }
+
+    fn set_tetromino(&mut self) {
+        // zen mode keeps what the game looked like before every placement so it can be undone
+
+        if let Gamemode::Zen = self.state.game_mode {
+            self.save_snapshot();
+        }
+
+        let current_tetromino = &self.state.current_tetromino;
+
+        // t-spins are detected before the tetromino becomes part of the map
+
+        let t_spin = current_tetromino.t_spin(&self.state.map);
+
+        for point in current_tetromino.grid.iter() {
+            let pos_x = point[0] + current_tetromino.position[0];
+            let pos_y = point[1] + current_tetromino.position[1];
+
+            // check for game over state
+
+            if pos_y < 0 {
+                if let Gamemode::Zen = self.state.game_mode {
+                    self.clear_stack();
+                    return;
+                }
+
+                self.state.run = false;
+                self.events.push(GameEvent::GameOver);
+                return;
+            }
+
+            self.state.map[pos_y as usize][pos_x as usize] = Cell {
+                color: Some(current_tetromino.color),
+                occupied: true,
+            };
+        }
+
+        self.events.push(GameEvent::PieceLocked);
+        self.state.pieces_placed += 1;
+
+        let finesse_faults = self.check_finesse();
+
+        let lines = self.clear_lines();
+        let perfect_clear = lines > 0 && self.is_map_empty();
+
+        let clear_type = ClearType {
+            lines,
+            t_spin,
+            perfect_clear,
+        };
+
+        self.score_clear(clear_type);
+
+        // master levels go up with every lock, not only the ones that clear lines
+
+        if let Gamemode::Master = self.state.game_mode {
+            self.advance_master_level(lines);
+            self.apply_level_speed();
+        }
+
+        // a puzzle is judged after every lock, one that clears nothing can still use up the last
+        // piece
+
+        if let Gamemode::Puzzle = self.state.game_mode {
+            self.check_puzzle_state(clear_type);
+        }
+
+        // nothing comes next once this lock ended the game
+
+        if !self.state.run {
+            return;
+        }
+
+        // finesse training starts over as soon as a piece took too many presses
+
+        if self.state.game_mode == Gamemode::Finesse && finesse_faults > 0 {
+            self.quick_reset_game();
+            return;
+        }
+
+        // a puzzle that ran out of pieces in its queue carries on with the held piece, and is
+        // failed once there is none
+
+        if !self.state.bag.is_empty() {
+            self.state.current_tetromino = self.state.bag.next_tetromino();
+        } else if let Some(held) = self.state.hold.take() {
+            let mut held = Tetromino::new(held.shape);
+            held.position = held.spawn_position();
+            self.state.current_tetromino = held;
+        } else {
+            self.state.run = false;
+            self.events.push(GameEvent::GameOver);
+            return;
+        }
+        self.state.is_holding = false;
+        self.reset_piece_inputs();
+
+        self.state.fall_timer = self.now;
+
+        // reset lock delay
+        self.state.lock_delay.is_in_delay = false;
+        self.state.lock_delay.moves_done = 0;
+
+        if let (Gamemode::Dig(goal), true) = (self.state.game_mode, self.state.run) {
+            self.lock_garbage(goal);
+        }
+
+        // incoming garbage only rises after a lock that didn't clear anything
+
+        if let (Gamemode::Versus, 0) = (self.state.game_mode, lines) {
+            self.rise_incoming_garbage();
+        }
+    }
+
+    // compare the presses the locked piece took with the fewest it needed and return how many
+    // were too many. a soft dropped piece might have been tucked or spun in so it isn't judged
+
+    fn check_finesse(&mut self) -> u32 {
+        if self.state.piece_soft_dropped {
+            return 0;
+        }
+
+        let Some(optimal) = finesse::optimal_inputs(&self.state.current_tetromino) else {
+            return 0;
+        };
+
+        let faults = self.state.piece_inputs.saturating_sub(optimal);
+
+        if faults > 0 {
+            self.state.finesse_faults += faults;
+            self.events.push(GameEvent::FinesseFault(faults));
+        }
+
+        faults
+    }
+
+    // a new piece starts with a clean count of presses
+
+    fn reset_piece_inputs(&mut self) {
+        self.state.piece_inputs = 0;
+        self.state.piece_soft_dropped = false;
+    }
+
+    fn hard_drop(&mut self) {
+        let current_tetromino =
+            lowest_avaliable_position(&self.state.current_tetromino, &self.state.map);
+
+        self.state.current_tetromino = current_tetromino;
+        self.set_tetromino();
+    }
+
+    // remove every full row from the map and return how many there were
+
+    fn clear_lines(&mut self) -> u32 {
+        let mut ammount_lines = 0;
+        let mut garbage_lines = 0;
+        let garbage_start = self.state.map.len() - self.state.garbage_rows as usize;
+
+        // iterate in revrse over the map and shift rows down by the ammount of full lines found
+        // so far
+
+        for row_index in (0..self.state.map.len()).rev() {
+            let all_occupied: bool = self.state.map[row_index].iter().all(|cell| cell.occupied);
+
+            if all_occupied {
+                ammount_lines += 1;
+
+                if row_index >= garbage_start {
+                    garbage_lines += 1;
+                }
+            } else if ammount_lines > 0 {
+                self.state.map[row_index + ammount_lines] = self.state.map[row_index];
+            }
+        }
+
+        // set blank lines at the top where the rows have been shifted down from
+
+        for row in self.state.map.iter_mut().take(ammount_lines) {
+            *row = [Cell {
+                occupied: false,
+                color: None,
+            }; 10];
+        }
+
+        self.state.garbage_rows -= garbage_lines;
+        self.state.garbage_cleared += garbage_lines;
+
+        ammount_lines as u32
+    }
+
+    fn is_map_empty(&self) -> bool {
+        self.state
+            .map
+            .iter()
+            .all(|row| row.iter().all(|cell| !cell.occupied))
+    }
+
+    fn score_clear(&mut self, clear_type: ClearType) {
+        // a lock without lines breaks the combo, a t-spin without lines keeps the back to back
+
+        if clear_type.lines == 0 {
+            self.state.combo = None;
+
+            if clear_type.t_spin == TSpin::None {
+                return;
+            }
+        } else {
+            self.state.combo = Some(self.state.combo.map_or(0, |combo| combo + 1));
+
+            self.state.back_to_back = if clear_type.is_difficult() {
+                Some(self.state.back_to_back.map_or(0, |chain| chain + 1))
+            } else {
+                None
+            };
+        }
+
+        self.state.max_combo = self.state.max_combo.max(self.state.combo.unwrap_or(0));
+        self.state.max_back_to_back = self
+            .state
+            .max_back_to_back
+            .max(self.state.back_to_back.unwrap_or(0));
+
+        self.state.lines_cleared += clear_type.lines;
+        self.events.push(GameEvent::Cleared(clear_type));
+
+        let breakdown_type = ClearType {
+            perfect_clear: false,
+            ..clear_type
+        };
+        *self.state.clear_types.entry(breakdown_type).or_insert(0) += 1;
+
+        let level = self.state.level;
+        let mut score = clear_type.score() * level;
+
+        if clear_type.is_difficult() && self.state.back_to_back.unwrap_or(0) > 0 {
+            score = score * 3 / 2;
+        }
+
+        if clear_type.lines > 0 {
+            score += 50 * self.state.combo.unwrap_or(0) * level;
+        }
+
+        if clear_type.perfect_clear {
+            self.state.perfect_clears += 1;
+
+            let back_to_back = self.state.back_to_back.unwrap_or(0) > 0;
+
+            score += match clear_type.lines {
+                1 => 800,
+                2 => 1200,
+                3 => 1800,
+                _ if back_to_back => 3200,
+                _ => 2000,
+            } * level;
+        }
+
+        match self.state.game_mode {
+            Gamemode::Classic => {
+                self.state.score += score;
+                self.set_level();
+            }
+            Gamemode::Sprint(goal) => {
+                self.check_sprint_game_over_state(goal);
+            }
+            Gamemode::Ultra(_) | Gamemode::Zen => {
+                self.state.score += score;
+            }
+            Gamemode::Finesse => {}
+            Gamemode::Dig(goal) => {
+                self.check_dig_game_over_state(goal);
+            }
+            Gamemode::Master => {}
+            Gamemode::Puzzle => {
+                self.state.score += score;
+            }
+            Gamemode::Versus => {
+                self.state.score += score;
+                self.send_attack(clear_type);
+            }
+        }
+
+        self.apply_level_speed();
+    }
+
+    // gravity and lock delay for the current level
+
+    fn apply_level_speed(&mut self) {
+        let game_mode = self.state.game_mode;
+        let level = self.state.level;
+
+        self.state.level_fall_interval = Self::fall_duration(game_mode, level);
+        self.state.lock_delay.lock_delay_duration = Self::lock_delay_duration(game_mode, level);
+    }
+
+    fn start_level(config: &EngineConfig, game_mode: Gamemode) -> u32 {
+        match game_mode {
+            Gamemode::Master => 0,
+            _ => config.init_level,
+        }
+    }
+
+    fn fall_duration(game_mode: Gamemode, level: u32) -> Duration {
+        match game_mode {
+            Gamemode::Master => Self::master_fall_duration(level),
+            _ => Self::calculate_fall_duration(level),
+        }
+    }
+
+    fn lock_delay_duration(game_mode: Gamemode, level: u32) -> Duration {
+        let frames = match game_mode {
+            Gamemode::Master => Self::MASTER_LOCK_DELAY
+                .iter()
+                .rev()
+                .find(|(from_level, _)| level >= *from_level)
+                .map_or(30, |(_, frames)| *frames),
+            _ => 30,
+        };
+
+        Self::FRAME * frames
+    }
+
+    // every locked piece moves the level up by one, except at the end of a section where only
+    // clearing lines gets past it
+
+    fn advance_master_level(&mut self, lines: u32) {
+        let level = self.state.level;
+        let section_stop = (level / 100 * 100 + 99).min(Self::MASTER_LAST_LEVEL - 1);
+        let new_level =
+            ((level + 1).min(section_stop).max(level) + lines).min(Self::MASTER_LAST_LEVEL);
+
+        if new_level / 100 > level / 100 || new_level == Self::MASTER_LAST_LEVEL {
+            let previous_sections: Duration = self.state.section_times.iter().sum();
+            self.state
+                .section_times
+                .push(self.elapsed() - previous_sections);
+        }
+
+        self.state.level = new_level;
+
+        if new_level == Self::MASTER_LAST_LEVEL {
+            self.state.run = false;
+            self.events.push(GameEvent::GoalReached);
+        }
+    }
+
+    // each finished section counts towards the grade, and counts twice if it was fast enough
+
+    pub fn master_grade(&self) -> &'static str {
+        let sections = self.state.section_times.len();
+        let fast_sections = self
+            .state
+            .section_times
+            .iter()
+            .filter(|time| **time <= Self::MASTER_SECTION_TARGET)
+            .count();
+
+        Self::MASTER_GRADES[(sections + fast_sections).min(Self::MASTER_GRADES.len() - 1)]
+    }
+
+    // master gravity ramps up in steps and ends up at 20g, which is returned as zero
+
+    pub fn master_fall_duration(level: u32) -> Duration {
+        let gravity = Self::MASTER_GRAVITY
+            .iter()
+            .rev()
+            .find(|(from_level, _)| level >= *from_level)
+            .map_or(4, |(_, gravity)| *gravity);
+
+        if gravity >= 20 * 256 {
+            Duration::ZERO
+        } else {
+            Self::FRAME * 256 / gravity
+        }
+    }
+
+    fn set_level(&mut self) {
+        if let Gamemode::Classic = self.state.game_mode {
+            self.state.level = ((self.state.lines_cleared / 10) + 1).max(self.config.init_level);
+        }
+    }
+
+    // puzzles can turn holding off, and holding with nothing in the hold needs a next piece
+
+    pub fn can_hold(&self) -> bool {
+        if let Some(puzzle) = &self.config.puzzle {
+            if !puzzle.allow_hold || (self.state.hold.is_none() && self.state.bag.is_empty()) {
+                return false;
+            }
+        }
+
+        !self.state.is_holding
+    }
+
+    fn switch_hold_tetromino(&mut self) {
+        if self.can_hold() {
+            if self.state.hold.is_none() {
+                let current_tetromino = &self.state.current_tetromino;
+                let hold_tetromino = Tetromino::new(current_tetromino.shape.clone());
+
+                self.state.hold = Some(hold_tetromino);
+                self.state.current_tetromino = self.state.bag.next_tetromino();
+            } else {
+                let current_tetromino = &self.state.current_tetromino;
+                let new_hold_tetromino = Tetromino::new(current_tetromino.shape.clone());
+                let mut new_current_tetromino =
+                    Tetromino::new(self.state.hold.as_ref().unwrap().shape.clone());
+
+                new_current_tetromino.position = new_current_tetromino.spawn_position();
+
+                self.state.hold = Some(new_hold_tetromino);
+                self.state.current_tetromino = new_current_tetromino;
+            }
+            self.state.is_holding = true;
+            self.reset_piece_inputs();
+
+            self.events.push(GameEvent::Held);
+        }
+    }
+
+    fn check_sprint_game_over_state(&mut self, goal: u32) {
+        if self.state.lines_cleared >= goal {
+            self.state.run = false;
+            self.events.push(GameEvent::GoalReached);
+        }
+    }
+
+    fn check_dig_game_over_state(&mut self, goal: u32) {
+        if self.state.garbage_cleared >= goal {
+            self.state.run = false;
+            self.events.push(GameEvent::GoalReached);
+        }
+    }
+
+    // keep the map topped up with garbage until every row of the goal has been sent up
+
+    fn fill_garbage(&mut self, goal: u32) {
+        let remaining = goal - self.state.garbage_spawned;
+        let target = Self::DIG_ROWS.min(remaining + self.state.garbage_rows);
+
+        while self.state.run && self.state.garbage_rows < target {
+            self.add_dig_garbage_row();
+        }
+    }
+
+    // garbage that comes up after a lock, every few pieces if set and then enough to refill the
+    // map
+
+    fn lock_garbage(&mut self, goal: u32) {
+        let pieces = self.config.dig_rise_pieces;
+
+        if pieces > 0 && self.state.pieces_placed.is_multiple_of(pieces) {
+            self.rise_garbage(goal);
+        }
+
+        self.fill_garbage(goal);
+    }
+
+    fn rise_garbage(&mut self, goal: u32) {
+        if self.state.garbage_spawned < goal {
+            self.add_dig_garbage_row();
+        }
+    }
+
+    // queue up garbage sent by the opponent, it rises the next time a piece locks without
+    // clearing lines
+
+    pub fn receive_garbage(&mut self, lines: u32) {
+        if lines > 0 && self.state.run {
+            self.state.incoming_garbage.push(lines);
+        }
+    }
+
+    pub fn incoming_garbage(&self) -> u32 {
+        self.state.incoming_garbage.iter().sum()
+    }
+
+    // work out the guideline attack of a clear, cancel incoming garbage with it first and send
+    // whatever is left to the opponent
+
+    fn send_attack(&mut self, clear_type: ClearType) {
+        if clear_type.lines == 0 {
+            return;
+        }
+
+        let mut attack = clear_type.attack();
+
+        if clear_type.is_difficult() && self.state.back_to_back.unwrap_or(0) > 0 {
+            attack += 1;
+        }
+
+        let combo = self.state.combo.unwrap_or(0) as usize;
+        attack += Self::COMBO_ATTACK
+            .get(combo)
+            .copied()
+            .unwrap_or(Self::COMBO_ATTACK_MAX);
+
+        if clear_type.perfect_clear {
+            attack += Self::PERFECT_CLEAR_ATTACK;
+        }
+
+        while attack > 0 && !self.state.incoming_garbage.is_empty() {
+            let cancelled = attack.min(self.state.incoming_garbage[0]);
+
+            attack -= cancelled;
+            self.state.incoming_garbage[0] -= cancelled;
+
+            if self.state.incoming_garbage[0] == 0 {
+                self.state.incoming_garbage.remove(0);
+            }
+        }
+
+        if attack > 0 {
+            self.state.lines_sent += attack;
+            self.events.push(GameEvent::Attack(attack));
+        }
+    }
+
+    // every attack rises as its own block of rows sharing one hole
+
+    fn rise_incoming_garbage(&mut self) {
+        let width = self.state.map[0].len();
+
+        for lines in std::mem::take(&mut self.state.incoming_garbage) {
+            self.state.garbage_hole = self.state.garbage_rng.gen_range(0..width);
+
+            for _ in 0..lines {
+                if !self.state.run {
+                    return;
+                }
+
+                self.add_garbage_row();
+            }
+        }
+    }
+
+    // the hole only moves as often as the messiness says, and never back onto the same column
+
+    fn add_dig_garbage_row(&mut self) {
+        let width = self.state.map[0].len();
+
+        if self.state.garbage_spawned == 0 {
+            self.state.garbage_hole = self.state.garbage_rng.gen_range(0..width);
+        } else if self.state.garbage_rng.gen_range(0..100) < self.config.dig_messiness {
+            let offset = self.state.garbage_rng.gen_range(1..width);
+            self.state.garbage_hole = (self.state.garbage_hole + offset) % width;
+        }
+
+        self.add_garbage_row();
+    }
+
+    // push the map up by one row and put a garbage row with a single hole at the bottom
+
+    fn add_garbage_row(&mut self) {
+        if self.state.map[0].iter().any(|cell| cell.occupied) {
+            self.state.run = false;
+            self.events.push(GameEvent::GameOver);
+            return;
+        }
+
+        self.state.map.rotate_left(1);
+
+        let hole = self.state.garbage_hole;
+        let last_row = self.state.map.len() - 1;
+
+        for (index, cell) in self.state.map[last_row].iter_mut().enumerate() {
+            *cell = if index == hole {
+                Cell {
+                    color: None,
+                    occupied: false,
+                }
+            } else {
+                Cell {
+                    color: Some(GARBAGE_COLOR),
+                    occupied: true,
+                }
+            };
+        }
+
+        self.state.garbage_rows += 1;
+        self.state.garbage_spawned += 1;
+
+        // the falling tetromino is pushed up with the stack if it is in the way
+
+        let tetromino = &self.state.current_tetromino;
+
+        if has_colided(
+            &tetromino.grid,
+            &(tetromino.position[0], tetromino.position[1]),
+            &self.state.map,
+        ) {
+            let mut position = tetromino.position;
+            position[1] -= 1;
+            self.state.current_tetromino.move_to(position);
+        }
+
+        self.events.push(GameEvent::GarbageRose);
+    }
+
+    fn save_snapshot(&mut self) {
+        let mut current_tetromino = Tetromino::new(self.state.current_tetromino.shape.clone());
+        current_tetromino.position = current_tetromino.spawn_position();
+
+        self.state.history.push(Snapshot {
+            map: self.state.map,
+            bag: self.state.bag.clone(),
+            hold: self.state.hold.clone(),
+            current_tetromino,
+            score: self.state.score,
+            lines_cleared: self.state.lines_cleared,
+            combo: self.state.combo,
+            back_to_back: self.state.back_to_back,
+            max_combo: self.state.max_combo,
+            max_back_to_back: self.state.max_back_to_back,
+            perfect_clears: self.state.perfect_clears,
+            pieces_placed: self.state.pieces_placed,
+            key_presses: self.state.key_presses,
+            clear_types: self.state.clear_types.clone(),
+        });
+
+        if self.state.history.len() > Self::UNDO_LIMIT {
+            self.state.history.remove(0);
+        }
+    }
+
+    // put everything back the way it was before the last placement in zen mode
+
+    fn undo(&mut self) {
+        if self.state.game_mode != Gamemode::Zen {
+            return;
+        }
+
+        let Some(snapshot) = self.state.history.pop() else {
+            return;
+        };
+
+        self.state.map = snapshot.map;
+        self.state.bag = snapshot.bag;
+        self.state.hold = snapshot.hold;
+        self.state.current_tetromino = snapshot.current_tetromino;
+        self.state.score = snapshot.score;
+        self.state.lines_cleared = snapshot.lines_cleared;
+        self.state.combo = snapshot.combo;
+        self.state.back_to_back = snapshot.back_to_back;
+        self.state.max_combo = snapshot.max_combo;
+        self.state.max_back_to_back = snapshot.max_back_to_back;
+        self.state.perfect_clears = snapshot.perfect_clears;
+        self.state.pieces_placed = snapshot.pieces_placed;
+        self.state.key_presses = snapshot.key_presses;
+        self.state.clear_types = snapshot.clear_types;
+        self.state.is_holding = false;
+        self.reset_piece_inputs();
+
+        self.state.fall_timer = self.now;
+        self.state.lock_delay.is_in_delay = false;
+        self.state.lock_delay.moves_done = 0;
+        self.state.lock_delay.ammount_fallen = 0;
+
+        self.events.push(GameEvent::Undone);
+    }
+
+    // zen mode never ends, topping out just clears the stack and play goes on
+
+    fn clear_stack(&mut self) {
+        self.state.map = [[Cell {
+            color: None,
+            occupied: false,
+        }; 10]; 20];
+
+        self.state.current_tetromino = self.state.bag.next_tetromino();
+        self.state.is_holding = false;
+        self.state.combo = None;
+        self.reset_piece_inputs();
+
+        self.state.fall_timer = self.now;
+        self.state.lock_delay.is_in_delay = false;
+        self.state.lock_delay.moves_done = 0;
+
+        self.events.push(GameEvent::StackCleared);
+    }
+
+    // start the map, queue and hold from the puzzle, if there is one
+
+    fn setup_puzzle(&mut self) {
+        let Some(puzzle) = &self.config.puzzle else {
+            return;
+        };
+
+        self.state.map = puzzle.map;
+        self.state.bag = if puzzle.goals.is_empty() {
+            Bag::starting_with(&puzzle.queue, self.state.bag.seed)
+        } else {
+            Bag::fixed(&puzzle.queue)
+        };
+        self.state.current_tetromino = self.state.bag.next_tetromino();
+        self.state.hold = puzzle.hold.clone().map(Tetromino::new);
+        self.state.puzzle_goals_met = vec![false; puzzle.goals.len()];
+    }
+
+    // a puzzle is solved once every goal has been met and failed when it runs out of pieces
+    // before that
+
+    fn check_puzzle_state(&mut self, clear_type: ClearType) {
+        let Some(puzzle) = &self.config.puzzle else {
+            return;
+        };
+
+        // a starting position without a goal plays on until it tops out
+
+        if puzzle.goals.is_empty() {
+            return;
+        }
+
+        for (goal, met) in puzzle
+            .goals
+            .iter()
+            .zip(self.state.puzzle_goals_met.iter_mut())
+        {
+            *met |= match goal {
+                PuzzleGoal::Lines(lines) => self.state.lines_cleared >= *lines,
+                PuzzleGoal::PerfectClear => clear_type.perfect_clear,
+                PuzzleGoal::Clear(goal_clear) => {
+                    clear_type.lines == goal_clear.lines && clear_type.t_spin == goal_clear.t_spin
+                }
+            };
+        }
+
+        let out_of_pieces = puzzle
+            .piece_limit
+            .is_some_and(|limit| self.state.pieces_placed >= limit)
+            || (self.state.bag.is_empty() && self.state.hold.is_none());
+
+        if self.state.puzzle_goals_met.iter().all(|met| *met) {
+            self.state.run = false;
+            self.events.push(GameEvent::GoalReached);
+        } else if out_of_pieces {
+            self.state.run = false;
+            self.events.push(GameEvent::GameOver);
+        }
+    }
+
+    fn check_time_limit_game_over_state(&mut self, time_limit: Duration) {
+        if self.elapsed() >= time_limit {
+            self.state.run = false;
+            self.events.push(GameEvent::GoalReached);
+        }
+    }
+
+    pub fn calculate_fall_duration(level: u32) -> Duration {
+        let fall_seconds = (0.8 - ((level as f64 - 1.0) * 0.007)).powf(level as f64 - 1.0);
+        let fall_seconds = fall_seconds.max(1.0 / 60.0);
+        Duration::from_secs_f64(fall_seconds)
+    }
+
+    fn quick_reset_game(&mut self) {
+        // reset all the necesary variables for a quick reset here
+
+        self.state.map = [[Cell {
+            color: None,
+            occupied: false,
+        }; 10]; 20];
+
+        // the next seed is drawn from the current bag so a whole session can be replayed from the
+        // first seed
+
+        let seed = if self.config.fixed_seed {
+            self.config.seed
+        } else {
+            self.state.bag.next_seed()
+        };
+
+        self.state.bag = Bag::new(seed);
+        self.state.current_tetromino = self.state.bag.next_tetromino();
+        self.state.hold = None;
+        self.state.is_holding = false;
+
+        self.state.game_timer = self.now;
+        self.state.fall_timer = self.now;
+        self.state.lock_delay.is_in_delay = false;
+        self.state.lock_delay.moves_done = 0;
+        self.state.lock_delay.ammount_fallen = 0;
+
+        self.state.score = 0;
+        self.state.lines_cleared = 0;
+        self.state.level = Self::start_level(&self.config, self.state.game_mode);
+        self.apply_level_speed();
+
+        self.state.combo = None;
+        self.state.back_to_back = None;
+        self.state.max_combo = 0;
+        self.state.max_back_to_back = 0;
+        self.state.perfect_clears = 0;
+        self.state.pieces_placed = 0;
+        self.state.key_presses = 0;
+        self.state.clear_types.clear();
+
+        self.state.garbage_rows = 0;
+        self.state.garbage_spawned = 0;
+        self.state.garbage_cleared = 0;
+        self.state.garbage_timer = self.now;
+        self.state.garbage_rng = StdRng::seed_from_u64(seed);
+        self.state.section_times.clear();
+        self.state.history.clear();
+        self.state.incoming_garbage.clear();
+        self.state.lines_sent = 0;
+        self.state.finesse_faults = 0;
+        self.reset_piece_inputs();
+
+        self.state.run = true;
+
+        if let Gamemode::Dig(goal) = self.state.game_mode {
+            self.fill_garbage(goal);
+        }
+
+        self.setup_puzzle();
+
+        self.events.push(GameEvent::Reset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetrominos::Shape;
+
+    fn config(seed: u64) -> EngineConfig {
+        EngineConfig {
+            insta_das: false,
+            insta_softdrop: false,
+            repeat_delay: Duration::from_millis(150),
+            repeat_interval: Duration::from_millis(30),
+            soft_drop_interval: Duration::from_millis(20),
+            fall_interval: Duration::from_millis(1000),
+            init_level: 1,
+            seed,
+            fixed_seed: true,
+            dig_messiness: 0,
+            dig_rise_pieces: 0,
+            dig_rise_interval: Duration::ZERO,
+            puzzle: None,
+        }
+    }
+
+    // press and release an action over two ticks and return what happened
+
+    fn press(engine: &mut Engine, action: Action) -> Vec<GameEvent> {
+        let mut events = engine.tick(&[Input::Press(action)]);
+        events.extend(engine.tick(&[Input::Release(action)]));
+        events
+    }
+
+    fn wait(engine: &mut Engine, duration: Duration) -> Vec<GameEvent> {
+        let ticks = duration.as_nanos() / TICK.as_nanos();
+
+        (0..ticks).flat_map(|_| engine.tick(&[])).collect()
+    }
+
+    // fill a row of the map, leaving the given columns empty
+
+    fn fill_row(engine: &mut Engine, row: usize, holes: &[usize]) {
+        for (column, cell) in engine.state.map[row].iter_mut().enumerate() {
+            *cell = Cell {
+                color: Some(GARBAGE_COLOR),
+                occupied: !holes.contains(&column),
+            };
+        }
+    }
+
+    // make the given piece the falling one, at its spawn position
+
+    fn spawn(engine: &mut Engine, shape: Shape) {
+        let mut tetromino = Tetromino::new(shape);
+        tetromino.position = tetromino.spawn_position();
+        engine.state.current_tetromino = tetromino;
+    }
+
+    fn occupied_cells(engine: &Engine) -> usize {
+        engine
+            .state
+            .map
+            .iter()
+            .flatten()
+            .filter(|cell| cell.occupied)
+            .count()
+    }
+
+    #[test]
+    fn spawns_the_same_pieces_for_the_same_seed() {
+        let first = Engine::new(config(7), Gamemode::Classic);
+        let second = Engine::new(config(7), Gamemode::Classic);
+
+        let current = &first.state.current_tetromino;
+        assert!(current.position == current.spawn_position());
+        assert_eq!(occupied_cells(&first), 0);
+
+        let first_queue: Vec<Shape> = first
+            .state
+            .bag
+            .preview(14)
+            .into_iter()
+            .map(|t| t.shape)
+            .collect();
+        let second_queue: Vec<Shape> = second
+            .state
+            .bag
+            .preview(14)
+            .into_iter()
+            .map(|t| t.shape)
+            .collect();
+
+        assert!(first.state.current_tetromino.shape == second.state.current_tetromino.shape);
+        assert!(first_queue == second_queue);
+    }
+
+    #[test]
+    fn hard_drop_locks_the_piece_and_spawns_the_next() {
+        let mut engine = Engine::new(config(1), Gamemode::Classic);
+        let next = engine.state.bag.preview(1)[0].shape.clone();
+
+        let events = press(&mut engine, Action::HardDrop);
+
+        assert!(events.contains(&GameEvent::PieceLocked));
+        assert_eq!(engine.state.pieces_placed, 1);
+        assert_eq!(occupied_cells(&engine), 4);
+        assert!(engine.state.map[19].iter().any(|cell| cell.occupied));
+        assert!(engine.state.current_tetromino.shape == next);
+        assert!(engine.state.run);
+    }
+
+    #[test]
+    fn gravity_locks_the_piece_after_the_lock_delay() {
+        let mut engine = Engine::new(config(1), Gamemode::Classic);
+
+        // at level one a piece falls a row a second, twenty rows and the lock delay take less
+        // than half a minute
+
+        let events = wait(&mut engine, Duration::from_secs(30));
+
+        assert!(events.contains(&GameEvent::Moved));
+        assert!(events.contains(&GameEvent::PieceLocked));
+        assert!(engine.state.map[19].iter().any(|cell| cell.occupied));
+    }
+
+    #[test]
+    fn moves_left_and_right() {
+        let mut engine = Engine::new(config(1), Gamemode::Classic);
+        spawn(&mut engine, Shape::T);
+
+        press(&mut engine, Action::MoveLeft);
+        press(&mut engine, Action::MoveLeft);
+        assert_eq!(engine.state.current_tetromino.position[0], 1);
+
+        press(&mut engine, Action::MoveRight);
+        assert_eq!(engine.state.current_tetromino.position[0], 2);
+        assert_eq!(engine.state.key_presses, 3);
+    }
+
+    #[test]
+    fn clears_a_full_line() {
+        let mut engine = Engine::new(config(1), Gamemode::Classic);
+
+        fill_row(&mut engine, 19, &[3, 4, 5, 6]);
+        fill_row(&mut engine, 18, &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        spawn(&mut engine, Shape::I);
+
+        let events = press(&mut engine, Action::HardDrop);
+
+        let single = ClearType {
+            lines: 1,
+            t_spin: TSpin::None,
+            perfect_clear: false,
+        };
+
+        assert!(events.contains(&GameEvent::Cleared(single)));
+        assert_eq!(engine.state.lines_cleared, 1);
+        assert_eq!(engine.state.score, 100);
+
+        // the row above moved down into the cleared one
+
+        assert!(engine.state.map[19][0].occupied);
+        assert_eq!(occupied_cells(&engine), 1);
+    }
+
+    #[test]
+    fn tops_out_when_a_piece_locks_above_the_map() {
+        let mut engine = Engine::new(config(1), Gamemode::Classic);
+
+        for row in 1..20 {
+            fill_row(&mut engine, row, &[9]);
+        }
+        spawn(&mut engine, Shape::T);
+
+        let events = press(&mut engine, Action::HardDrop);
+
+        assert!(events.contains(&GameEvent::GameOver));
+        assert!(!engine.state.run);
+
+        // a finished game ignores everything but a reset
+
+        assert!(press(&mut engine, Action::HardDrop).is_empty());
+
+        let events = press(&mut engine, Action::QuickReset);
+
+        assert!(events.contains(&GameEvent::Reset));
+        assert!(engine.state.run);
+        assert_eq!(occupied_cells(&engine), 0);
+    }
+
+    // a t in the given orientation, written as cells around the center at [1, 1], locked in by
+    // the given wall kick
+
+    const T_UP: [[i32; 2]; 4] = [[0, 1], [1, 1], [2, 1], [1, 0]];
+    const T_DOWN: [[i32; 2]; 4] = [[0, 1], [1, 1], [2, 1], [1, 2]];
+    const T_LEFT: [[i32; 2]; 4] = [[1, 0], [1, 1], [1, 2], [0, 1]];
+
+    fn t_piece(grid: [[i32; 2]; 4], position: [i32; 2], last_kick: Option<usize>) -> Tetromino {
+        let mut tetromino = Tetromino::new(Shape::T);
+        tetromino.grid = grid.to_vec();
+        tetromino.position = position;
+        tetromino.last_kick = last_kick;
+        tetromino
+    }
+
+    fn block(engine: &mut Engine, column: usize, row: usize) {
+        engine.state.map[row][column] = Cell {
+            color: Some(GARBAGE_COLOR),
+            occupied: true,
+        };
+    }
+
+    // the slots the t-spin tests drop into, the t always sits with its center at column 4
+
+    type Setup = fn(&mut Engine) -> Tetromino;
+
+    fn t_spin_single(engine: &mut Engine) -> Tetromino {
+        fill_row(engine, 19, &[4]);
+        fill_row(engine, 18, &[0, 3, 4, 5]);
+        block(engine, 3, 17);
+        t_piece(T_DOWN, [3, 17], Some(0))
+    }
+
+    fn t_spin_double(engine: &mut Engine) -> Tetromino {
+        fill_row(engine, 19, &[4]);
+        fill_row(engine, 18, &[3, 4, 5]);
+        block(engine, 3, 17);
+        t_piece(T_DOWN, [3, 17], Some(0))
+    }
+
+    fn t_spin_triple(engine: &mut Engine) -> Tetromino {
+        fill_row(engine, 19, &[4]);
+        fill_row(engine, 18, &[3, 4]);
+        fill_row(engine, 17, &[4]);
+        block(engine, 0, 16);
+        t_piece(T_LEFT, [3, 17], Some(0))
+    }
+
+    // only one of the two corners in front of the point is blocked
+
+    fn mini_t_spin_single(engine: &mut Engine, last_kick: usize) -> Tetromino {
+        fill_row(engine, 19, &[3, 4, 5]);
+        block(engine, 3, 18);
+        t_piece(T_UP, [3, 18], Some(last_kick))
+    }
+
+    #[test]
+    fn detects_t_spins_with_the_three_corner_rule() {
+        let cases: [(&str, Setup, TSpin); 9] = [
+            ("t-spin single", t_spin_single, TSpin::Full),
+            ("t-spin double", t_spin_double, TSpin::Full),
+            ("t-spin triple", t_spin_triple, TSpin::Full),
+            ("mini", |engine| mini_t_spin_single(engine, 0), TSpin::Mini),
+            (
+                "mini upgraded by the last kick",
+                |engine| mini_t_spin_single(engine, 4),
+                TSpin::Full,
+            ),
+            (
+                "not rotated into place",
+                |engine| Tetromino {
+                    last_kick: None,
+                    ..t_spin_double(engine)
+                },
+                TSpin::None,
+            ),
+            (
+                "two corners",
+                |engine| {
+                    fill_row(engine, 19, &[3, 4, 5]);
+                    t_piece(T_UP, [3, 18], Some(0))
+                },
+                TSpin::None,
+            ),
+            (
+                "walls count as corners",
+                |engine| {
+                    fill_row(engine, 19, &[0]);
+                    fill_row(engine, 18, &[0, 1]);
+                    t_piece(T_LEFT, [-1, 17], Some(0))
+                },
+                TSpin::Full,
+            ),
+            (
+                "not a t",
+                |engine| {
+                    t_spin_double(engine);
+                    let mut tetromino = Tetromino::new(Shape::L);
+                    tetromino.position = [3, 17];
+                    tetromino.last_kick = Some(0);
+                    tetromino
+                },
+                TSpin::None,
+            ),
+        ];
+
+        for (name, setup, expected) in cases {
+            let mut engine = Engine::new(config(1), Gamemode::Classic);
+            let tetromino = setup(&mut engine);
+
+            assert_eq!(tetromino.t_spin(&engine.state.map), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn scores_t_spins() {
+        let cases: [(Setup, u32, TSpin, u32); 5] = [
+            (t_spin_single, 1, TSpin::Full, 800),
+            (t_spin_double, 2, TSpin::Full, 1200),
+            (t_spin_triple, 3, TSpin::Full, 1600),
+            (|engine| mini_t_spin_single(engine, 0), 1, TSpin::Mini, 200),
+            (|engine| mini_t_spin_single(engine, 4), 1, TSpin::Full, 800),
+        ];
+
+        for (setup, lines, t_spin, score) in cases {
+            let mut engine = Engine::new(config(1), Gamemode::Classic);
+            engine.state.current_tetromino = setup(&mut engine);
+
+            let events = press(&mut engine, Action::HardDrop);
+
+            let clear_type = ClearType {
+                lines,
+                t_spin,
+                perfect_clear: false,
+            };
+
+            assert!(
+                events.contains(&GameEvent::Cleared(clear_type)),
+                "{}",
+                clear_type.name()
+            );
+            assert_eq!(engine.state.lines_cleared, lines);
+            assert_eq!(engine.state.score, score, "{}", clear_type.name());
+            assert_eq!(engine.state.back_to_back, Some(0));
+        }
+    }
+
+    #[test]
+    fn scores_t_spins_without_lines() {
+        let mut engine = Engine::new(config(1), Gamemode::Classic);
+
+        fill_row(&mut engine, 19, &[0, 4]);
+        fill_row(&mut engine, 18, &[0, 3, 4, 5]);
+        block(&mut engine, 3, 17);
+        engine.state.current_tetromino = t_piece(T_DOWN, [3, 17], Some(0));
+
+        let events = press(&mut engine, Action::HardDrop);
+
+        let clear_type = ClearType {
+            lines: 0,
+            t_spin: TSpin::Full,
+            perfect_clear: false,
+        };
+
+        assert!(events.contains(&GameEvent::Cleared(clear_type)));
+        assert_eq!(engine.state.score, 400);
+        assert_eq!(engine.state.lines_cleared, 0);
+
+        // it breaks the combo but doesn't start a back to back
+
+        assert_eq!(engine.state.combo, None);
+        assert_eq!(engine.state.back_to_back, None);
+    }
+
+    // empty the map and fill its bottom rows with a well in the last column that a column of
+    // cells as tall as the rows drops into
+
+    fn fill_well(engine: &mut Engine, lines: usize) {
+        engine.state.map = [[Cell {
+            color: None,
+            occupied: false,
+        }; 10]; 20];
+
+        for row in 20 - lines..20 {
+            fill_row(engine, row, &[9]);
+        }
+
+        let mut tetromino = Tetromino::new(Shape::I);
+        tetromino.grid = (0..lines as i32).map(|y| [0, y]).collect();
+        tetromino.position = [9, 0];
+        engine.state.current_tetromino = tetromino;
+    }
+
+    // clear the given number of lines with a cell left over on top so it isn't a perfect clear
+
+    fn clear(engine: &mut Engine, lines: usize) -> Vec<GameEvent> {
+        fill_well(engine, lines);
+        block(engine, 0, 19 - lines);
+
+        press(engine, Action::HardDrop)
+    }
+
+    fn lock_without_clear(engine: &mut Engine) -> Vec<GameEvent> {
+        spawn(engine, Shape::O);
+
+        press(engine, Action::HardDrop)
+    }
+
+    fn attacks(events: &[GameEvent]) -> Vec<u32> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                GameEvent::Attack(lines) => Some(*lines),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn combos_add_to_the_score_until_a_lock_clears_nothing() {
+        let mut engine = Engine::new(config(1), Gamemode::Classic);
+
+        let scores: Vec<u32> = (0..4)
+            .map(|_| {
+                let score = engine.state.score;
+                clear(&mut engine, 1);
+                engine.state.score - score
+            })
+            .collect();
+
+        assert_eq!(scores, [100, 150, 200, 250]);
+        assert_eq!(engine.state.combo, Some(3));
+        assert_eq!(engine.state.max_combo, 3);
+
+        lock_without_clear(&mut engine);
+
+        assert_eq!(engine.state.combo, None);
+        assert_eq!(engine.state.max_combo, 3);
+
+        clear(&mut engine, 1);
+
+        assert_eq!(engine.state.combo, Some(0));
+        assert_eq!(engine.state.score, 800);
+    }
+
+    #[test]
+    fn back_to_back_survives_locks_without_lines_but_not_easy_clears() {
+        // zen keeps the level the same however many lines are cleared
+
+        let mut engine = Engine::new(config(1), Gamemode::Zen);
+
+        clear(&mut engine, 4);
+        assert_eq!(engine.state.score, 800);
+        assert_eq!(engine.state.back_to_back, Some(0));
+
+        lock_without_clear(&mut engine);
+        clear(&mut engine, 4);
+
+        // the second tetris in a row is worth half as much again
+
+        assert_eq!(engine.state.score, 800 + 1200);
+        assert_eq!(engine.state.back_to_back, Some(1));
+        assert_eq!(engine.state.max_back_to_back, 1);
+
+        lock_without_clear(&mut engine);
+        clear(&mut engine, 2);
+
+        assert_eq!(engine.state.score, 800 + 1200 + 300);
+        assert_eq!(engine.state.back_to_back, None);
+
+        lock_without_clear(&mut engine);
+        clear(&mut engine, 4);
+
+        assert_eq!(engine.state.score, 800 + 1200 + 300 + 800);
+        assert_eq!(engine.state.back_to_back, Some(0));
+        assert_eq!(engine.state.max_back_to_back, 1);
+    }
+
+    #[test]
+    fn attacks_follow_the_guideline_table() {
+        let cases = [(1, 0), (2, 1), (3, 2), (4, 4)];
+
+        for (lines, attack) in cases {
+            let mut engine = Engine::new(config(1), Gamemode::Versus);
+            let events = clear(&mut engine, lines);
+
+            let expected: Vec<u32> = [attack].into_iter().filter(|a| *a > 0).collect();
+
+            assert_eq!(attacks(&events), expected, "{} lines", lines);
+            assert_eq!(engine.state.lines_sent, attack);
+        }
+    }
+
+    #[test]
+    fn back_to_back_and_combos_send_extra_garbage() {
+        let mut engine = Engine::new(config(1), Gamemode::Versus);
+
+        clear(&mut engine, 4);
+        lock_without_clear(&mut engine);
+
+        assert_eq!(attacks(&clear(&mut engine, 4)), [5]);
+
+        // singles send nothing on their own, everything here comes from the combo table and
+        // combos longer than the table send the most
+
+        lock_without_clear(&mut engine);
+
+        let sent: Vec<u32> = (0..14)
+            .map(|_| attacks(&clear(&mut engine, 1)).iter().sum())
+            .collect();
+
+        assert_eq!(sent, [0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5, 5]);
+        assert_eq!(engine.state.lines_sent, 4 + 5 + 35);
+    }
+
+    #[test]
+    fn attacks_cancel_incoming_garbage_first() {
+        let mut engine = Engine::new(config(1), Gamemode::Versus);
+
+        engine.receive_garbage(3);
+        engine.receive_garbage(2);
+
+        // the tetris cancels the first attack and part of the second
+
+        assert!(attacks(&clear(&mut engine, 4)).is_empty());
+        assert_eq!(engine.state.incoming_garbage, [1]);
+        assert_eq!(engine.state.lines_sent, 0);
+
+        // a back to back tetris cancels the rest and sends what is left over
+
+        assert_eq!(attacks(&clear(&mut engine, 4)), [4]);
+        assert!(engine.state.incoming_garbage.is_empty());
+        assert_eq!(engine.state.lines_sent, 4);
+    }
+
+    #[test]
+    fn incoming_garbage_rises_after_a_lock_without_lines() {
+        let mut engine = Engine::new(config(1), Gamemode::Versus);
+
+        engine.receive_garbage(2);
+        let events = lock_without_clear(&mut engine);
+
+        assert!(events.contains(&GameEvent::GarbageRose));
+        assert_eq!(engine.state.garbage_rows, 2);
+        assert!(engine.state.incoming_garbage.is_empty());
+    }
+
+    fn perfect_clear(engine: &mut Engine, lines: usize) -> Vec<GameEvent> {
+        fill_well(engine, lines);
+
+        press(engine, Action::HardDrop)
+    }
+
+    #[test]
+    fn perfect_clears_add_a_bonus() {
+        let cases = [
+            (1, 100 + 800),
+            (2, 300 + 1200),
+            (3, 500 + 1800),
+            (4, 800 + 2000),
+        ];
+
+        for (lines, score) in cases {
+            let mut engine = Engine::new(config(1), Gamemode::Zen);
+            let events = perfect_clear(&mut engine, lines);
+
+            let clear_type = ClearType {
+                lines: lines as u32,
+                t_spin: TSpin::None,
+                perfect_clear: true,
+            };
+
+            assert!(events.contains(&GameEvent::Cleared(clear_type)));
+            assert_eq!(engine.state.score, score, "{} lines", lines);
+            assert_eq!(engine.state.perfect_clears, 1);
+
+            // the breakdown counts the clear without the perfect clear
+
+            let breakdown_type = ClearType {
+                perfect_clear: false,
+                ..clear_type
+            };
+
+            assert_eq!(engine.state.clear_types.get(&breakdown_type), Some(&1));
+        }
+    }
+
+    #[test]
+    fn back_to_back_tetris_perfect_clears_are_worth_more() {
+        let mut engine = Engine::new(config(1), Gamemode::Zen);
+
+        clear(&mut engine, 4);
+        lock_without_clear(&mut engine);
+        perfect_clear(&mut engine, 4);
+
+        assert_eq!(engine.state.score, 800 + 1200 + 3200);
+        assert_eq!(engine.state.perfect_clears, 1);
+    }
+
+    #[test]
+    fn perfect_clears_send_extra_garbage() {
+        let mut engine = Engine::new(config(1), Gamemode::Versus);
+
+        assert_eq!(attacks(&perfect_clear(&mut engine, 1)), [10]);
+
+        lock_without_clear(&mut engine);
+
+        assert_eq!(attacks(&perfect_clear(&mut engine, 4)), [14]);
+        assert_eq!(engine.state.lines_sent, 24);
+    }
+
+    #[test]
+    fn master_levels_go_up_with_every_lock() {
+        let mut engine = Engine::new(config(1), Gamemode::Master);
+
+        lock_without_clear(&mut engine);
+        assert_eq!(engine.state.level, 1);
+
+        lock_without_clear(&mut engine);
+        assert_eq!(engine.state.level, 2);
+
+        // a clear adds its lines on top of the lock
+
+        clear(&mut engine, 2);
+        assert_eq!(engine.state.level, 5);
+    }
+
+    #[test]
+    fn master_sections_end_only_with_a_clear() {
+        let mut engine = Engine::new(config(1), Gamemode::Master);
+        engine.state.level = 98;
+
+        lock_without_clear(&mut engine);
+        lock_without_clear(&mut engine);
+
+        assert_eq!(engine.state.level, 99);
+        assert!(engine.state.section_times.is_empty());
+
+        clear(&mut engine, 1);
+
+        assert_eq!(engine.state.level, 100);
+        assert_eq!(engine.state.section_times.len(), 1);
+    }
+
+    #[test]
+    fn undo_puts_the_statistics_back() {
+        let mut engine = Engine::new(config(1), Gamemode::Zen);
+
+        clear(&mut engine, 1);
+        clear(&mut engine, 1);
+        perfect_clear(&mut engine, 4);
+
+        // presses made before the lock stay counted, the ones after it are undone with it
+
+        let key_presses = engine.state.key_presses;
+        press(&mut engine, Action::MoveLeft);
+
+        assert_eq!(engine.state.pieces_placed, 3);
+        assert_eq!(engine.state.max_combo, 2);
+        assert_eq!(engine.state.perfect_clears, 1);
+
+        let events = press(&mut engine, Action::Undo);
+
+        assert!(events.contains(&GameEvent::Undone));
+        assert_eq!(engine.state.pieces_placed, 2);
+        assert_eq!(engine.state.key_presses, key_presses);
+        assert_eq!(engine.state.max_combo, 1);
+        assert_eq!(engine.state.max_back_to_back, 0);
+        assert_eq!(engine.state.perfect_clears, 0);
+        assert_eq!(engine.state.lines_cleared, 2);
+        assert_eq!(engine.state.score, 100 + 150);
+
+        let single = ClearType {
+            lines: 1,
+            t_spin: TSpin::None,
+            perfect_clear: false,
+        };
+
+        assert_eq!(engine.state.clear_types.len(), 1);
+        assert_eq!(engine.state.clear_types.get(&single), Some(&2));
+    }
+
+    #[test]
+    fn zen_clears_the_stack_instead_of_topping_out() {
+        let mut engine = Engine::new(config(1), Gamemode::Zen);
+
+        for row in 1..20 {
+            fill_row(&mut engine, row, &[9]);
+        }
+        spawn(&mut engine, Shape::T);
+
+        let events = press(&mut engine, Action::HardDrop);
+
+        assert!(events.contains(&GameEvent::StackCleared));
+        assert!(!events.contains(&GameEvent::PieceLocked));
+        assert!(!events.contains(&GameEvent::GameOver));
+        assert!(engine.state.run);
+        assert_eq!(engine.state.pieces_placed, 0);
+        assert_eq!(occupied_cells(&engine), 0);
+
+        // the full stack can still be brought back
+
+        press(&mut engine, Action::Undo);
+        assert_eq!(occupied_cells(&engine), 19 * 9);
+    }
+
+    fn puzzle_engine(path: &str) -> Engine {
+        let puzzle = Puzzle::load(path).unwrap();
+
+        Engine::new(
+            EngineConfig {
+                puzzle: Some(puzzle),
+                ..config(1)
+            },
+            Gamemode::Puzzle,
+        )
+    }
+
+    #[test]
+    fn puzzles_fail_when_the_last_piece_misses() {
+        let mut engine = puzzle_engine("puzzles/01_tetris.txt");
+
+        // the only piece is dropped flat onto the stack next to the well, with nothing held
+
+        let events = press(&mut engine, Action::HardDrop);
+
+        assert!(events.contains(&GameEvent::PieceLocked));
+        assert!(events.contains(&GameEvent::GameOver));
+        assert!(!engine.state.run);
+        assert!(engine.state.hold.is_none());
+    }
+
+    #[test]
+    fn puzzles_are_solved_by_meeting_their_goals() {
+        let mut engine = puzzle_engine("puzzles/01_tetris.txt");
+
+        press(&mut engine, Action::RotateClockwise);
+
+        for _ in 0..5 {
+            press(&mut engine, Action::MoveRight);
+        }
+
+        let events = press(&mut engine, Action::HardDrop);
+
+        assert!(events.contains(&GameEvent::GoalReached));
+        assert!(!events.contains(&GameEvent::GameOver));
+        assert_eq!(engine.state.lines_cleared, 4);
+        assert_eq!(engine.state.puzzle_goals_met, [true]);
+    }
+
+    #[test]
+    fn puzzles_fail_at_the_piece_limit_without_a_clear() {
+        let mut engine = puzzle_engine("puzzles/01_tetris.txt");
+
+        // pieces left in the queue don't matter once the limit is used up
+
+        engine.state.bag = Bag::fixed(&[Shape::I, Shape::I]);
+        spawn(&mut engine, Shape::O);
+
+        let events = press(&mut engine, Action::HardDrop);
+
+        assert!(events.contains(&GameEvent::GameOver));
+        assert!(!engine.state.run);
+    }
+
+    #[test]
+    fn finesse_faults_start_the_run_over() {
+        let mut engine = Engine::new(config(1), Gamemode::Finesse);
+
+        // an o piece dropped where it spawns needs no presses at all
+
+        spawn(&mut engine, Shape::O);
+        let events = press(&mut engine, Action::HardDrop);
+
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, GameEvent::FinesseFault(_))));
+        assert_eq!(engine.state.pieces_placed, 1);
+
+        // one column over takes one tap, going there and back is two taps too many
+
+        spawn(&mut engine, Shape::O);
+        press(&mut engine, Action::MoveLeft);
+        press(&mut engine, Action::MoveLeft);
+        press(&mut engine, Action::MoveRight);
+        let events = press(&mut engine, Action::HardDrop);
+
+        assert!(events.contains(&GameEvent::FinesseFault(2)));
+        assert!(engine.state.run);
+        assert_eq!(engine.state.pieces_placed, 0);
+        assert_eq!(occupied_cells(&engine), 0);
+    }
+}
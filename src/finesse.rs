@@ -0,0 +1,180 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::tetrominos::Tetromino;
+use crate::utilities::{left_most_position, right_most_position, Cell};
+
+// finesse is placing every piece with as few key presses as possible. holding a move key until
+// the piece reaches the wall is a single press just like a tap, so the fewest presses are found
+// by trying taps, wall slides and rotations from the spawn on an empty map
+
+const EMPTY_MAP: [[Cell; 10]; 20] = [[Cell {
+    color: None,
+    occupied: false,
+}; 10]; 20];
+
+// the fewest presses a new piece of the same shape needs to cover the same columns in the same
+// orientation as the tetromino, none if it can't get there without the stack like a tuck or a
+// spin
+
+pub fn optimal_inputs(tetromino: &Tetromino) -> Option<u32> {
+    let target = footprint(tetromino);
+
+    let mut start = Tetromino::new(tetromino.shape.clone());
+    start.position = start.spawn_position();
+
+    let mut seen = HashSet::from([(start.position, start.rotation)]);
+    let mut queue = VecDeque::from([(start, 0)]);
+
+    while let Some((current, inputs)) = queue.pop_front() {
+        if footprint(&current) == target {
+            return Some(inputs);
+        }
+
+        for next in moves(&current) {
+            if seen.insert((next.position, next.rotation)) {
+                queue.push_back((next, inputs + 1));
+            }
+        }
+    }
+
+    None
+}
+
+// everything a single press can do to the tetromino
+
+fn moves(tetromino: &Tetromino) -> Vec<Tetromino> {
+    let mut left = tetromino.clone();
+    left.left(&EMPTY_MAP);
+
+    let mut right = tetromino.clone();
+    right.right(&EMPTY_MAP);
+
+    let mut left_wall = tetromino.clone();
+    let (x, y) = left_most_position(tetromino, &EMPTY_MAP);
+    left_wall.move_to([x, y]);
+
+    let mut right_wall = tetromino.clone();
+    let (x, y) = right_most_position(tetromino, &EMPTY_MAP);
+    right_wall.move_to([x, y]);
+
+    let mut moves = vec![left, right, left_wall, right_wall];
+
+    for clockwise in [false, true] {
+        let mut rotated = tetromino.clone();
+
+        if rotated.srs_rotate(clockwise, &EMPTY_MAP) {
+            moves.push(rotated);
+        }
+    }
+
+    let mut rotated = tetromino.clone();
+
+    if rotated.rotate_180(&EMPTY_MAP) {
+        moves.push(rotated);
+    }
+
+    moves
+}
+
+// the cells of the tetromino with their height taken out, two placements with the same
+// footprint land in the same spot when hard dropped
+
+fn footprint(tetromino: &Tetromino) -> Vec<[i32; 2]> {
+    let top = tetromino
+        .grid
+        .iter()
+        .map(|point| point[1])
+        .min()
+        .unwrap_or(0);
+
+    let mut cells: Vec<[i32; 2]> = tetromino
+        .grid
+        .iter()
+        .map(|point| [point[0] + tetromino.position[0], point[1] - top])
+        .collect();
+
+    cells.sort();
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetrominos::Shape;
+
+    // a piece turned from its spawn and slid so its left most cell is in the given column
+
+    fn placed(shape: Shape, turn: fn(&mut Tetromino), column: i32) -> Tetromino {
+        let mut tetromino = Tetromino::new(shape);
+        tetromino.position = tetromino.spawn_position();
+        turn(&mut tetromino);
+
+        let left_most = |tetromino: &Tetromino| {
+            tetromino
+                .grid
+                .iter()
+                .map(|point| point[0] + tetromino.position[0])
+                .min()
+                .unwrap()
+        };
+
+        while left_most(&tetromino) > column {
+            tetromino.left(&EMPTY_MAP);
+        }
+
+        while left_most(&tetromino) < column {
+            tetromino.right(&EMPTY_MAP);
+        }
+
+        assert_eq!(left_most(&tetromino), column);
+        tetromino
+    }
+
+    fn no_turn(_: &mut Tetromino) {}
+
+    fn turn_180(tetromino: &mut Tetromino) {
+        assert!(tetromino.rotate_180(&EMPTY_MAP));
+    }
+
+    fn turn_upright(tetromino: &mut Tetromino) {
+        assert!(tetromino.srs_rotate(false, &EMPTY_MAP));
+    }
+
+    #[test]
+    fn o_pieces_need_at_most_two_presses() {
+        let presses = [1, 2, 2, 1, 0, 1, 2, 2, 1];
+
+        for (column, expected) in presses.into_iter().enumerate() {
+            let target = placed(Shape::O, no_turn, column as i32);
+
+            assert_eq!(optimal_inputs(&target), Some(expected), "column {}", column);
+        }
+    }
+
+    #[test]
+    fn i_pieces_slide_to_the_walls() {
+        let cases = [
+            ("flat left", no_turn as fn(&mut Tetromino), 0, 1),
+            ("flat right", no_turn, 6, 1),
+            ("upright left", turn_upright, 0, 2),
+            ("upright right", turn_upright, 9, 2),
+        ];
+
+        for (name, turn, column, expected) in cases {
+            let target = placed(Shape::I, turn, column);
+
+            assert_eq!(optimal_inputs(&target), Some(expected), "{}", name);
+        }
+    }
+
+    #[test]
+    fn upside_down_t_pieces_use_a_single_180() {
+        let presses = [2, 3, 2, 1, 2, 3, 3, 2];
+
+        for (column, expected) in presses.into_iter().enumerate() {
+            let target = placed(Shape::T, turn_180, column as i32);
+
+            assert_eq!(optimal_inputs(&target), Some(expected), "column {}", column);
+        }
+    }
+}
@@ -1,14 +1,18 @@
-use crate::tetrominos::{Bag, Shape, Tetromino};
+use crate::bot::Bot;
+use crate::engine::{Action, Engine, EngineConfig, GameEvent, Input};
+use crate::paths;
+use crate::puzzle::Puzzle;
+use crate::replay::Replay;
+use crate::scoreboard::ScoreBoard;
+use crate::tetrominos::{Bag, Shape, TSpin, Tetromino};
 use crate::utilities::{
-    has_colided, left_most_position, lowest_avaliable_position, render_bg, render_center_box,
-    render_text, right_most_position, Cell, Gamemode, Keystate, Lockdelay, Settings, Theme,
+    lowest_avaliable_position, render_bg, render_center_box, render_text, Binding, Gamemode,
+    MenuInput, Settings, Theme,
 };
 use chrono::Local;
-use core::f64;
-use sdl2::event::Event;
-use sdl2::keyboard::Scancode;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::pixels::Color;
 use sdl2::rect::Rect;
-use std::collections::HashMap;
 use std::fs::{self};
 use std::path::Path;
 use std::time::{Duration, Instant};
@@ -18,28 +22,31 @@ pub struct Game<'a> {
     font: sdl2::ttf::Font<'a, 'static>,
     canvas: &'a mut sdl2::render::Canvas<sdl2::video::Window>,
     event_pump: &'a mut sdl2::EventPump,
-    state: GameState,
+    engine: Engine,
+    previous_position: (Vec<[i32; 2]>, [i32; 2]), //stores the tetromino's last position to clear it from the screen
+    recording: Replay,
+    playback: Option<Replay>,
+    bot: Option<Bot>, // plays instead of the keyboard when watching the cpu
+    callout: Option<(String, Instant)>, // name of the last special clear and when it happened
+    hint: Option<Tetromino>, // where the current piece is best placed
+    fault_flash: Option<Instant>, // when the last finesse fault happened
+    finished: bool,   // the game ended by topping out or reaching its goal
+    personal_best: bool,
+    save_error: Option<String>, // why the score of the finished game couldn't be saved
+    replay_error: Option<String>, // why the replay picked on the results screen couldn't be saved
     theme: &'a Theme,
-    settings: &'a Settings,
+    settings: &'a mut Settings,
 }
 
-struct GameState {
-    pub run: bool,
-    pub game_mode: Gamemode,
-    pub map: [[Cell; 10]; 20],
-    pub level: u32,
-    pub bag: Bag,
-    pub current_tetromino: Tetromino,
-    pub previous_position: (Vec<[i32; 2]>, [i32; 2]), //stores the tetromino's last position to clear it from the screen
-    pub hold: Option<Tetromino>,
-    pub score: u32,
-    pub lines_cleared: u32,
-    pub game_timer: Instant,
-    pub fall_timer: Instant,
-    pub fall_interval: Duration,
-    pub level_fall_interval: Duration,
-    pub is_holding: bool,
-    pub lock_delay: Lockdelay,
+enum PauseChoice {
+    Resume,
+    Restart,
+    Quit,
+}
+
+enum ResultsChoice {
+    Retry,
+    Quit,
 }
 
 impl<'a> Game<'a> {
@@ -50,6 +57,14 @@ impl<'a> Game<'a> {
     const GRID_WIDTH: u32 = 10;
     const GRID_HEIGHT: u32 = 20;
 
+    const CALLOUT_DURATION: Duration = Duration::from_secs(2);
+
+    const HINT_BORDER: u32 = 3; // width of the placement hint's outline
+
+    const FAULT_FLASH_DURATION: Duration = Duration::from_millis(300);
+    const FAULT_FLASH_WIDTH: u32 = 8;
+    const FAULT_FLASH_COLOR: Color = Color::RGB(220, 40, 40);
+
     pub fn new(
         sdl_context: &'a sdl2::Sdl,
         ttf_context: &'a sdl2::ttf::Sdl2TtfContext,
@@ -58,7 +73,7 @@ impl<'a> Game<'a> {
         theme: &'a Theme,
         fall_interval: Duration,
         game_mode: Gamemode,
-        settings: &'a Settings,
+        settings: &'a mut Settings,
     ) -> Result<Self, String> {
         let video_subsystem = sdl_context.video()?;
         let mut window = video_subsystem
@@ -71,105 +86,157 @@ impl<'a> Game<'a> {
             .set_minimum_size(Self::WINDOW_WIDTH, Self::WINDOW_HEIGHT)
             .map_err(|e| e.to_string())?;
 
-        let map = [[Cell {
-            color: None,
-            occupied: false,
-        }; 10]; 20];
-        let mut bag = Bag::new();
-        let current_tetromino = bag.next_tetromino();
-        let previous_position = (vec![[0, 0]], [0, 0]);
-
         // init font here
-        let font_path = Path::new(&"assets/FreeMono.ttf");
-        let font = ttf_context.load_font(font_path, 22)?;
-
-        let lock_delay = Lockdelay {
-            lock_delay_timer: Instant::now(),
-            lock_delay_duration: Duration::from_secs_f64(0.5),
-            is_in_delay: false,
-            moves_done: 0,
-            ammount_fallen: 0,
+        let font_path = paths::font();
+        let font = ttf_context.load_font(&font_path, 22)?;
+
+        // puzzles are only loaded for puzzle games, watching a replay brings its own puzzle
+
+        let puzzle = match (game_mode, &settings.puzzle) {
+            (Gamemode::Puzzle, Some(path)) => Some(Puzzle::load(path)?),
+            _ => None,
+        };
+
+        let config = EngineConfig {
+            insta_das: settings.insta_das,
+            insta_softdrop: settings.insta_softdrop,
+            repeat_delay: settings.repeat_delay,
+            repeat_interval: settings.repeat_interval,
+            soft_drop_interval: settings.fall_interval,
+            fall_interval,
+            init_level: settings.init_level,
+            seed: settings.seed.unwrap_or_else(Bag::random_seed),
+            fixed_seed: settings.seed.is_some(),
+            dig_messiness: settings.dig_messiness,
+            dig_rise_pieces: settings.dig_rise_pieces,
+            dig_rise_interval: settings.dig_rise_interval,
+            puzzle,
         };
 
-        let level_fall_interval = Self::calculate_fall_duration(settings.init_level);
+        let recording = Replay::new(game_mode, config.clone());
+        let engine = Engine::new(config, game_mode);
+
+        let previous_position = (
+            engine.state.current_tetromino.grid.clone(),
+            engine.state.current_tetromino.position,
+        );
 
         Ok(Game {
             sdl_context,
             font,
             canvas,
             event_pump,
-            state: GameState {
-                run: true,
-                game_mode,
-                map,
-                bag,
-                level: settings.init_level,
-                lines_cleared: 0,
-                current_tetromino,
-                previous_position,
-                hold: None,
-                score: 0,
-                game_timer: Instant::now(),
-                fall_timer: Instant::now(),
-                is_holding: false,
-                fall_interval,
-                level_fall_interval,
-                lock_delay,
-            },
+            engine,
+            previous_position,
+            recording,
+            playback: None,
+            bot: None,
+            hint: None,
+            fault_flash: None,
+            callout: None,
+            finished: false,
+            personal_best: false,
+            save_error: None,
+            replay_error: None,
             theme,
             settings,
         })
     }
 
     pub fn run(&mut self) {
-        let target_frame_duration: i32 = 1000 / 60;
+        loop {
+            self.play();
 
-        let key_bindings = &self.settings.key_bindings;
+            if self.playback.is_some() || !self.finished {
+                break;
+            }
 
-        let mut key_states: HashMap<Scancode, Keystate> = HashMap::from([
-            (
-                key_bindings.move_left,
-                Keystate {
-                    is_pressed: false,
-                    first_press_time: Instant::now(),
-                    last_repeat_time: Instant::now(),
-                },
-            ),
-            (
-                key_bindings.move_right,
-                Keystate {
-                    is_pressed: false,
-                    first_press_time: Instant::now(),
-                    last_repeat_time: Instant::now(),
-                },
-            ),
-            (
-                key_bindings.soft_drop,
-                Keystate {
-                    is_pressed: false,
-                    first_press_time: Instant::now(),
-                    last_repeat_time: Instant::now(),
-                },
-            ),
-        ]);
+            match self.results() {
+                ResultsChoice::Retry => {
+                    let inputs = [Input::Press(Action::QuickReset)];
 
-        render_bg(
-            self.canvas,
-            self.theme.bg_color_1,
-            self.theme.bg_color_2,
-            Self::CELL_SIZE,
-            Self::GRID_WIDTH,
-            Self::GRID_HEIGHT,
-        );
-        self.render_preview_tetrominos();
-        self.render_lowest_avaliable_tetromino();
-        self.render_current_tetromino();
-        self.render_score();
+                    self.recording.record(self.engine.ticks() + 1, &inputs);
+                    self.engine.tick(&inputs);
 
-        while self.state.run {
+                    self.finished = false;
+                    self.personal_best = false;
+                    self.save_error = None;
+                    self.callout = None;
+                }
+                ResultsChoice::Quit => break,
+            }
+        }
+
+        if !self.is_watching() {
+            let _ = self.recording.save(&Replay::last_path());
+        }
+    }
+
+    // run frames until the engine stops, either because the game ended or it was quit
+
+    fn play(&mut self) {
+        let target_frame_duration: i32 = 1000 / 60;
+
+        let mut clock = Instant::now();
+        let mut start_time = self.engine.now();
+
+        self.render_all();
+
+        while self.engine.state.run {
             let frame_start_time = self.sdl_context.timer().unwrap().ticks();
 
-            self.update(&mut key_states);
+            let (mut inputs, pause) = self.handle_input();
+
+            if pause {
+                match self.pause() {
+                    PauseChoice::Resume => {}
+                    PauseChoice::Restart => inputs.push(Input::Press(Action::QuickReset)),
+                    PauseChoice::Quit => self.engine.state.run = false,
+                }
+
+                // keys released while paused were never seen by the engine
+
+                inputs.extend(Action::ALL.into_iter().map(Input::Release));
+
+                // the engine was not stepped while paused so the wall clock starts over
+
+                clock = Instant::now();
+                start_time = self.engine.now();
+
+                self.render_all();
+            }
+
+            let mut events: Vec<GameEvent> = Vec::new();
+
+            // step the engine until it has caught up with the wall clock, the inputs of this
+            // frame are applied on the first tick
+
+            while self.engine.state.run {
+                match &mut self.playback {
+                    Some(replay) => {
+                        if self.engine.ticks() >= replay.end_tick {
+                            self.engine.state.run = false;
+                            break;
+                        }
+                        events.extend(replay.step(&mut self.engine));
+                    }
+                    None => {
+                        if let Some(bot) = &mut self.bot {
+                            inputs.extend(bot.inputs(&self.engine));
+                        }
+
+                        self.recording.record(self.engine.ticks() + 1, &inputs);
+                        events.extend(self.engine.tick(&inputs));
+                        inputs.clear();
+                    }
+                }
+
+                if self.engine.now() - start_time >= clock.elapsed() {
+                    break;
+                }
+            }
+
+            self.render_events(&events);
 
             let frame_end_time = self.sdl_context.timer().unwrap().ticks();
             let frame_duration: i32 = (frame_end_time - frame_start_time) as i32;
@@ -181,476 +248,595 @@ impl<'a> Game<'a> {
         }
     }
 
-    fn update(&mut self, key_states: &mut HashMap<Scancode, Keystate>) {
-        let moved = self.handle_input(key_states);
+    // play a recorded session back through the engine, keyboard input is ignored apart from
+    // the pause key which stops watching
 
-        // set the previous position in case the tetromino falls and needs to be renderd
+    pub fn watch(&mut self, mut replay: Replay) {
+        self.engine = Engine::new(replay.config.clone(), replay.game_mode);
 
-        self.state.previous_position.0 = self.state.current_tetromino.grid.clone();
-        self.state.previous_position.1 = self.state.current_tetromino.position;
+        // skip ahead to the start of the last game in the session without rendering
 
-        let is_against_stack = has_colided(
-            &self.state.current_tetromino.grid,
-            &(
-                self.state.current_tetromino.position[0],
-                self.state.current_tetromino.position[1] + 1,
-            ),
-            &self.state.map,
-        );
+        let last_game_start = replay.last_game_start();
 
-        if self.state.fall_timer.elapsed() >= self.state.fall_interval && !is_against_stack {
-            self.state.current_tetromino.fall();
-            self.render_current_tetromino();
-            self.state.fall_timer = Instant::now();
+        while self.engine.ticks() < last_game_start {
+            replay.step(&mut self.engine);
         }
 
-        // set the lock delay timer here if the tetromino is touching the ground
+        self.playback = Some(replay);
+        self.run();
+    }
 
-        if has_colided(
-            &self.state.current_tetromino.grid,
-            &(
-                self.state.current_tetromino.position[0],
-                self.state.current_tetromino.position[1] + 1,
-            ),
-            &self.state.map,
-        ) && !self.state.lock_delay.is_in_delay
-        {
-            self.state.lock_delay.is_in_delay = true;
-            self.state.lock_delay.lock_delay_timer = Instant::now();
-        }
+    // let the cpu play, it needs instant soft drop to make its moves in one go
 
-        let current_tetromino = &mut self.state.current_tetromino;
+    pub fn watch_cpu(&mut self, pieces_per_second: f64) {
+        self.engine.config.insta_softdrop = true;
+        self.recording
+            .record_config(self.engine.ticks() + 1, &self.engine.config);
 
-        // if in lock delay and the tetromino has moved than increase the move counter
+        self.bot = Some(Bot::new(pieces_per_second));
+        self.run();
+    }
 
-        let previous_position = &self.state.previous_position.1;
+    // nothing is saved from games the player isn't playing
 
-        if (moved || *previous_position != current_tetromino.position)
-            && self.state.lock_delay.is_in_delay
-            && self.state.lock_delay.moves_done < 15
-        {
-            self.state.lock_delay.moves_done += 1;
-            self.state.lock_delay.lock_delay_timer = Instant::now();
-            self.state.fall_timer = Instant::now();
+    fn is_watching(&self) -> bool {
+        self.playback.is_some() || self.bot.is_some()
+    }
 
-            // if the y position is lager then increase the y position and restart the lock delay
-            // if the tetromino has fallen more than 3 spaces.
+    // statistics of the finished game, the game can be retried from here
 
-            if previous_position[1] < current_tetromino.position[1] {
-                if let Some(new_value) = self.state.lock_delay.ammount_fallen.checked_add(1) {
-                    self.state.lock_delay.ammount_fallen = new_value;
-                } else {
-                    self.state.lock_delay.ammount_fallen = 4;
-                }
+    fn results(&mut self) -> ResultsChoice {
+        let options = ["Retry", "Save Replay", "Menu"];
+        let mut index = 0;
+        let mut replay_saved = false;
+        self.replay_error = None;
+
+        loop {
+            self.render_results(&options, index, replay_saved);
 
-                if self.state.lock_delay.ammount_fallen > 3 {
-                    self.state.lock_delay.is_in_delay = false;
-                    self.state.lock_delay.moves_done = 0;
+            let event = self.event_pump.wait_event();
+
+            if let Event::Quit { .. } = event {
+                return ResultsChoice::Quit;
+            }
+
+            match self.settings.key_bindings.menu_input(&event) {
+                Some(MenuInput::Back) => return ResultsChoice::Quit,
+                Some(MenuInput::Up) => {
+                    index = index.saturating_sub(1);
+                }
+                Some(MenuInput::Down) => {
+                    index = (index + 1).min(options.len() - 1);
                 }
+                Some(MenuInput::Confirm) => match options[index] {
+                    "Retry" => return ResultsChoice::Retry,
+                    "Save Replay" => {
+                        if !replay_saved {
+                            self.replay_error = self.save_replay().err();
+                            replay_saved = self.replay_error.is_none();
+                        }
+                    }
+                    _ => return ResultsChoice::Quit,
+                },
+                _ => {}
             }
         }
+    }
 
-        // if in lock delay check if the timer has surpassed and the tetromino is on the stack
-        // then set the tetromino
-
-        let is_in_lock_delay = self.state.lock_delay.is_in_delay;
-        let lock_delay_time = self.state.lock_delay.lock_delay_timer.elapsed();
-        let lock_delay_duration = self.state.lock_delay.lock_delay_duration;
-        let is_touching_stack = has_colided(
-            &current_tetromino.grid,
-            &(
-                current_tetromino.position[0],
-                current_tetromino.position[1] + 1,
-            ),
-            &self.state.map,
+    fn render_results(&mut self, options: &[&str], index: usize, replay_saved: bool) {
+        render_bg(
+            self.canvas,
+            self.theme.bg_color_1,
+            self.theme.bg_color_2,
+            Self::CELL_SIZE,
+            Self::GRID_WIDTH,
+            Self::GRID_HEIGHT,
         );
 
-        if is_in_lock_delay && lock_delay_time >= lock_delay_duration && is_touching_stack {
-            self.set_tetromino();
+        let state = &self.engine.state;
+        let time = self.engine.elapsed().as_secs_f64();
+        let pieces = state.pieces_placed as f64;
+
+        let timed_out = state
+            .game_mode
+            .time_limit()
+            .is_some_and(|time_limit| self.engine.elapsed() >= time_limit);
+
+        let title = match (self.personal_best, state.game_mode) {
+            (true, _) => "New Personal Best!",
+            (false, Gamemode::Sprint(goal)) if state.lines_cleared >= goal => "Finished",
+            (false, Gamemode::Ultra(_)) if timed_out => "Time's Up",
+            (false, Gamemode::Dig(goal)) if state.garbage_cleared >= goal => "Finished",
+            (false, Gamemode::Master) if state.level >= 999 => "Finished",
+            (false, Gamemode::Puzzle) if state.puzzle_goals_met.is_empty() => "Game Over",
+            (false, Gamemode::Puzzle) if state.puzzle_goals_met.iter().all(|met| *met) => "Solved",
+            (false, Gamemode::Puzzle) => "Failed",
+            _ => "Game Over",
+        };
+
+        let mut lines = vec![
+            format!("Score: {}", state.score),
+            format!("Time: {:.2}", time),
+            format!("Lines: {}", state.lines_cleared),
+            format!("Pieces: {}", state.pieces_placed),
+            format!("PPS: {:.2}", if time > 0.0 { pieces / time } else { 0.0 }),
+            format!(
+                "KPP: {:.2}",
+                if pieces > 0.0 {
+                    state.key_presses as f64 / pieces
+                } else {
+                    0.0
+                }
+            ),
+            format!("Finesse Faults: {}", state.finesse_faults),
+            format!("Max Combo: {}", state.max_combo),
+            format!("Max B2B: {}", state.max_back_to_back),
+            format!("Perfect Clears: {}", state.perfect_clears),
+            format!("Seed: {}", state.bag.seed),
+        ];
+
+        if let Gamemode::Dig(_) = state.game_mode {
+            lines.insert(3, format!("Garbage: {}", state.garbage_cleared));
         }
 
-        // render the time that has transpired in the game
+        // master shows the grade and how long each section took, five sections to a line
 
-        match self.state.game_mode {
-            Gamemode::Lines40 => {
-                self.render_time();
+        if let Gamemode::Master = state.game_mode {
+            lines.insert(3, format!("Level: {}", state.level));
+            lines.insert(4, format!("Grade: {}", self.engine.master_grade()));
+
+            for (index, sections) in state.section_times.chunks(5).enumerate() {
+                let times: Vec<String> = sections
+                    .iter()
+                    .map(|time| format!("{:.1}", time.as_secs_f64()))
+                    .collect();
+
+                lines.insert(5 + index, format!("Sections: {}", times.join(" ")));
             }
-            _ => {}
         }
-    }
 
-    fn handle_input(&mut self, key_states: &mut HashMap<Scancode, Keystate>) -> bool {
-        // set the previous position in case of needing to render so the previous position can be
-        // cleared
+        // the player should know when the leaderboard is missing this game
+
+        if let Some(error) = &self.save_error {
+            lines.insert(0, format!("Score not saved: {}", error));
+        }
+
+        if let Some(error) = &self.replay_error {
+            lines.insert(0, format!("Replay not saved: {}", error));
+        }
+
+        // clear types from t-spins down to singles
+
+        let mut clear_types: Vec<_> = state.clear_types.iter().collect();
+        clear_types.sort_by_key(|(clear_type, _)| {
+            (
+                std::cmp::Reverse(clear_type.t_spin),
+                std::cmp::Reverse(clear_type.lines),
+            )
+        });
+
+        for (clear_type, count) in clear_types.iter().take(7) {
+            lines.push(format!("{}: {}", clear_type.name(), count));
+        }
+
+        let _ = render_text(
+            self.canvas,
+            &self.font,
+            self.theme.text_color,
+            &title.to_string(),
+            320,
+            20,
+        );
+
+        let mut y = 70;
+
+        for line in lines.iter() {
+            let _ = render_text(self.canvas, &self.font, self.theme.text_color, line, 320, y);
+            y += 28;
+        }
+
+        let mut options_y = 660;
+
+        for (option_index, option) in options.iter().enumerate() {
+            let prefix = if option_index == index { "> " } else { "  " };
+            let option = match (*option, replay_saved) {
+                ("Save Replay", true) => "Replay Saved",
+                _ => option,
+            };
 
-        self.state.previous_position.0 = self.state.current_tetromino.grid.clone();
-        self.state.previous_position.1 = self.state.current_tetromino.position;
+            let _ = render_text(
+                self.canvas,
+                &self.font,
+                self.theme.text_color,
+                &format!("{}{}", prefix, option),
+                320,
+                options_y,
+            );
+            options_y += 40;
+        }
+    }
 
-        let now = Instant::now();
+    // translate the key and controller events of this frame into engine inputs and whether the game should be
+    // paused
 
-        let mut moved: bool = false;
+    fn handle_input(&mut self) -> (Vec<Input>, bool) {
+        let mut inputs: Vec<Input> = Vec::new();
+        let mut pause = false;
 
+        let watching = self.is_watching();
         let key_bindings = &self.settings.key_bindings;
         let events: Vec<Event> = self.event_pump.poll_iter().collect();
 
         for event in events {
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    scancode: Some(Scancode::Escape),
-                    ..
-                } => {
-                    self.state.run = false;
+                Event::Quit { .. } => {
+                    self.engine.state.run = false;
                 }
-                Event::KeyDown {
-                    scancode: Some(scancode),
-                    repeat,
-                    ..
-                } => {
-                    if !repeat {
-                        if scancode == key_bindings.move_left {
-                            let key_state = key_states
-                                .get_mut(&self.settings.key_bindings.move_left)
-                                .unwrap();
-                            key_state.is_pressed = true;
-                            key_state.first_press_time = Instant::now();
-
-                            let current_tetromino = &mut self.state.current_tetromino;
-                            current_tetromino.left(&self.state.map);
-
-                            moved = true;
-                        } else if scancode == key_bindings.move_right {
-                            let key_state = key_states
-                                .get_mut(&self.settings.key_bindings.move_right)
-                                .unwrap();
-                            key_state.is_pressed = true;
-                            key_state.first_press_time = Instant::now();
-
-                            let current_tetromino = &mut self.state.current_tetromino;
-                            current_tetromino.right(&self.state.map);
-
-                            moved = true;
-                        } else if scancode == key_bindings.soft_drop {
-                            if self.settings.insta_softdrop {
-                                self.state.current_tetromino = lowest_avaliable_position(
-                                    &self.state.current_tetromino,
-                                    &self.state.map,
-                                );
-                                moved = true;
-                                self.state.fall_timer = Instant::now();
-                            } else {
-                                let key_state = key_states
-                                    .get_mut(&self.settings.key_bindings.soft_drop)
-                                    .unwrap();
-                                key_state.is_pressed = true;
-                                key_state.first_press_time = Instant::now();
-                            }
-                        } else if scancode == key_bindings.hard_drop {
-                            self.hard_drop();
-                        } else if scancode == key_bindings.rotate_clockwise {
-                            let current_tetromino = &mut self.state.current_tetromino;
-                            let success = current_tetromino.srs_rotate(false, &self.state.map);
-                            if success {
-                                moved = true;
-                            }
-                        } else if scancode == key_bindings.rotate_counter_clockwise {
-                            let current_tetromino = &mut self.state.current_tetromino;
-                            let success = current_tetromino.srs_rotate(true, &self.state.map);
-                            if success {
-                                moved = true;
-                            }
-                        } else if scancode == key_bindings.rotate_180 {
-                            let current_tetromino = &mut self.state.current_tetromino;
-                            let success = current_tetromino.rotate_180(&self.state.map);
-                            if success {
-                                moved = true;
-                            }
-                        } else if scancode == key_bindings.hold {
-                            self.switch_hold_tetromino();
-                        } else if scancode == key_bindings.quick_reset {
-                            self.quick_reset_game();
-                        }
+                _ if key_bindings.is_pressed(Binding::Pause, &event) => {
+                    if watching {
+                        self.engine.state.run = false;
+                    } else {
+                        pause = true;
                     }
                 }
-                Event::KeyUp {
-                    scancode: Some(scancode),
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
                     ..
                 } => {
-                    if let Some(state) = key_states.get_mut(&scancode) {
-                        state.is_pressed = false;
+                    pause = !watching;
+                }
+                _ => {
+                    if let Some(input) = key_bindings.input(&event) {
+                        inputs.push(input);
                     }
                 }
-                _ => {}
             }
         }
 
-        let repeat_delay = self.settings.repeat_delay;
-        let repeat_interval = self.settings.repeat_interval;
+        (inputs, pause && self.engine.state.run)
+    }
 
-        if key_states[&self.settings.key_bindings.move_left].is_pressed
-            && !key_states[&self.settings.key_bindings.move_right].is_pressed
-        {
-            let time_since_first_press = now
-                .duration_since(key_states[&self.settings.key_bindings.move_left].first_press_time);
-            let time_since_last_repeat = now
-                .duration_since(key_states[&self.settings.key_bindings.move_left].last_repeat_time);
-
-            if time_since_first_press >= repeat_delay {
-                if self.settings.insta_das {
-                    let current_tetromino = &mut self.state.current_tetromino;
-                    let new_position = left_most_position(current_tetromino, &self.state.map);
-                    current_tetromino.position = [new_position.0, new_position.1];
-                    moved = true;
-                } else if time_since_last_repeat >= repeat_interval {
-                    self.state.current_tetromino.left(&self.state.map);
-                    key_states
-                        .get_mut(&self.settings.key_bindings.move_left)
-                        .unwrap()
-                        .last_repeat_time = now;
-                    moved = true;
-                }
-            }
-        }
+    // the board is hidden while paused, nothing in the engine moves since it's not being ticked
 
-        if key_states[&self.settings.key_bindings.move_right].is_pressed
-            && !key_states[&self.settings.key_bindings.move_left].is_pressed
-        {
-            let time_since_first_press = now.duration_since(
-                key_states[&self.settings.key_bindings.move_right].first_press_time,
-            );
-            let time_since_last_repeat = now.duration_since(
-                key_states[&self.settings.key_bindings.move_right].last_repeat_time,
-            );
+    fn pause(&mut self) -> PauseChoice {
+        let options = ["Resume", "Restart", "Settings", "Quit"];
+        let mut index = 0;
 
-            if time_since_first_press >= repeat_delay {
-                if self.settings.insta_das {
-                    let current_tetromino = &mut self.state.current_tetromino;
-                    let new_position = right_most_position(current_tetromino, &self.state.map);
-                    current_tetromino.position = [new_position.0, new_position.1];
-                    moved = true;
-                } else if time_since_last_repeat >= repeat_interval {
-                    self.state.current_tetromino.right(&self.state.map);
-                    key_states
-                        .get_mut(&self.settings.key_bindings.move_right)
-                        .unwrap()
-                        .last_repeat_time = now;
-                    moved = true;
-                }
-            }
-        }
+        self.render_menu("Paused", &options.map(|option| option.to_string()), index);
 
-        if key_states[&self.settings.key_bindings.soft_drop].is_pressed {
-            let time_since_first_press = now.duration_since(
-                key_states[&self.settings.key_bindings.move_right].first_press_time,
-            );
-            let time_since_last_repeat = now.duration_since(
-                key_states[&self.settings.key_bindings.move_right].last_repeat_time,
-            );
+        loop {
+            let event = self.event_pump.wait_event();
+            let key_bindings = &self.settings.key_bindings;
 
-            if time_since_first_press >= repeat_delay
-                && time_since_last_repeat >= repeat_interval
-                && self.settings.fall_interval < self.state.fall_interval
-            {
-                self.state.fall_interval = self.settings.fall_interval;
+            // the pause key resumes the game as well as the menu's back key
+
+            match event {
+                Event::Quit { .. } => return PauseChoice::Quit,
+                _ if key_bindings.is_pressed(Binding::Pause, &event) => return PauseChoice::Resume,
+                _ => {}
             }
-        } else {
-            self.state.fall_interval = self.state.level_fall_interval;
-        }
 
-        if moved {
-            self.render_lowest_avaliable_tetromino();
-            self.render_current_tetromino();
-        }
+            match key_bindings.menu_input(&event) {
+                Some(MenuInput::Back) => return PauseChoice::Resume,
+                Some(MenuInput::Up) => {
+                    index = index.saturating_sub(1);
+                }
+                Some(MenuInput::Down) => {
+                    index = (index + 1).min(options.len() - 1);
+                }
+                Some(MenuInput::Confirm) => match options[index] {
+                    "Resume" => return PauseChoice::Resume,
+                    "Restart" => return PauseChoice::Restart,
+                    "Settings" => self.pause_settings(),
+                    _ => return PauseChoice::Quit,
+                },
+                _ => continue,
+            }
 
-        moved
+            self.render_menu("Paused", &options.map(|option| option.to_string()), index);
+        }
     }
 
-    fn set_tetromino(&mut self) {
-        let current_tetromino = &self.state.current_tetromino;
+    // handling settings that can be changed in the middle of a game, changes are recorded in the
+    // replay so it stays in sync
 
-        for point in current_tetromino.grid.iter() {
-            let pos_x = point[0] + current_tetromino.position[0];
-            let pos_y = point[1] + current_tetromino.position[1];
+    fn pause_settings(&mut self) {
+        let mut index = 0;
 
-            // check for game over state
+        loop {
+            let settings = &self.settings;
+            let options = [
+                format!("Instant DAS: {}", settings.insta_das),
+                format!("Instant Soft Drop: {}", settings.insta_softdrop),
+                format!("Repeat Delay: {}", settings.repeat_delay.as_millis()),
+                format!("Repeat Interval: {}", settings.repeat_interval.as_millis()),
+                format!("Soft Drop Interval: {}", settings.fall_interval.as_millis()),
+                "Back".to_string(),
+            ];
 
-            if pos_y < 0 {
-                self.state.run = false;
-                match self.state.game_mode {
-                    Gamemode::Classic => {
-                        self.save_score();
-                    }
-                    _ => {}
-                }
-                return;
+            self.render_menu("Settings", &options, index);
+
+            let event = self.event_pump.wait_event();
+
+            if let Event::Quit { .. } = event {
+                break;
             }
 
-            let map = &mut self.state.map;
+            let (toggle, increase) = match self.settings.key_bindings.menu_input(&event) {
+                Some(MenuInput::Back) => break,
+                Some(MenuInput::Up) => {
+                    index = index.saturating_sub(1);
+                    continue;
+                }
+                Some(MenuInput::Down) => {
+                    index = (index + 1).min(options.len() - 1);
+                    continue;
+                }
+                Some(MenuInput::Confirm) => (true, true),
+                Some(MenuInput::Left) => (false, false),
+                Some(MenuInput::Right) => (false, true),
+                None => continue,
+            };
 
-            map[pos_y as usize][pos_x as usize] = Cell {
-                color: Some(current_tetromino.color),
-                occupied: true,
+            let step = |duration: Duration| {
+                if increase {
+                    duration + Duration::from_millis(1)
+                } else {
+                    duration
+                        .saturating_sub(Duration::from_millis(1))
+                        .max(Duration::from_millis(1))
+                }
             };
-        }
 
-        self.clear_lines();
+            let settings = &mut self.settings;
 
-        self.state.current_tetromino = self.state.bag.next_tetromino();
-        self.state.is_holding = false;
-        self.state.previous_position.0 = self.state.current_tetromino.grid.clone();
-        self.state.previous_position.1 = self.state.current_tetromino.position;
+            match index {
+                0 if toggle => settings.insta_das = !settings.insta_das,
+                1 if toggle => settings.insta_softdrop = !settings.insta_softdrop,
+                2 if !toggle => settings.repeat_delay = step(settings.repeat_delay),
+                3 if !toggle => settings.repeat_interval = step(settings.repeat_interval),
+                4 if !toggle => settings.fall_interval = step(settings.fall_interval),
+                5 if toggle => break,
+                _ => continue,
+            }
 
-        self.render_map();
-        self.render_preview_tetrominos();
-        self.render_lowest_avaliable_tetromino();
-        self.render_current_tetromino();
-        self.render_score();
+            self.apply_handling_settings();
+        }
+
+        self.settings.save();
+    }
+
+    fn apply_handling_settings(&mut self) {
+        let config = &mut self.engine.config;
 
-        self.state.fall_timer = Instant::now();
+        config.insta_das = self.settings.insta_das;
+        config.insta_softdrop = self.settings.insta_softdrop;
+        config.repeat_delay = self.settings.repeat_delay;
+        config.repeat_interval = self.settings.repeat_interval;
+        config.soft_drop_interval = self.settings.fall_interval;
 
-        // reset lock delay
-        self.state.lock_delay.is_in_delay = false;
-        self.state.lock_delay.moves_done = 0;
+        self.recording
+            .record_config(self.engine.ticks() + 1, &self.engine.config);
     }
 
-    fn hard_drop(&mut self) {
-        let current_tetromino =
-            lowest_avaliable_position(&self.state.current_tetromino, &self.state.map);
+    fn render_menu(&mut self, title: &str, options: &[String], index: usize) {
+        render_bg(
+            self.canvas,
+            self.theme.bg_color_1,
+            self.theme.bg_color_2,
+            Self::CELL_SIZE,
+            Self::GRID_WIDTH,
+            Self::GRID_HEIGHT,
+        );
+
+        let _ = render_text(
+            self.canvas,
+            &self.font,
+            self.theme.text_color,
+            &title.to_string(),
+            320,
+            50,
+        );
 
-        self.state.current_tetromino = current_tetromino;
-        self.set_tetromino();
+        let mut options_y = 300;
+
+        for (option_index, option) in options.iter().enumerate() {
+            let prefix = if option_index == index { "> " } else { "  " };
+
+            let _ = render_text(
+                self.canvas,
+                &self.font,
+                self.theme.text_color,
+                &format!("{}{}", prefix, option),
+                320,
+                options_y,
+            );
+            options_y += 50;
+        }
     }
 
-    fn clear_lines(&mut self) {
-        if let Some(first_full_line) = self.get_first_full_line() {
-            let ammount_lines = self.get_subsequent_lines(first_full_line);
+    // redraw the parts of the screen affected by what happened in the engine this frame
 
-            // iterate in revrse over the map and shift rows down by ammount of lines
+    fn render_events(&mut self, events: &[GameEvent]) {
+        let watching = self.is_watching();
 
-            for row_index in (0..first_full_line + ammount_lines).rev() {
-                if row_index >= ammount_lines {
-                    self.state.map[row_index] = self.state.map[row_index - ammount_lines];
-                } else {
-                    // set a blank line if the itteration goes out of bounds of the array
+        if events.contains(&GameEvent::GameOver) {
+            self.finished = true;
 
-                    self.state.map[row_index] = [Cell {
-                        occupied: false,
-                        color: None,
-                    }; 10];
-                }
+            if let (Gamemode::Classic | Gamemode::Master, false) =
+                (self.engine.state.game_mode, watching)
+            {
+                self.personal_best = self.is_personal_best();
+                self.save_error = self.save_score().err();
             }
+            return;
+        }
 
-            self.state.lines_cleared += ammount_lines as u32;
+        if events.contains(&GameEvent::GoalReached) {
+            self.finished = true;
 
-            let level = self.state.level;
+            if !watching && self.engine.state.game_mode != Gamemode::Puzzle {
+                self.personal_best = self.is_personal_best();
+                self.save_error = self.save_score().err();
+            }
+            return;
+        }
 
-            let score = match ammount_lines {
-                1 => 100 * level,
-                2 => 300 * level,
-                3 => 500 * level,
-                4 => 800 * level,
-                _ => 0,
-            };
+        // announce tetrises, t-spins and perfect clears next to the score
 
-            match self.state.game_mode {
-                Gamemode::Classic => {
-                    self.state.score += score;
-                    self.set_level();
-                }
-                Gamemode::Lines40 => {
-                    self.check_40_lines_game_over_state();
+        for event in events {
+            if let GameEvent::Cleared(clear_type) = event {
+                if clear_type.lines == 4
+                    || clear_type.t_spin != TSpin::None
+                    || clear_type.perfect_clear
+                {
+                    let name = if clear_type.perfect_clear {
+                        "Perfect Clear".to_string()
+                    } else {
+                        clear_type.name()
+                    };
+
+                    let name = match self.engine.state.back_to_back {
+                        Some(chain) if chain > 0 && clear_type.is_difficult() => {
+                            format!("B2B {}", name)
+                        }
+                        _ => name,
+                    };
+
+                    self.callout = Some((name, Instant::now()));
+                    self.render_score();
                 }
             }
-
-            self.state.level_fall_interval = Self::calculate_fall_duration(self.state.level);
         }
-    }
 
-    fn set_level(&mut self) {
-        match self.state.game_mode {
-            Gamemode::Classic => {
-                self.state.level =
-                    ((self.state.lines_cleared / 10) + 1).max(self.settings.init_level);
+        if let Some((_, time)) = &self.callout {
+            if time.elapsed() >= Self::CALLOUT_DURATION {
+                self.callout = None;
+                self.render_score();
             }
-            _ => {}
         }
-    }
 
-    fn get_first_full_line(&self) -> Option<usize> {
-        for (row_index, row) in self.state.map.iter().enumerate() {
-            let all_occupied: bool = row.iter().all(|cell| cell.occupied);
+        // the sides of the board flash when a piece was placed with too many presses
 
-            if all_occupied {
-                return Some(row_index);
+        if events
+            .iter()
+            .any(|event| matches!(event, GameEvent::FinesseFault(_)))
+        {
+            self.fault_flash = Some(Instant::now());
+            self.render_fault_flash();
+            self.render_score();
+        } else if let Some(time) = self.fault_flash {
+            if time.elapsed() >= Self::FAULT_FLASH_DURATION {
+                self.fault_flash = None;
+                self.render_fault_flash();
             }
         }
-        None
-    }
 
-    fn get_subsequent_lines(&self, first_full_line: usize) -> usize {
-        let mut count = 1;
+        if events.contains(&GameEvent::Reset)
+            || events.contains(&GameEvent::Undone)
+            || events.contains(&GameEvent::StackCleared)
+        {
+            self.callout = None;
+            self.render_all();
+        } else if events.contains(&GameEvent::PieceLocked)
+            || events.contains(&GameEvent::Held)
+            || events.contains(&GameEvent::GarbageRose)
+        {
+            // the board gets redrawn so the previous position does not need to be cleared
 
-        for i in 0..4 {
-            let index = first_full_line + i + 1;
+            self.set_previous_position();
+            self.update_hint();
 
-            if index >= self.state.map.len() {
-                break;
+            if events.contains(&GameEvent::Held) {
+                self.render_hold_tetromino();
+            } else {
+                self.render_map();
             }
+            self.render_preview_tetrominos();
+            self.render_lowest_avaliable_tetromino();
+            self.render_current_tetromino();
+            self.render_score();
+        } else if events.contains(&GameEvent::Moved) {
+            self.render_lowest_avaliable_tetromino();
+            self.render_current_tetromino();
+        }
 
-            let all_occupied: bool = self.state.map[index].iter().all(|cell| cell.occupied);
+        self.set_previous_position();
 
-            if !all_occupied {
-                break;
-            }
+        // render the time that has transpired in the game
 
-            count += 1;
+        if let Gamemode::Sprint(_) | Gamemode::Ultra(_) | Gamemode::Dig(_) | Gamemode::Master =
+            self.engine.state.game_mode
+        {
+            self.render_time();
         }
-
-        count
     }
 
-    fn switch_hold_tetromino(&mut self) {
-        if !self.state.is_holding {
-            if self.state.hold.is_none() {
-                let current_tetromino = &self.state.current_tetromino;
-                let hold_tetromino = Tetromino::new(current_tetromino.shape.clone());
+    // the hint only changes when there's a new piece or the board changed, looking for it takes
+    // too long to do on every move
 
-                self.state.hold = Some(hold_tetromino);
-                self.state.current_tetromino = self.state.bag.next_tetromino();
-            } else {
-                let current_tetromino = &self.state.current_tetromino;
-                let new_hold_tetromino = Tetromino::new(current_tetromino.shape.clone());
-                let mut new_current_tetromino =
-                    Tetromino::new(self.state.hold.as_ref().unwrap().shape.clone());
+    fn update_hint(&mut self) {
+        self.hint = if self.settings.placement_hint && self.engine.state.run {
+            Bot::hint(&self.engine)
+        } else {
+            None
+        };
+    }
 
-                let position_x = match new_current_tetromino.shape {
-                    Shape::O => 4,
-                    _ => 3,
-                };
+    fn set_previous_position(&mut self) {
+        self.previous_position.0 = self.engine.state.current_tetromino.grid.clone();
+        self.previous_position.1 = self.engine.state.current_tetromino.position;
+    }
 
-                let position_y = match new_current_tetromino.shape {
-                    Shape::I => 0,
-                    _ => -1,
-                };
+    fn render_all(&mut self) {
+        self.set_previous_position();
+        self.update_hint();
 
-                new_current_tetromino.position[0] = position_x;
-                new_current_tetromino.position[1] = position_y;
+        render_bg(
+            self.canvas,
+            self.theme.bg_color_1,
+            self.theme.bg_color_2,
+            Self::CELL_SIZE,
+            Self::GRID_WIDTH,
+            Self::GRID_HEIGHT,
+        );
+        if self.engine.state.hold.is_some() {
+            self.render_hold_tetromino();
+        } else {
+            self.render_map();
+        }
+        self.render_preview_tetrominos();
+        self.render_lowest_avaliable_tetromino();
+        self.render_current_tetromino();
+        self.render_score();
+        self.render_fault_flash();
+    }
 
-                // store previous position for clearing the screen
+    // draw the bars on both sides of the board in the flash color while a fault is shown and in
+    // the background color otherwise
 
-                self.state.hold = Some(new_hold_tetromino);
-                self.state.current_tetromino = new_current_tetromino;
+    fn render_fault_flash(&mut self) {
+        let box_width: u32 = Self::CELL_SIZE * Self::GRID_WIDTH;
+        let box_height: u32 = Self::CELL_SIZE * Self::GRID_HEIGHT;
+        let x_offset: i32 = ((self.canvas.window().size().0 / 2) - (box_width / 2)) as i32;
+        let y_offset: i32 = (self.canvas.window().size().1 - box_height) as i32;
 
-                self.state.previous_position.0 = self.state.current_tetromino.grid.clone();
-                self.state.previous_position.1 = self.state.current_tetromino.position;
-            }
-            self.state.is_holding = true;
+        let color = match self.fault_flash {
+            Some(_) => Self::FAULT_FLASH_COLOR,
+            None => self.theme.bg_color_1,
+        };
 
-            self.render_hold_tetromino();
-            self.render_preview_tetrominos();
-            self.render_lowest_avaliable_tetromino();
-            self.render_current_tetromino();
+        self.canvas.set_draw_color(color);
+
+        for x in [
+            x_offset - Self::FAULT_FLASH_WIDTH as i32,
+            x_offset + box_width as i32,
+        ] {
+            let rect = Rect::new(x, y_offset, Self::FAULT_FLASH_WIDTH, box_height);
+            let _ = self.canvas.fill_rect(rect);
         }
+
+        self.canvas.present();
     }
 
     fn render_map(&mut self) {
@@ -667,7 +853,7 @@ impl<'a> Game<'a> {
             Self::GRID_HEIGHT,
         );
 
-        let map = &self.state.map;
+        let map = &self.engine.state.map;
 
         for (y, row) in map.iter().enumerate() {
             for (x, &cell) in row.iter().enumerate() {
@@ -718,9 +904,9 @@ impl<'a> Game<'a> {
         let x_offset: i32 = ((self.canvas.window().size().0 / 2) - (box_width / 2)) as i32;
         let y_offset: i32 = (self.canvas.window().size().1 - box_height) as i32;
 
-        let current_tetromino = self.state.current_tetromino.clone();
-        let previous_grid = self.state.previous_position.0.clone();
-        let previous_position = self.state.previous_position.1;
+        let current_tetromino = self.engine.state.current_tetromino.clone();
+        let previous_grid = self.previous_position.0.clone();
+        let previous_position = self.previous_position.1;
 
         let mut previous_tetromino = Tetromino::new(current_tetromino.shape.clone());
         previous_tetromino.grid = previous_grid;
@@ -729,6 +915,7 @@ impl<'a> Game<'a> {
         // clear the screen of previous position where the tetromino was
 
         self.render_tetromino(&previous_tetromino, x_offset, y_offset, true);
+        self.render_hint();
 
         // render the current tetromino
 
@@ -743,17 +930,20 @@ impl<'a> Game<'a> {
 
         // clear screen of previous tetrominos lowest avaliable tetromino
 
-        let mut previous_tetromino = Tetromino::new(self.state.current_tetromino.shape.clone());
-        previous_tetromino.grid = self.state.previous_position.0.clone();
-        previous_tetromino.position = self.state.previous_position.1;
+        let mut previous_tetromino =
+            Tetromino::new(self.engine.state.current_tetromino.shape.clone());
+        previous_tetromino.grid = self.previous_position.0.clone();
+        previous_tetromino.position = self.previous_position.1;
 
-        let previous_tetromino = lowest_avaliable_position(&previous_tetromino, &self.state.map);
+        let previous_tetromino =
+            lowest_avaliable_position(&previous_tetromino, &self.engine.state.map);
         self.render_tetromino(&previous_tetromino, x_offset, y_offset, true);
+        self.render_hint();
 
         // render the lowest avaliable tetromino
 
         let mut tetromino =
-            lowest_avaliable_position(&self.state.current_tetromino, &self.state.map);
+            lowest_avaliable_position(&self.engine.state.current_tetromino, &self.engine.state.map);
         let mut render_color = tetromino.color;
 
         render_color.a = 100;
@@ -763,6 +953,40 @@ impl<'a> Game<'a> {
         self.render_tetromino(&tetromino, x_offset, y_offset, false)
     }
 
+    // the placement hint is drawn as an outline so it can't be mistaken for the ghost, it's
+    // redrawn whenever a tetromino is cleared from the board in case they overlapped
+
+    fn render_hint(&mut self) {
+        let Some(hint) = &self.hint else {
+            return;
+        };
+
+        let box_width: u32 = Self::CELL_SIZE * Self::GRID_WIDTH;
+        let box_height: u32 = Self::CELL_SIZE * Self::GRID_HEIGHT;
+        let x_offset: i32 = ((self.canvas.window().size().0 / 2) - (box_width / 2)) as i32;
+        let y_offset: i32 = (self.canvas.window().size().1 - box_height) as i32;
+
+        self.canvas.set_draw_color(hint.color);
+
+        for point in hint.grid.iter() {
+            let pos_x = (point[0] + hint.position[0]) * Self::CELL_SIZE as i32 + x_offset;
+            let pos_y = (point[1] + hint.position[1]) * Self::CELL_SIZE as i32 + y_offset;
+
+            for inset in 0..Self::HINT_BORDER {
+                let rect: Rect = Rect::new(
+                    pos_x + inset as i32,
+                    pos_y + inset as i32,
+                    Self::CELL_SIZE - inset * 2,
+                    Self::CELL_SIZE - inset * 2,
+                );
+
+                let _ = self.canvas.draw_rect(rect);
+            }
+        }
+
+        self.canvas.present();
+    }
+
     fn render_preview_tetrominos(&mut self) {
         let box_width: u32 = Self::CELL_SIZE * Self::GRID_WIDTH;
         let box_height: u32 = Self::CELL_SIZE * Self::GRID_HEIGHT;
@@ -772,7 +996,7 @@ impl<'a> Game<'a> {
         let mut y_offset: i32 =
             (self.canvas.window().size().1 - box_height) as i32 + (Self::CELL_SIZE * 2) as i32;
 
-        let preview_tetrominos: &Vec<Tetromino> = &self.state.bag.preview(5);
+        let preview_tetrominos: &Vec<Tetromino> = &self.engine.state.bag.preview(5);
 
         // clear the preview tetromino part of the screen before rendering the tetrominos
 
@@ -805,7 +1029,7 @@ impl<'a> Game<'a> {
         let mut y_offset: i32 =
             (self.canvas.window().size().1 - box_height) as i32 + (Self::CELL_SIZE * 2) as i32;
 
-        let hold_tetromino = &self.state.hold.as_ref().unwrap().clone();
+        let hold_tetromino = &self.engine.state.hold.as_ref().unwrap().clone();
         match hold_tetromino.shape {
             Shape::I => {
                 x_offset -= Self::CELL_SIZE as i32;
@@ -845,17 +1069,102 @@ impl<'a> Game<'a> {
 
         let score_x = 100;
         let score_y = 650;
-        let score = &format!("Score: {}", &self.state.score).to_string();
+        let score = &format!("Score: {}", &self.engine.state.score).to_string();
 
         let lines_x = 100;
         let lines_y = 700;
-        let lines = &format!("Lines: {}", &self.state.lines_cleared).to_string();
+        let lines = &format!("Lines: {}", &self.engine.state.lines_cleared).to_string();
 
         let level_x = 100;
         let level_y = 750;
-        let level = &format!("Level: {}", &self.state.level).to_string();
+        let level = &format!("Level: {}", &self.engine.state.level).to_string();
+
+        // the seed is always shown so a run can be raced or reproduced with the same pieces
+
+        let seed_x = 100;
+        let seed_y = 560;
+        let seed = &format!("Seed: {}", &self.engine.state.bag.seed).to_string();
+
+        let _ = render_text(
+            self.canvas,
+            &self.font,
+            self.theme.text_color,
+            seed,
+            seed_x,
+            seed_y,
+        );
+
+        // long clear names start further left so they stay clear of the board
 
-        match self.state.game_mode {
+        if let Some((callout, _)) = &self.callout {
+            let _ = render_text(
+                self.canvas,
+                &self.font,
+                self.theme.text_color,
+                callout,
+                40,
+                420,
+            );
+        }
+
+        let combo = &format!(
+            "Combo: {}  B2B: {}",
+            self.engine.state.combo.unwrap_or(0),
+            self.engine.state.back_to_back.unwrap_or(0)
+        );
+
+        let _ = render_text(
+            self.canvas,
+            &self.font,
+            self.theme.text_color,
+            combo,
+            40,
+            500,
+        );
+
+        let perfect_clears = &format!("Perfect Clears: {}", self.engine.state.perfect_clears);
+
+        let _ = render_text(
+            self.canvas,
+            &self.font,
+            self.theme.text_color,
+            perfect_clears,
+            40,
+            530,
+        );
+
+        let finesse_faults = &format!("Faults: {}", self.engine.state.finesse_faults);
+
+        let _ = render_text(
+            self.canvas,
+            &self.font,
+            self.theme.text_color,
+            finesse_faults,
+            40,
+            460,
+        );
+
+        if self.playback.is_some() {
+            let _ = render_text(
+                self.canvas,
+                &self.font,
+                self.theme.text_color,
+                &"Replay".to_string(),
+                200,
+                460,
+            );
+        } else if self.bot.is_some() {
+            let _ = render_text(
+                self.canvas,
+                &self.font,
+                self.theme.text_color,
+                &"CPU".to_string(),
+                200,
+                460,
+            );
+        }
+
+        match self.engine.state.game_mode {
             Gamemode::Classic => {
                 let _ = render_text(
                     self.canvas,
@@ -882,7 +1191,128 @@ impl<'a> Game<'a> {
                     level_y,
                 );
             }
-            Gamemode::Lines40 => {
+            Gamemode::Sprint(goal) => {
+                let lines = &format!("Lines: {}/{}", self.engine.state.lines_cleared, goal);
+
+                let _ = render_text(
+                    self.canvas,
+                    &self.font,
+                    self.theme.text_color,
+                    lines,
+                    lines_x,
+                    lines_y,
+                );
+            }
+            Gamemode::Dig(goal) => {
+                let garbage = &format!("Garbage: {}/{}", self.engine.state.garbage_cleared, goal);
+
+                let _ = render_text(
+                    self.canvas,
+                    &self.font,
+                    self.theme.text_color,
+                    garbage,
+                    lines_x,
+                    lines_y,
+                );
+            }
+            Gamemode::Master => {
+                let grade = &format!("Grade: {}", self.engine.master_grade());
+
+                let _ = render_text(
+                    self.canvas,
+                    &self.font,
+                    self.theme.text_color,
+                    grade,
+                    score_x,
+                    score_y,
+                );
+                let _ = render_text(
+                    self.canvas,
+                    &self.font,
+                    self.theme.text_color,
+                    lines,
+                    lines_x,
+                    lines_y,
+                );
+                let _ = render_text(
+                    self.canvas,
+                    &self.font,
+                    self.theme.text_color,
+                    level,
+                    level_x,
+                    level_y,
+                );
+            }
+            Gamemode::Puzzle => {
+                // the goal can be long so it starts further left like the callouts
+
+                if let Some(puzzle) = &self.engine.config.puzzle {
+                    let goal = &format!("Goal: {}", puzzle.goal_name());
+                    let pieces = &match puzzle.piece_limit {
+                        Some(limit) => {
+                            format!("Pieces: {}/{}", self.engine.state.pieces_placed, limit)
+                        }
+                        None => format!("Pieces: {}", self.engine.state.pieces_placed),
+                    };
+
+                    let _ = render_text(
+                        self.canvas,
+                        &self.font,
+                        self.theme.text_color,
+                        goal,
+                        40,
+                        score_y,
+                    );
+                    let _ = render_text(
+                        self.canvas,
+                        &self.font,
+                        self.theme.text_color,
+                        pieces,
+                        level_x,
+                        level_y,
+                    );
+                }
+
+                let _ = render_text(
+                    self.canvas,
+                    &self.font,
+                    self.theme.text_color,
+                    lines,
+                    lines_x,
+                    lines_y,
+                );
+            }
+            Gamemode::Finesse => {
+                // pieces count up from the last restart so they show the current streak
+
+                let pieces = &format!("Pieces: {}", self.engine.state.pieces_placed);
+
+                let _ = render_text(
+                    self.canvas,
+                    &self.font,
+                    self.theme.text_color,
+                    pieces,
+                    score_x,
+                    score_y,
+                );
+                let _ = render_text(
+                    self.canvas,
+                    &self.font,
+                    self.theme.text_color,
+                    lines,
+                    lines_x,
+                    lines_y,
+                );
+            }
+            Gamemode::Ultra(_) | Gamemode::Zen | Gamemode::Versus => {
+                let _ = render_text(
+                    self.canvas,
+                    &self.font,
+                    self.theme.text_color,
+                    score,
+                    score_x,
+                    score_y,
+                );
                 let _ = render_text(
                     self.canvas,
                     &self.font,
@@ -908,11 +1338,15 @@ impl<'a> Game<'a> {
 
         let time_x = 100;
         let time_y = 600;
-        let time = &format!(
-            "Time: {}",
-            &self.state.game_timer.elapsed().as_secs().to_string()
-        )
-        .to_string();
+
+        // timed modes count down to the end instead of up from the start
+
+        let seconds = match self.engine.state.game_mode.time_limit() {
+            Some(time_limit) => time_limit.saturating_sub(self.engine.elapsed()).as_secs(),
+            None => self.engine.elapsed().as_secs(),
+        };
+
+        let time = &format!("Time: {}", seconds).to_string();
 
         let _ = render_text(
             self.canvas,
@@ -924,41 +1358,41 @@ impl<'a> Game<'a> {
         );
     }
 
-    fn check_40_lines_game_over_state(&mut self) {
-        if self.state.lines_cleared >= 40 {
-            self.state.run = false;
-            self.save_score();
-        }
-    }
-
-    pub fn calculate_fall_duration(level: u32) -> Duration {
-        let fall_seconds = (0.8 - ((level as f64 - 1.0) * 0.007)).powf(level as f64 - 1.0);
-        let fall_seconds = fall_seconds.max(1.0 / 60.0);
-        Duration::from_secs_f64(fall_seconds)
-    }
-
     // prepend to the score to the score file
 
-    fn save_score(&self) {
-        let _ = fs::create_dir_all("score");
-
+    fn save_score(&self) -> Result<(), String> {
         let now = Local::now();
         let timestamp = now.format("%Y-%m-%d %H:%M:%S").to_string();
 
-        let (score, game_mode, file_path) = match self.state.game_mode {
-            Gamemode::Classic => (
-                self.state.score.to_string(),
-                "Classic".to_string(),
-                "score/classic.csv".to_string(),
-            ),
-            Gamemode::Lines40 => (
-                self.state.game_timer.elapsed().as_secs_f64().to_string(),
-                "Lines 40".to_string(),
-                "score/lines40.csv".to_string(),
-            ),
+        let game_mode = self.engine.state.game_mode;
+        let file_path = game_mode.score_file();
+
+        if let Some(parent) = Path::new(&file_path).parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        let score = match game_mode {
+            Gamemode::Classic
+            | Gamemode::Ultra(_)
+            | Gamemode::Zen
+            | Gamemode::Puzzle
+            | Gamemode::Versus
+            | Gamemode::Finesse => self.engine.state.score.to_string(),
+            Gamemode::Master => self.engine.state.level.to_string(),
+            Gamemode::Sprint(_) | Gamemode::Dig(_) => {
+                self.engine.elapsed().as_secs_f64().to_string()
+            }
         };
 
-        let csv_line = format!("{},{},{}\n", timestamp, game_mode, score);
+        let csv_line = format!(
+            "{},{},{},{},{},{}\n",
+            timestamp,
+            game_mode.name(),
+            score,
+            self.engine.state.bag.seed,
+            self.engine.state.max_combo,
+            self.engine.state.max_back_to_back
+        );
 
         // read existing content
         let content = fs::read_to_string(&file_path).unwrap_or("".to_string());
@@ -966,32 +1400,45 @@ impl<'a> Game<'a> {
         let new_content = csv_line + &content;
 
         // write to file
-        fs::write(&file_path, new_content).unwrap();
+        fs::write(&file_path, new_content).map_err(|e| e.to_string())
     }
 
-    fn quick_reset_game(&mut self) {
-        // reset all the necesary variables for a quick reset here
+    // compare against the scores saved so far, a higher score or a lower time is better
+
+    fn is_personal_best(&self) -> bool {
+        let game_mode = self.engine.state.game_mode;
+        let lower_is_better = game_mode.lower_is_better();
+
+        let value = match game_mode {
+            Gamemode::Classic
+            | Gamemode::Ultra(_)
+            | Gamemode::Zen
+            | Gamemode::Puzzle
+            | Gamemode::Versus
+            | Gamemode::Finesse => self.engine.state.score as f64,
+            Gamemode::Master => self.engine.state.level as f64,
+            Gamemode::Sprint(_) | Gamemode::Dig(_) => self.engine.elapsed().as_secs_f64(),
+        };
 
-        self.state.map = [[Cell {
-            color: None,
-            occupied: false,
-        }; 10]; 20];
+        match ScoreBoard::best_score(&game_mode.score_file(), lower_is_better) {
+            Some(best) if lower_is_better => value < best,
+            Some(best) => value > best,
+            None => true,
+        }
+    }
 
-        self.state.bag = Bag::new();
-        self.state.current_tetromino = self.state.bag.next_tetromino();
-        self.state.hold = None;
+    // keep a copy of the replay of a finished game so it can be shared
 
-        self.state.game_timer = Instant::now();
-        self.state.score = 0;
-        self.state.lines_cleared = 0;
-        self.state.level = self.settings.init_level;
+    fn save_replay(&self) -> Result<(), String> {
+        let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+        let game_mode = self.engine.state.game_mode.id();
 
-        // re render everything to clear the screen
-        self.render_map();
-        self.render_time();
-        self.render_score();
-        self.render_current_tetromino();
-        self.render_preview_tetrominos();
-        self.render_lowest_avaliable_tetromino();
+        let file_path = paths::data_file(&format!(
+            "{}/{}_{}.txt",
+            Replay::DIRECTORY,
+            game_mode,
+            timestamp
+        ));
+        self.recording.save(&file_path)
     }
 }
@@ -1,67 +1,222 @@
 #![windows_subsystem = "windows"]
 
+mod bot;
+mod cli;
+mod controller;
+mod editor;
+mod engine;
+mod finesse;
 mod game;
 mod menu;
+mod network;
+mod paths;
+mod puzzle;
+mod replay;
 mod scoreboard;
 mod tetrominos;
 mod utilities;
+mod versus;
 
+use cli::{Arguments, Launch};
+use editor::{Editor, EditorExit};
+use engine::Action;
 use game::Game;
 use menu::{InteractionType, MenuManager, MenuNode, MenuOption};
+use puzzle::Puzzle;
+use replay::Replay;
 use scoreboard::ScoreBoard;
 use sdl2::pixels::Color;
+use std::path::Path;
 use std::time::Duration;
-use utilities::{Gamemode, Theme};
+use utilities::{Binding, Gamemode, KeyBindings, Theme};
+use versus::{Opponent, Versus};
 
 fn main() -> Result<(), String> {
+    let arguments = match Arguments::parse(std::env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    paths::init(arguments.data_dir.clone());
+    paths::migrate_legacy_folders();
+
+    ScoreBoard::migrate_score_files();
+
+    // help and the leaderboards don't need a window
+
+    match arguments.launch {
+        Launch::Help => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Launch::Scores(game_mode) => {
+            ScoreBoard::print_leaderboards(game_mode);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let settings = arguments.settings()?;
+
     let mut sdl = init_sdl()?;
+    let _controllers = controller::watch(&sdl.0)?;
 
     let classic_game = |menu_manager: &mut MenuManager| {
-        let fall_interval = Duration::from_millis(20);
+        start_game(menu_manager, Gamemode::Classic);
+    };
+
+    let sprint_20_game = |menu_manager: &mut MenuManager| {
+        start_game(menu_manager, Gamemode::Sprint(20));
+    };
+
+    let sprint_40_game = |menu_manager: &mut MenuManager| {
+        start_game(menu_manager, Gamemode::Sprint(40));
+    };
+
+    let sprint_100_game = |menu_manager: &mut MenuManager| {
+        start_game(menu_manager, Gamemode::Sprint(100));
+    };
+
+    let sprint_1000_game = |menu_manager: &mut MenuManager| {
+        start_game(menu_manager, Gamemode::Sprint(1000));
+    };
+
+    let ultra_2_game = |menu_manager: &mut MenuManager| {
+        start_game(menu_manager, Gamemode::Ultra(2));
+    };
+
+    let ultra_3_game = |menu_manager: &mut MenuManager| {
+        start_game(menu_manager, Gamemode::Ultra(3));
+    };
+
+    let dig_10_game = |menu_manager: &mut MenuManager| {
+        start_game(menu_manager, Gamemode::Dig(10));
+    };
+
+    let dig_18_game = |menu_manager: &mut MenuManager| {
+        start_game(menu_manager, Gamemode::Dig(18));
+    };
+
+    let dig_100_game = |menu_manager: &mut MenuManager| {
+        start_game(menu_manager, Gamemode::Dig(100));
+    };
+
+    let master_game = |menu_manager: &mut MenuManager| {
+        start_game(menu_manager, Gamemode::Master);
+    };
+
+    let zen_game = |menu_manager: &mut MenuManager| {
+        start_game(menu_manager, Gamemode::Zen);
+    };
+
+    let finesse_game = |menu_manager: &mut MenuManager| {
+        start_game(menu_manager, Gamemode::Finesse);
+    };
+
+    let puzzle_game = |menu_manager: &mut MenuManager| {
+        if menu_manager.settings.puzzle.is_some() {
+            start_game(menu_manager, Gamemode::Puzzle);
+        }
+    };
+
+    let local_versus_game = |menu_manager: &mut MenuManager| {
+        start_versus(menu_manager, Opponent::Local);
+    };
+
+    let cpu_versus_game = |menu_manager: &mut MenuManager| {
+        let pieces_per_second = menu_manager.settings.cpu_speed as f64 / 10.0;
+        start_versus(menu_manager, Opponent::Cpu(pieces_per_second));
+    };
+
+    let host_versus_game = |menu_manager: &mut MenuManager| {
+        let port = menu_manager.settings.network_port;
+        start_versus(menu_manager, Opponent::Host(port));
+    };
+
+    let join_versus_game = |menu_manager: &mut MenuManager| {
+        let address = menu_manager.settings.network_address.clone();
+        let port = menu_manager.settings.network_port;
+        start_versus(menu_manager, Opponent::Join(address, port));
+    };
+
+    let custom_sprint_game = |menu_manager: &mut MenuManager, goal: Option<u64>| match goal
+        .and_then(|goal| u32::try_from(goal).ok())
+    {
+        Some(goal) if goal > 0 => start_game(menu_manager, Gamemode::Sprint(goal)),
+        _ => {}
+    };
+
+    let watch_replay = |menu_manager: &mut MenuManager| {
+        let path = menu_manager
+            .settings
+            .replay
+            .clone()
+            .unwrap_or(Replay::last_path());
+
+        start_replay(menu_manager, &path);
+    };
+
+    let watch_cpu = |menu_manager: &mut MenuManager| {
+        let pieces_per_second = menu_manager.settings.cpu_speed as f64 / 10.0;
 
         let game = Game::new(
-            &menu_manager.sdl_context,
-            &menu_manager.ttf_context,
-            &mut menu_manager.canvas,
-            &mut menu_manager.event_pump,
+            menu_manager.sdl_context,
+            menu_manager.ttf_context,
+            menu_manager.canvas,
+            menu_manager.event_pump,
             &menu_manager.theme,
-            fall_interval,
+            Duration::from_millis(20),
             Gamemode::Classic,
-            &menu_manager.settings,
+            &mut menu_manager.settings,
         );
 
         match game {
-            Ok(mut g) => g.run(),
+            Ok(mut g) => g.watch_cpu(pieces_per_second),
             Err(e) => println!("Failed to start game: {}", e),
         }
     };
 
-    let lines_40_game = |menu_manager: &mut MenuManager| {
-        let fall_interval = Duration::from_millis(20);
+    // the editor comes back after playing from its board so the board can be tweaked and tried
+    // again
 
-        let game = Game::new(
-            &menu_manager.sdl_context,
-            &menu_manager.ttf_context,
-            &mut menu_manager.canvas,
-            &mut menu_manager.event_pump,
+    let board_editor = |menu_manager: &mut MenuManager| loop {
+        let exit = match Editor::new(
+            menu_manager.ttf_context,
+            menu_manager.canvas,
+            menu_manager.event_pump,
             &menu_manager.theme,
-            fall_interval,
-            Gamemode::Lines40,
-            &menu_manager.settings,
-        );
+            &menu_manager.settings.key_bindings,
+        ) {
+            Ok(mut e) => e.run(),
+            Err(e) => {
+                println!("Failed to start editor: {}", e);
+                return;
+            }
+        };
 
-        match game {
-            Ok(mut g) => g.run(),
-            Err(e) => println!("Failed to start game: {}", e),
+        match exit {
+            EditorExit::Back => break,
+            EditorExit::Play => {
+                menu_manager.settings.puzzle = Some(Editor::file_path());
+                start_game(menu_manager, Gamemode::Puzzle);
+            }
         }
     };
 
+    let reset_controls = |menu_manager: &mut MenuManager| {
+        menu_manager.settings.key_bindings = KeyBindings::default();
+    };
+
     let show_scoreboard = |menu_manager: &mut MenuManager| {
         let scoreboard = ScoreBoard::new(
-            &menu_manager.ttf_context,
-            &mut menu_manager.canvas,
-            &mut menu_manager.event_pump,
+            menu_manager.ttf_context,
+            menu_manager.canvas,
+            menu_manager.event_pump,
             &menu_manager.theme,
+            &menu_manager.settings.key_bindings,
         );
 
         match scoreboard {
@@ -80,10 +235,54 @@ fn main() -> Result<(), String> {
                 dynamic_value: None,
                 action: InteractionType::Toggle(&classic_game),
             },
+            MenuOption::Submenu {
+                name: "Sprint".to_string(),
+                submenu_index: 3,
+            },
+            MenuOption::Submenu {
+                name: "Ultra".to_string(),
+                submenu_index: 4,
+            },
+            MenuOption::Submenu {
+                name: "Dig".to_string(),
+                submenu_index: 5,
+            },
             MenuOption::Action {
-                name: "40 Lines".to_string(),
+                name: "Master".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&master_game),
+            },
+            MenuOption::Action {
+                name: "Zen".to_string(),
                 dynamic_value: None,
-                action: InteractionType::Toggle(&lines_40_game),
+                action: InteractionType::Toggle(&zen_game),
+            },
+            MenuOption::Action {
+                name: "Finesse".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&finesse_game),
+            },
+            MenuOption::Submenu {
+                name: "Versus".to_string(),
+                submenu_index: 7,
+            },
+            MenuOption::Submenu {
+                name: "Puzzles".to_string(),
+                submenu_index: 6,
+            },
+            MenuOption::Action {
+                name: "Editor".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&board_editor),
+            },
+            MenuOption::Submenu {
+                name: "Watch Replay".to_string(),
+                submenu_index: 8,
+            },
+            MenuOption::Action {
+                name: "Watch CPU".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&watch_cpu),
             },
             MenuOption::Submenu {
                 name: "Options".to_string(),
@@ -139,6 +338,15 @@ fn main() -> Result<(), String> {
                     }
                 }),
             },
+            MenuOption::Action {
+                name: "Placement Hint".to_string(),
+                dynamic_value: Some(&|menu_manager| {
+                    menu_manager.settings.placement_hint.to_string()
+                }),
+                action: InteractionType::Toggle(&|menu_manager: &mut MenuManager| {
+                    menu_manager.settings.placement_hint = !menu_manager.settings.placement_hint;
+                }),
+            },
             MenuOption::Action {
                 name: "Repeat Delay".to_string(),
                 dynamic_value: Some(&|menu_manager| {
@@ -238,6 +446,18 @@ fn main() -> Result<(), String> {
                     },
                 ),
             },
+            MenuOption::Action {
+                name: "Seed".to_string(),
+                dynamic_value: Some(&|menu_manager| match menu_manager.settings.seed {
+                    Some(seed) => seed.to_string(),
+                    None => "Random".to_string(),
+                }),
+                action: InteractionType::Number(
+                    &|menu_manager: &mut MenuManager, seed: Option<u64>| {
+                        menu_manager.settings.seed = seed;
+                    },
+                ),
+            },
             MenuOption::Back {
                 name: "Back to Main Menu".to_string(),
             },
@@ -245,10 +465,10 @@ fn main() -> Result<(), String> {
         parent: Some(0),
     };
 
-    // menu for keybindings
+    // menu for keybindings, every binding can have a second key
 
     let controls_menu = MenuNode {
-        title: "Options".to_string(),
+        title: "Controls".to_string(),
         options: vec![
             MenuOption::Action {
                 name: "Move Left".to_string(),
@@ -256,11 +476,9 @@ fn main() -> Result<(), String> {
                     menu_manager
                         .settings
                         .key_bindings
-                        .move_left
-                        .name()
-                        .to_string()
+                        .names(Binding::Game(Action::MoveLeft))
                 }),
-                action: InteractionType::Scancode("move_left"),
+                action: InteractionType::Binding(Binding::Game(Action::MoveLeft)),
             },
             MenuOption::Action {
                 name: "Move Right".to_string(),
@@ -268,11 +486,9 @@ fn main() -> Result<(), String> {
                     menu_manager
                         .settings
                         .key_bindings
-                        .move_right
-                        .name()
-                        .to_string()
+                        .names(Binding::Game(Action::MoveRight))
                 }),
-                action: InteractionType::Scancode("move_right"),
+                action: InteractionType::Binding(Binding::Game(Action::MoveRight)),
             },
             MenuOption::Action {
                 name: "Rotate Clockwise".to_string(),
@@ -280,11 +496,9 @@ fn main() -> Result<(), String> {
                     menu_manager
                         .settings
                         .key_bindings
-                        .rotate_clockwise
-                        .name()
-                        .to_string()
+                        .names(Binding::Game(Action::RotateClockwise))
                 }),
-                action: InteractionType::Scancode("rotate_clockwise"),
+                action: InteractionType::Binding(Binding::Game(Action::RotateClockwise)),
             },
             MenuOption::Action {
                 name: "Rotate Counter Clockwise".to_string(),
@@ -292,11 +506,9 @@ fn main() -> Result<(), String> {
                     menu_manager
                         .settings
                         .key_bindings
-                        .rotate_counter_clockwise
-                        .name()
-                        .to_string()
+                        .names(Binding::Game(Action::RotateCounterClockwise))
                 }),
-                action: InteractionType::Scancode("rotate_counter_clockwise"),
+                action: InteractionType::Binding(Binding::Game(Action::RotateCounterClockwise)),
             },
             MenuOption::Action {
                 name: "Rotate 180".to_string(),
@@ -304,11 +516,9 @@ fn main() -> Result<(), String> {
                     menu_manager
                         .settings
                         .key_bindings
-                        .rotate_180
-                        .name()
-                        .to_string()
+                        .names(Binding::Game(Action::Rotate180))
                 }),
-                action: InteractionType::Scancode("rotate_180"),
+                action: InteractionType::Binding(Binding::Game(Action::Rotate180)),
             },
             MenuOption::Action {
                 name: "Hard Drop".to_string(),
@@ -316,11 +526,9 @@ fn main() -> Result<(), String> {
                     menu_manager
                         .settings
                         .key_bindings
-                        .hard_drop
-                        .name()
-                        .to_string()
+                        .names(Binding::Game(Action::HardDrop))
                 }),
-                action: InteractionType::Scancode("hard_drop"),
+                action: InteractionType::Binding(Binding::Game(Action::HardDrop)),
             },
             MenuOption::Action {
                 name: "Soft Drop".to_string(),
@@ -328,18 +536,19 @@ fn main() -> Result<(), String> {
                     menu_manager
                         .settings
                         .key_bindings
-                        .soft_drop
-                        .name()
-                        .to_string()
+                        .names(Binding::Game(Action::SoftDrop))
                 }),
-                action: InteractionType::Scancode("soft_drop"),
+                action: InteractionType::Binding(Binding::Game(Action::SoftDrop)),
             },
             MenuOption::Action {
                 name: "Hold".to_string(),
                 dynamic_value: Some(&|menu_manager| {
-                    menu_manager.settings.key_bindings.hold.name().to_string()
+                    menu_manager
+                        .settings
+                        .key_bindings
+                        .names(Binding::Game(Action::Hold))
                 }),
-                action: InteractionType::Scancode("hold"),
+                action: InteractionType::Binding(Binding::Game(Action::Hold)),
             },
             MenuOption::Action {
                 name: "Quick Reset".to_string(),
@@ -347,11 +556,304 @@ fn main() -> Result<(), String> {
                     menu_manager
                         .settings
                         .key_bindings
-                        .quick_reset
-                        .name()
-                        .to_string()
+                        .names(Binding::Game(Action::QuickReset))
+                }),
+                action: InteractionType::Binding(Binding::Game(Action::QuickReset)),
+            },
+            MenuOption::Action {
+                name: "Undo (Zen)".to_string(),
+                dynamic_value: Some(&|menu_manager| {
+                    menu_manager
+                        .settings
+                        .key_bindings
+                        .names(Binding::Game(Action::Undo))
+                }),
+                action: InteractionType::Binding(Binding::Game(Action::Undo)),
+            },
+            MenuOption::Action {
+                name: "Pause".to_string(),
+                dynamic_value: Some(&|menu_manager| {
+                    menu_manager.settings.key_bindings.names(Binding::Pause)
+                }),
+                action: InteractionType::Binding(Binding::Pause),
+            },
+            MenuOption::Action {
+                name: "Menu Confirm".to_string(),
+                dynamic_value: Some(&|menu_manager| {
+                    menu_manager
+                        .settings
+                        .key_bindings
+                        .names(Binding::MenuConfirm)
+                }),
+                action: InteractionType::Binding(Binding::MenuConfirm),
+            },
+            MenuOption::Action {
+                name: "Menu Back".to_string(),
+                dynamic_value: Some(&|menu_manager| {
+                    menu_manager.settings.key_bindings.names(Binding::MenuBack)
+                }),
+                action: InteractionType::Binding(Binding::MenuBack),
+            },
+            MenuOption::Action {
+                name: "Reset to Defaults".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&reset_controls),
+            },
+            MenuOption::Back {
+                name: "Back to Main Menu".to_string(),
+            },
+        ],
+        parent: Some(0),
+    };
+
+    // sprint menu
+
+    let sprint_menu = MenuNode {
+        title: "Sprint".to_string(),
+        options: vec![
+            MenuOption::Action {
+                name: "20 Lines".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&sprint_20_game),
+            },
+            MenuOption::Action {
+                name: "40 Lines".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&sprint_40_game),
+            },
+            MenuOption::Action {
+                name: "100 Lines".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&sprint_100_game),
+            },
+            MenuOption::Action {
+                name: "1000 Lines".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&sprint_1000_game),
+            },
+            MenuOption::Action {
+                name: "Custom".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Number(&custom_sprint_game),
+            },
+            MenuOption::Back {
+                name: "Back to Main Menu".to_string(),
+            },
+        ],
+        parent: Some(0),
+    };
+
+    // ultra menu
+
+    let ultra_menu = MenuNode {
+        title: "Ultra".to_string(),
+        options: vec![
+            MenuOption::Action {
+                name: "2 Minutes".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&ultra_2_game),
+            },
+            MenuOption::Action {
+                name: "3 Minutes".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&ultra_3_game),
+            },
+            MenuOption::Back {
+                name: "Back to Main Menu".to_string(),
+            },
+        ],
+        parent: Some(0),
+    };
+
+    // dig menu, the garbage options only apply to dig games
+
+    let dig_menu = MenuNode {
+        title: "Dig".to_string(),
+        options: vec![
+            MenuOption::Action {
+                name: "10 Lines".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&dig_10_game),
+            },
+            MenuOption::Action {
+                name: "18 Lines".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&dig_18_game),
+            },
+            MenuOption::Action {
+                name: "100 Lines".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&dig_100_game),
+            },
+            MenuOption::Action {
+                name: "Messiness".to_string(),
+                dynamic_value: Some(&|menu_manager| {
+                    format!("{}%", menu_manager.settings.dig_messiness)
+                }),
+                action: InteractionType::Scrollable(
+                    &|menu_manager: &mut MenuManager, increase: bool| {
+                        if increase {
+                            menu_manager.settings.dig_messiness =
+                                (menu_manager.settings.dig_messiness + 10).min(100);
+                        } else {
+                            menu_manager.settings.dig_messiness =
+                                menu_manager.settings.dig_messiness.saturating_sub(10);
+                        }
+                    },
+                ),
+            },
+            MenuOption::Action {
+                name: "Rise Every Pieces".to_string(),
+                dynamic_value: Some(
+                    &|menu_manager| match menu_manager.settings.dig_rise_pieces {
+                        0 => "Off".to_string(),
+                        pieces => pieces.to_string(),
+                    },
+                ),
+                action: InteractionType::Scrollable(
+                    &|menu_manager: &mut MenuManager, increase: bool| {
+                        if increase {
+                            menu_manager.settings.dig_rise_pieces += 1;
+                        } else {
+                            menu_manager.settings.dig_rise_pieces =
+                                menu_manager.settings.dig_rise_pieces.saturating_sub(1);
+                        }
+                    },
+                ),
+            },
+            MenuOption::Action {
+                name: "Rise Every Seconds".to_string(),
+                dynamic_value: Some(&|menu_manager| match menu_manager
+                    .settings
+                    .dig_rise_interval
+                    .as_secs()
+                {
+                    0 => "Off".to_string(),
+                    seconds => seconds.to_string(),
+                }),
+                action: InteractionType::Scrollable(
+                    &|menu_manager: &mut MenuManager, increase: bool| {
+                        if increase {
+                            menu_manager.settings.dig_rise_interval += Duration::from_secs(1);
+                        } else {
+                            menu_manager.settings.dig_rise_interval = menu_manager
+                                .settings
+                                .dig_rise_interval
+                                .saturating_sub(Duration::from_secs(1));
+                        }
+                    },
+                ),
+            },
+            MenuOption::Back {
+                name: "Back to Main Menu".to_string(),
+            },
+        ],
+        parent: Some(0),
+    };
+
+    // puzzle menu, the puzzles are read from the puzzle directory every time the list is shown
+
+    let puzzle_menu = MenuNode {
+        title: "Puzzles".to_string(),
+        options: vec![
+            MenuOption::Action {
+                name: "Puzzle".to_string(),
+                dynamic_value: Some(&|menu_manager| {
+                    let puzzle = menu_manager
+                        .settings
+                        .puzzle
+                        .as_ref()
+                        .and_then(|path| Puzzle::load(path).ok());
+
+                    match puzzle {
+                        Some(puzzle) => format!("{} ({})", puzzle.name, puzzle.goal_name()),
+                        None => "None".to_string(),
+                    }
+                }),
+                action: InteractionType::Scrollable(
+                    &|menu_manager: &mut MenuManager, increase: bool| {
+                        let puzzles = Puzzle::list();
+
+                        if puzzles.is_empty() {
+                            menu_manager.settings.puzzle = None;
+                            return;
+                        }
+
+                        let current = puzzles.iter().position(|puzzle| {
+                            Some(&puzzle.path) == menu_manager.settings.puzzle.as_ref()
+                        });
+
+                        let index = match (current, increase) {
+                            (None, _) => 0,
+                            (Some(index), true) => (index + 1) % puzzles.len(),
+                            (Some(index), false) => (index + puzzles.len() - 1) % puzzles.len(),
+                        };
+
+                        menu_manager.settings.puzzle = Some(puzzles[index].path.clone());
+                    },
+                ),
+            },
+            MenuOption::Action {
+                name: "Play".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&puzzle_game),
+            },
+            MenuOption::Back {
+                name: "Back to Main Menu".to_string(),
+            },
+        ],
+        parent: Some(0),
+    };
+
+    // replay menu, the replays are read from the replay directory every time the list is shown
+    // and the last session is picked until another replay is
+
+    let replay_menu = MenuNode {
+        title: "Replays".to_string(),
+        options: vec![
+            MenuOption::Action {
+                name: "Replay".to_string(),
+                dynamic_value: Some(&|menu_manager| {
+                    let path = menu_manager
+                        .settings
+                        .replay
+                        .clone()
+                        .unwrap_or(Replay::last_path());
+
+                    match Path::new(&path).file_stem() {
+                        Some(name) if Path::new(&path).exists() => {
+                            name.to_string_lossy().into_owned()
+                        }
+                        _ => "None".to_string(),
+                    }
                 }),
-                action: InteractionType::Scancode("quick_reset"),
+                action: InteractionType::Scrollable(
+                    &|menu_manager: &mut MenuManager, increase: bool| {
+                        let replays = Replay::list();
+
+                        if replays.is_empty() {
+                            menu_manager.settings.replay = None;
+                            return;
+                        }
+
+                        let current = replays
+                            .iter()
+                            .position(|path| Some(path) == menu_manager.settings.replay.as_ref());
+
+                        let index = match (current, increase) {
+                            (None, _) => 0,
+                            (Some(index), true) => (index + 1) % replays.len(),
+                            (Some(index), false) => (index + replays.len() - 1) % replays.len(),
+                        };
+
+                        menu_manager.settings.replay = Some(replays[index].clone());
+                    },
+                ),
+            },
+            MenuOption::Action {
+                name: "Watch".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&watch_replay),
             },
             MenuOption::Back {
                 name: "Back to Main Menu".to_string(),
@@ -360,16 +862,167 @@ fn main() -> Result<(), String> {
         parent: Some(0),
     };
 
-    let menus = vec![main_menu, options_menu, controls_menu];
+    // versus menu, a network match is hosted on the port or joined at the address and port. the
+    // cpu speed is also used by watch cpu
+
+    let versus_menu = MenuNode {
+        title: "Versus".to_string(),
+        options: vec![
+            MenuOption::Action {
+                name: "Local".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&local_versus_game),
+            },
+            MenuOption::Action {
+                name: "Versus CPU".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&cpu_versus_game),
+            },
+            MenuOption::Action {
+                name: "CPU Speed".to_string(),
+                dynamic_value: Some(&|menu_manager| {
+                    format!("{:.1} PPS", menu_manager.settings.cpu_speed as f64 / 10.0)
+                }),
+                action: InteractionType::Scrollable(
+                    &|menu_manager: &mut MenuManager, increase: bool| {
+                        if increase {
+                            menu_manager.settings.cpu_speed =
+                                (menu_manager.settings.cpu_speed + 1).min(200);
+                        } else {
+                            menu_manager.settings.cpu_speed =
+                                menu_manager.settings.cpu_speed.saturating_sub(1).max(5);
+                        }
+                    },
+                ),
+            },
+            MenuOption::Action {
+                name: "Host Online".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&host_versus_game),
+            },
+            MenuOption::Action {
+                name: "Join Online".to_string(),
+                dynamic_value: None,
+                action: InteractionType::Toggle(&join_versus_game),
+            },
+            MenuOption::Action {
+                name: "Address".to_string(),
+                dynamic_value: Some(&|menu_manager| menu_manager.settings.network_address.clone()),
+                action: InteractionType::Text(
+                    &|menu_manager: &mut MenuManager, address: Option<String>| {
+                        if let Some(address) = address.filter(|address| !address.is_empty()) {
+                            menu_manager.settings.network_address = address;
+                        }
+                    },
+                ),
+            },
+            MenuOption::Action {
+                name: "Port".to_string(),
+                dynamic_value: Some(&|menu_manager| menu_manager.settings.network_port.to_string()),
+                action: InteractionType::Number(
+                    &|menu_manager: &mut MenuManager, port: Option<u64>| {
+                        if let Some(port) = port.and_then(|port| u16::try_from(port).ok()) {
+                            menu_manager.settings.network_port = port;
+                        }
+                    },
+                ),
+            },
+            MenuOption::Back {
+                name: "Back to Main Menu".to_string(),
+            },
+        ],
+        parent: Some(0),
+    };
+
+    let menus = vec![
+        main_menu,
+        options_menu,
+        controls_menu,
+        sprint_menu,
+        ultra_menu,
+        dig_menu,
+        puzzle_menu,
+        versus_menu,
+        replay_menu,
+    ];
 
     let mut menu_manager: MenuManager =
-        MenuManager::new(&sdl.0, &sdl.1, &mut sdl.2, &mut sdl.3, menus)?;
+        MenuManager::new(&sdl.0, &sdl.1, &mut sdl.2, &mut sdl.3, menus, settings)?;
 
-    menu_manager.run();
+    // a game or replay picked on the command line closes the game when it's left
+
+    match arguments.launch {
+        Launch::Game(game_mode) => start_game(&mut menu_manager, game_mode),
+        Launch::Replay(path) => start_replay(&mut menu_manager, &path),
+        _ => menu_manager.run(),
+    }
 
     Ok(())
 }
 
+fn start_game(menu_manager: &mut MenuManager, game_mode: Gamemode) {
+    let fall_interval = Duration::from_millis(20);
+
+    let game = Game::new(
+        menu_manager.sdl_context,
+        menu_manager.ttf_context,
+        menu_manager.canvas,
+        menu_manager.event_pump,
+        &menu_manager.theme,
+        fall_interval,
+        game_mode,
+        &mut menu_manager.settings,
+    );
+
+    match game {
+        Ok(mut g) => g.run(),
+        Err(e) => println!("Failed to start game: {}", e),
+    }
+}
+
+fn start_replay(menu_manager: &mut MenuManager, path: &str) {
+    let replay = match Replay::load(path) {
+        Ok(r) => r,
+        Err(e) => {
+            println!("Failed to load replay: {}", e);
+            return;
+        }
+    };
+
+    let game = Game::new(
+        menu_manager.sdl_context,
+        menu_manager.ttf_context,
+        menu_manager.canvas,
+        menu_manager.event_pump,
+        &menu_manager.theme,
+        replay.config.fall_interval,
+        replay.game_mode,
+        &mut menu_manager.settings,
+    );
+
+    match game {
+        Ok(mut g) => g.watch(replay),
+        Err(e) => println!("Failed to start game: {}", e),
+    }
+}
+
+fn start_versus(menu_manager: &mut MenuManager, opponent: Opponent) {
+    let versus = Versus::new(
+        menu_manager.sdl_context,
+        menu_manager.ttf_context,
+        menu_manager.canvas,
+        menu_manager.event_pump,
+        &menu_manager.theme,
+        &menu_manager.settings,
+        opponent,
+    );
+
+    match versus {
+        Ok(mut v) => v.run(),
+        Err(e) => println!("Failed to start game: {}", e),
+    }
+}
+
 fn init_sdl() -> Result<
     (
         sdl2::Sdl,
@@ -1,20 +1,23 @@
-use std::{path::Path, thread, time::Duration};
+use std::{thread, time::Duration};
 
 use sdl2::{
+    controller::Button,
     event::Event,
     keyboard::{Keycode, Scancode},
 };
 
 use crate::{
-    init_theme,
-    utilities::{render_bg, render_text, Settings, Theme},
+    init_theme, paths,
+    utilities::{render_bg, render_text, Binding, MenuInput, Settings, Theme},
 };
 
 #[derive(Clone)]
 pub enum InteractionType<'a> {
     Toggle(&'a dyn Fn(&mut MenuManager<'a>)),
     Scrollable(&'a dyn Fn(&mut MenuManager<'a>, bool)),
-    Scancode(&'a str),
+    Binding(Binding),
+    Number(&'a dyn Fn(&mut MenuManager<'a>, Option<u64>)),
+    Text(&'a dyn Fn(&mut MenuManager<'a>, Option<String>)),
 }
 
 #[derive(Clone)]
@@ -33,6 +36,13 @@ pub enum MenuOption<'a> {
     },
 }
 
+// what was pressed while waiting for a new binding
+
+pub enum BindingPress {
+    Key(Scancode),
+    Button(Button),
+}
+
 pub struct MenuNode<'a> {
     pub title: String,
     pub options: Vec<MenuOption<'a>>,
@@ -56,6 +66,7 @@ impl<'a> MenuManager<'a> {
     const CELL_SIZE: u32 = 40;
     const GRID_WIDTH: u32 = 10;
     const GRID_HEIGHT: u32 = 20;
+    const VISIBLE_OPTIONS: usize = 10; // longer menus scroll to keep the selected option shown
 
     pub fn new(
         sdl_context: &'a sdl2::Sdl,
@@ -63,11 +74,10 @@ impl<'a> MenuManager<'a> {
         canvas: &'a mut sdl2::render::Canvas<sdl2::video::Window>,
         event_pump: &'a mut sdl2::EventPump,
         menus: Vec<MenuNode<'a>>,
+        settings: Settings,
     ) -> Result<Self, String> {
-        let font_path = Path::new(&"assets/FreeMono.ttf");
-        let font = ttf_context.load_font(font_path, 22)?;
-
-        let settings = Settings::new().unwrap();
+        let font_path = paths::font();
+        let font = ttf_context.load_font(&font_path, 22)?;
 
         let theme = init_theme(settings.bright_mode);
 
@@ -107,12 +117,41 @@ impl<'a> MenuManager<'a> {
                     toggle_action(self);
                     self.render_current_menu();
                 }
-                InteractionType::Scancode(scancode_string) => {
-                    let new_scancode = self.get_key_press();
+                InteractionType::Binding(binding) => {
+                    let press = self.get_key_press();
+                    let key_bindings = &mut self.settings.key_bindings;
+
+                    // delete takes the second key off and pressing the bound button again
+                    // unbinds it, a key or button that is already used is left alone
+
+                    let taken = match press {
+                        BindingPress::Key(Scancode::Delete) => {
+                            key_bindings.clear_second_key(binding);
+                            false
+                        }
+                        BindingPress::Key(scancode) => {
+                            let taken = key_bindings.is_taken(binding, scancode);
 
-                    // if key is already used show so on the screen and return
+                            if !taken {
+                                key_bindings.bind(binding, scancode);
+                            }
 
-                    if self.settings.key_bindings.contains_scancode(new_scancode) {
+                            taken
+                        }
+                        BindingPress::Button(button) => {
+                            let taken = key_bindings.is_button_taken(binding, button);
+
+                            if key_bindings.button(binding) == Some(button) {
+                                key_bindings.set_button(binding, None);
+                            } else if !taken {
+                                key_bindings.set_button(binding, Some(button));
+                            }
+
+                            taken
+                        }
+                    };
+
+                    if taken {
                         render_bg(
                             self.canvas,
                             self.theme.bg_color_1,
@@ -131,15 +170,18 @@ impl<'a> MenuManager<'a> {
                             400,
                         );
                         thread::sleep(Duration::from_millis(500));
-
-                        self.render_current_menu();
-                        return;
                     }
 
-                    self.settings
-                        .key_bindings
-                        .update_binding(scancode_string, new_scancode);
-
+                    self.render_current_menu();
+                }
+                InteractionType::Number(number_action) => {
+                    let number = self.get_number_input();
+                    number_action(self, number);
+                    self.render_current_menu();
+                }
+                InteractionType::Text(text_action) => {
+                    let text = self.get_text_input();
+                    text_action(self, text);
                     self.render_current_menu();
                 }
                 _ => {}
@@ -200,7 +242,15 @@ impl<'a> MenuManager<'a> {
 
         // render options
 
-        for (index, option) in menu.options.iter().enumerate() {
+        let first_shown = self.current_index.saturating_sub(Self::VISIBLE_OPTIONS - 1);
+
+        for (index, option) in menu
+            .options
+            .iter()
+            .enumerate()
+            .skip(first_shown)
+            .take(Self::VISIBLE_OPTIONS)
+        {
             let prefix = if index == self.current_index {
                 "> "
             } else {
@@ -248,7 +298,7 @@ impl<'a> MenuManager<'a> {
         self.render_current_menu();
     }
 
-    pub fn get_key_press(&mut self) -> Scancode {
+    pub fn get_key_press(&mut self) -> BindingPress {
         render_bg(
             self.canvas,
             self.theme.bg_color_1,
@@ -262,21 +312,150 @@ impl<'a> MenuManager<'a> {
             self.canvas,
             &self.font,
             self.theme.text_color,
-            &"Press Key".to_string(),
-            350,
+            &"Press Key or Button".to_string(),
+            300,
             400,
         );
+
+        let _ = render_text(
+            self.canvas,
+            &self.font,
+            self.theme.text_color,
+            &"Delete removes the second key".to_string(),
+            250,
+            450,
+        );
         loop {
             let events: Vec<Event> = self.event_pump.poll_iter().collect();
 
             for event in events {
-                if let Event::KeyDown {
-                    scancode: Some(scancode),
+                match event {
+                    Event::KeyDown {
+                        scancode: Some(scancode),
+                        ..
+                    } => return BindingPress::Key(scancode),
+                    Event::ControllerButtonDown { button, .. } => {
+                        return BindingPress::Button(button)
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // read digits until return is pressed, an empty or too large input gives none
+
+    pub fn get_number_input(&mut self) -> Option<u64> {
+        let mut digits = String::new();
+
+        loop {
+            render_bg(
+                self.canvas,
+                self.theme.bg_color_1,
+                self.theme.bg_color_2,
+                Self::CELL_SIZE,
+                Self::GRID_WIDTH,
+                Self::GRID_HEIGHT,
+            );
+
+            let _ = render_text(
+                self.canvas,
+                &self.font,
+                self.theme.text_color,
+                &format!("Enter Number: {}_", digits),
+                300,
+                400,
+            );
+
+            let event = self.event_pump.wait_event();
+
+            match event {
+                Event::KeyDown {
+                    keycode: Some(Keycode::Return),
+                    ..
+                } => return digits.parse().ok(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return None,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
                     ..
-                } = event
-                {
-                    return scancode;
+                } => {
+                    digits.pop();
                 }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    let name = keycode.name();
+                    let is_digit = name.len() == 1 && name.chars().all(|c| c.is_ascii_digit());
+
+                    if is_digit && digits.len() < 19 {
+                        digits.push_str(&name);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // read an address or name until return is pressed, escape gives none
+
+    pub fn get_text_input(&mut self) -> Option<String> {
+        let mut text = String::new();
+
+        loop {
+            render_bg(
+                self.canvas,
+                self.theme.bg_color_1,
+                self.theme.bg_color_2,
+                Self::CELL_SIZE,
+                Self::GRID_WIDTH,
+                Self::GRID_HEIGHT,
+            );
+
+            let _ = render_text(
+                self.canvas,
+                &self.font,
+                self.theme.text_color,
+                &format!("Enter Text: {}_", text),
+                300,
+                400,
+            );
+
+            let event = self.event_pump.wait_event();
+
+            match event {
+                Event::KeyDown {
+                    keycode: Some(Keycode::Return),
+                    ..
+                } => return Some(text),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return None,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => {
+                    text.pop();
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    let name = keycode.name().to_lowercase();
+                    let is_allowed = name.len() == 1
+                        && name
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+
+                    if is_allowed && text.len() < 64 {
+                        text.push_str(&name);
+                    }
+                }
+                _ => {}
             }
         }
     }
@@ -288,49 +467,34 @@ impl<'a> MenuManager<'a> {
             let events: Vec<Event> = self.event_pump.poll_iter().collect();
 
             for event in events {
-                match event {
-                    Event::Quit { .. } => break 'running,
-                    Event::KeyDown {
-                        keycode: Some(Keycode::Escape),
-                        ..
-                    } => {
+                if let Event::Quit { .. } = event {
+                    break 'running;
+                }
+
+                match self.settings.key_bindings.menu_input(&event) {
+                    Some(MenuInput::Back) => {
                         if self.current_menu == 0 {
                             break 'running;
                         } else {
                             self.back_to_parent();
                         }
                     }
-                    Event::KeyDown {
-                        keycode: Some(Keycode::Up),
-                        ..
-                    } => {
+                    Some(MenuInput::Up) => {
                         self.move_index(false);
                     }
-                    Event::KeyDown {
-                        keycode: Some(Keycode::Down),
-                        ..
-                    } => {
+                    Some(MenuInput::Down) => {
                         self.move_index(true);
                     }
-                    Event::KeyDown {
-                        keycode: Some(Keycode::Space),
-                        ..
-                    } => {
+                    Some(MenuInput::Confirm) => {
                         self.select_option(self.current_index);
                     }
-                    Event::KeyDown {
-                        keycode: Some(Keycode::Left),
-                        ..
-                    } => {
+                    Some(MenuInput::Left) => {
                         self.scroll_option(self.current_index, false);
                     }
-                    Event::KeyDown {
-                        keycode: Some(Keycode::Right),
-                        ..
-                    } => {
+                    Some(MenuInput::Right) => {
                         self.scroll_option(self.current_index, true);
                     }
-                    _ => {}
+                    None => {}
                 }
             }
         }
@@ -0,0 +1,368 @@
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::engine::{Action, EngineConfig, Input};
+
+// a network match sends one line per message. after the handshake both sides only send the
+// inputs of every step, both run the same two engines from the same seed so the games stay the
+// same without ever sending the boards
+
+pub enum Message {
+    Step(u64, Vec<Input>), // inputs of the sender's player for a step
+    Rematch,
+    Quit,
+}
+
+pub struct Connection {
+    stream: TcpStream,
+    lines: Receiver<String>,
+    pub is_host: bool,
+}
+
+impl Connection {
+    pub const DEFAULT_PORT: u16 = 7878;
+
+    const HEADER: &'static str = "rustris-versus 1";
+    const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub fn listen(port: u16) -> Result<TcpListener, String> {
+        let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|e| e.to_string())?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        Ok(listener)
+    }
+
+    // take the opponent waiting on the listener if there is one, never blocks
+
+    pub fn accept(listener: &TcpListener) -> Result<Option<Self>, String> {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+                Ok(Some(Self::new(stream, true)?))
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn join(address: &str, port: u16) -> Result<Self, String> {
+        let socket_address = (address, port)
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or(format!("could not resolve {}", address))?;
+
+        let stream = TcpStream::connect_timeout(&socket_address, Self::CONNECT_TIMEOUT)
+            .map_err(|e| e.to_string())?;
+
+        Self::new(stream, false)
+    }
+
+    // lines are read on their own thread so the game can check for them without waiting
+
+    fn new(stream: TcpStream, is_host: bool) -> Result<Self, String> {
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+
+        let reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+        let (sender, lines) = mpsc::channel();
+
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            stream,
+            lines,
+            is_host,
+        })
+    }
+
+    // agree on everything the two engines need, the host picks the seed and the starting level
+    // and each player keeps their own handling. the configs come back in player order, the host
+    // is always the first player
+
+    pub fn handshake(&mut self, mut local: EngineConfig) -> Result<[EngineConfig; 2], String> {
+        self.send_line(Self::HEADER)?;
+
+        if self.receive_line()? != Self::HEADER {
+            return Err("the opponent is running a different version".to_string());
+        }
+
+        if self.is_host {
+            self.send_line(&format!(
+                "start {} {} {}",
+                local.seed, local.fixed_seed, local.init_level
+            ))?;
+        } else {
+            let line = self.receive_line()?;
+            let parts: Vec<&str> = line.split(' ').collect();
+
+            if parts.len() != 4 || parts[0] != "start" {
+                return Err(format!("unexpected message \"{}\"", line));
+            }
+
+            local.seed = parse_value(parts[1])?;
+            local.fixed_seed = parse_value(parts[2])?;
+            local.init_level = parse_value(parts[3])?;
+        }
+
+        self.send_line(&format!(
+            "handling {} {} {} {} {}",
+            local.insta_das,
+            local.insta_softdrop,
+            local.repeat_delay.as_millis(),
+            local.repeat_interval.as_millis(),
+            local.soft_drop_interval.as_millis(),
+        ))?;
+
+        let line = self.receive_line()?;
+        let parts: Vec<&str> = line.split(' ').collect();
+
+        if parts.len() != 6 || parts[0] != "handling" {
+            return Err(format!("unexpected message \"{}\"", line));
+        }
+
+        let mut remote = local.clone();
+        remote.insta_das = parse_value(parts[1])?;
+        remote.insta_softdrop = parse_value(parts[2])?;
+        remote.repeat_delay = Duration::from_millis(parse_value(parts[3])?);
+        remote.repeat_interval = Duration::from_millis(parse_value(parts[4])?);
+        remote.soft_drop_interval = Duration::from_millis(parse_value(parts[5])?);
+
+        if self.is_host {
+            Ok([local, remote])
+        } else {
+            Ok([remote, local])
+        }
+    }
+
+    pub fn send(&mut self, message: &Message) -> Result<(), String> {
+        let line = match message {
+            Message::Step(step, inputs) => {
+                let mut line = format!("step {}", step);
+
+                for input in inputs {
+                    let (kind, action) = match input {
+                        Input::Press(action) => ("press", action),
+                        Input::Release(action) => ("release", action),
+                    };
+
+                    line += &format!(" {} {}", kind, action.name());
+                }
+
+                line
+            }
+            Message::Rematch => "rematch".to_string(),
+            Message::Quit => "quit".to_string(),
+        };
+
+        self.send_line(&line)
+    }
+
+    // the next message if one has arrived, an error once the opponent is gone
+
+    pub fn receive(&mut self) -> Result<Option<Message>, String> {
+        let line = match self.lines.try_recv() {
+            Ok(line) => line,
+            Err(TryRecvError::Empty) => return Ok(None),
+            Err(TryRecvError::Disconnected) => return Err("connection closed".to_string()),
+        };
+
+        let parts: Vec<&str> = line.split(' ').collect();
+
+        match parts[0] {
+            "step" if parts.len().is_multiple_of(2) => {
+                let step = parse_value(parts[1])?;
+                let mut inputs = Vec::new();
+
+                for pair in parts[2..].chunks(2) {
+                    let action = Action::from_name(pair[1])
+                        .ok_or(format!("unknown action \"{}\"", pair[1]))?;
+
+                    inputs.push(match pair[0] {
+                        "press" => Input::Press(action),
+                        "release" => Input::Release(action),
+                        _ => return Err(format!("unexpected message \"{}\"", line)),
+                    });
+                }
+
+                Ok(Some(Message::Step(step, inputs)))
+            }
+            "rematch" => Ok(Some(Message::Rematch)),
+            "quit" => Ok(Some(Message::Quit)),
+            _ => Err(format!("unexpected message \"{}\"", line)),
+        }
+    }
+
+    fn send_line(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.stream, "{}", line).map_err(|e| e.to_string())
+    }
+
+    fn receive_line(&mut self) -> Result<String, String> {
+        match self.lines.recv_timeout(Self::HANDSHAKE_TIMEOUT) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err("the opponent did not answer".to_string()),
+            Err(RecvTimeoutError::Disconnected) => Err("connection closed".to_string()),
+        }
+    }
+}
+
+// closing the socket also ends the reading thread
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value \"{}\" from the opponent", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn config(seed: u64, init_level: u32, repeat_delay: u64) -> EngineConfig {
+        EngineConfig {
+            insta_das: false,
+            insta_softdrop: true,
+            repeat_delay: Duration::from_millis(repeat_delay),
+            repeat_interval: Duration::from_millis(25),
+            soft_drop_interval: Duration::from_millis(20),
+            fall_interval: Duration::from_millis(1000),
+            init_level,
+            seed,
+            fixed_seed: seed == 42,
+            dig_messiness: 40,
+            dig_rise_pieces: 5,
+            dig_rise_interval: Duration::from_millis(7000),
+            puzzle: None,
+        }
+    }
+
+    // host on a free local port and let a second thread join and shake hands with it
+
+    fn connect() -> (Connection, [EngineConfig; 2], Connection, [EngineConfig; 2]) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let guest = thread::spawn(move || {
+            let mut connection = Connection::join("127.0.0.1", port).unwrap();
+            let configs = connection.handshake(config(1, 1, 200)).unwrap();
+            (connection, configs)
+        });
+
+        let start = Instant::now();
+        let mut host = loop {
+            if let Some(connection) = Connection::accept(&listener).unwrap() {
+                break connection;
+            }
+
+            assert!(start.elapsed() < Duration::from_secs(5), "nobody joined");
+            thread::sleep(Duration::from_millis(1));
+        };
+
+        let host_configs = host.handshake(config(42, 7, 100)).unwrap();
+        let (guest, guest_configs) = guest.join().unwrap();
+
+        (host, host_configs, guest, guest_configs)
+    }
+
+    // wait for the next message, receive itself never blocks
+
+    fn next_message(connection: &mut Connection) -> Result<Message, String> {
+        let start = Instant::now();
+
+        while start.elapsed() < Duration::from_secs(5) {
+            if let Some(message) = connection.receive()? {
+                return Ok(message);
+            }
+
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        panic!("no message arrived");
+    }
+
+    #[test]
+    fn both_players_agree_on_the_game() {
+        let (host, host_configs, guest, guest_configs) = connect();
+
+        assert!(host.is_host);
+        assert!(!guest.is_host);
+
+        for configs in [&host_configs, &guest_configs] {
+            for config in configs {
+                assert_eq!(config.seed, 42);
+                assert!(config.fixed_seed);
+                assert_eq!(config.init_level, 7);
+            }
+
+            // each player keeps their own handling, the host is always the first player
+
+            assert_eq!(configs[0].repeat_delay, Duration::from_millis(100));
+            assert_eq!(configs[1].repeat_delay, Duration::from_millis(200));
+        }
+    }
+
+    #[test]
+    fn messages_arrive_intact() {
+        let (mut host, _, mut guest, _) = connect();
+
+        let inputs = vec![
+            Input::Press(Action::MoveLeft),
+            Input::Release(Action::MoveLeft),
+            Input::Press(Action::HardDrop),
+        ];
+
+        guest.send(&Message::Step(12, inputs.clone())).unwrap();
+        guest.send(&Message::Step(13, Vec::new())).unwrap();
+        guest.send(&Message::Rematch).unwrap();
+        guest.send(&Message::Quit).unwrap();
+
+        assert!(matches!(
+            next_message(&mut host),
+            Ok(Message::Step(12, received)) if received == inputs
+        ));
+        assert!(matches!(
+            next_message(&mut host),
+            Ok(Message::Step(13, received)) if received.is_empty()
+        ));
+        assert!(matches!(next_message(&mut host), Ok(Message::Rematch)));
+        assert!(matches!(next_message(&mut host), Ok(Message::Quit)));
+
+        host.send(&Message::Step(1, vec![Input::Press(Action::Hold)]))
+            .unwrap();
+
+        assert!(matches!(
+            next_message(&mut guest),
+            Ok(Message::Step(1, received)) if received == [Input::Press(Action::Hold)]
+        ));
+    }
+
+    #[test]
+    fn a_dropped_opponent_is_an_error() {
+        let (mut host, _, guest, _) = connect();
+
+        drop(guest);
+
+        assert!(next_message(&mut host).is_err());
+    }
+}
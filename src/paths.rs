@@ -0,0 +1,164 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// settings are kept in the platform's config directory and scores, replays and the player's own
+// puzzles in its data directory. the data directory flag or environment variable puts everything
+// in one directory laid out like the folders the game used to make next to itself. the font and
+// the puzzles that come with the game are looked up next to the executable
+
+const APP_NAME: &str = "rustris";
+const DATA_DIR_VARIABLE: &str = "RUSTRIS_DATA_DIR";
+const FONT: &str = "assets/FreeMono.ttf";
+
+struct Directories {
+    config: PathBuf,
+    data: PathBuf,
+    install: PathBuf, // where the assets and bundled puzzles are
+}
+
+static DIRECTORIES: OnceLock<Directories> = OnceLock::new();
+
+// pick the directories before anything is loaded, a directory passed on the command line wins
+// over the environment variable
+
+pub fn init(data_dir: Option<PathBuf>) {
+    let _ = DIRECTORIES.set(Directories::new(data_dir));
+}
+
+fn directories() -> &'static Directories {
+    DIRECTORIES.get_or_init(|| Directories::new(None))
+}
+
+impl Directories {
+    fn new(data_dir: Option<PathBuf>) -> Self {
+        let data_dir = data_dir.or_else(|| env::var_os(DATA_DIR_VARIABLE).map(PathBuf::from));
+
+        let (config, data) = match data_dir {
+            Some(data_dir) => (data_dir.join("settings"), data_dir),
+            None => platform_directories(),
+        };
+
+        Self {
+            config,
+            data,
+            install: install_directory(),
+        }
+    }
+}
+
+// xdg on linux, application support on macos and the roaming app data on windows. without a
+// home directory the game falls back to the working directory like it used to
+
+fn platform_directories() -> (PathBuf, PathBuf) {
+    let absolute = |name: &str| {
+        env::var_os(name)
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+    };
+
+    let home = absolute("HOME");
+
+    if cfg!(target_os = "windows") {
+        if let Some(app_data) = absolute("APPDATA") {
+            let data = app_data.join(APP_NAME);
+            return (data.join("settings"), data);
+        }
+    } else if cfg!(target_os = "macos") {
+        if let Some(home) = home {
+            let data = home.join("Library/Application Support").join(APP_NAME);
+            return (data.join("settings"), data);
+        }
+    } else {
+        let config = absolute("XDG_CONFIG_HOME").or(home.as_ref().map(|home| home.join(".config")));
+        let data =
+            absolute("XDG_DATA_HOME").or(home.as_ref().map(|home| home.join(".local/share")));
+
+        if let (Some(config), Some(data)) = (config, data) {
+            return (config.join(APP_NAME), data.join(APP_NAME));
+        }
+    }
+
+    (PathBuf::from("settings"), PathBuf::from("."))
+}
+
+// the first directory with the font in it, starting next to the executable and going up so a
+// build in the target directory finds the assets of the project, then the working directory
+
+fn install_directory() -> PathBuf {
+    let executable = env::current_exe().ok();
+    let executable_directories = executable.iter().flat_map(|path| path.ancestors().skip(1));
+
+    executable_directories
+        .map(Path::to_path_buf)
+        .chain(env::current_dir().ok())
+        .find(|directory| directory.join(FONT).is_file())
+        .unwrap_or_default()
+}
+
+pub fn font() -> PathBuf {
+    directories().install.join(FONT)
+}
+
+pub fn bundled_puzzles() -> PathBuf {
+    directories().install.join("puzzles")
+}
+
+pub fn config_file(name: &str) -> String {
+    path_string(&directories().config.join(name))
+}
+
+// a file in the data directory, the name can start with the folder it's kept in
+
+pub fn data_file(name: &str) -> String {
+    path_string(&directories().data.join(name))
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+// move the settings, scores and replays the game used to keep in the working directory to where
+// they are kept now, nothing is moved over files that are already there
+
+pub fn migrate_legacy_folders() {
+    let folders = [
+        ("settings", directories().config.clone()),
+        ("score", directories().data.join("score")),
+        ("replay", directories().data.join("replay")),
+    ];
+
+    for (folder, destination) in folders {
+        let source = Path::new(folder);
+
+        if !source.is_dir() || destination.exists() {
+            continue;
+        }
+
+        if let Some(parent) = destination.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+
+        // a rename can't cross file systems, the files are copied instead and the old folder is
+        // left alone
+
+        if fs::rename(source, &destination).is_err() {
+            let _ = copy_folder(source, &destination);
+        }
+    }
+}
+
+fn copy_folder(source: &Path, destination: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(destination)?;
+
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+
+        if entry.file_type()?.is_file() {
+            fs::copy(entry.path(), destination.join(entry.file_name()))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,346 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::engine::ClearType;
+use crate::paths;
+use crate::tetrominos::{Shape, TSpin, Tetromino};
+use crate::utilities::{Cell, GARBAGE_COLOR};
+
+// a puzzle is a board to start from, the pieces to solve it with and what counts as solving it.
+// a puzzle without a goal is just a starting position, its queue is followed by random pieces
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PuzzleGoal {
+    Lines(u32),       // clear at least this many lines
+    PerfectClear,     // empty the map with a line clear
+    Clear(ClearType), // make this clear at least once, like a t-spin triple
+}
+
+impl PuzzleGoal {
+    pub fn name(&self) -> String {
+        match self {
+            PuzzleGoal::Lines(lines) => format!("Clear {} Lines", lines),
+            PuzzleGoal::PerfectClear => "Perfect Clear".to_string(),
+            PuzzleGoal::Clear(clear_type) => clear_type.name(),
+        }
+    }
+
+    // how the goal is written in a puzzle file
+
+    pub fn id(&self) -> String {
+        match self {
+            PuzzleGoal::Lines(lines) => format!("lines {}", lines),
+            PuzzleGoal::PerfectClear => "perfect_clear".to_string(),
+            PuzzleGoal::Clear(clear_type) => format!("clear {}", clear_type.name()),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Puzzle {
+    pub path: String,
+    pub name: String,
+    pub map: [[Cell; 10]; 20],
+    pub queue: Vec<Shape>,
+    pub hold: Option<Shape>,
+    pub allow_hold: bool,
+    pub goals: Vec<PuzzleGoal>, // every goal has to be met to solve the puzzle
+    pub piece_limit: Option<u32>,
+}
+
+impl Puzzle {
+    const HEADER: &'static str = "rustris-puzzle 1";
+    pub const DIRECTORY: &'static str = "puzzles";
+
+    // the file starts with key value lines and ends with the board, one line of ten cells per row
+    // aligned to the bottom of the map. a cell is "." when empty, a piece letter for a cell in
+    // that piece's color or "X" for garbage
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::from_text(&content, path)
+    }
+
+    // a puzzle written out as it is in its file, the path is only kept to tell puzzles apart
+
+    pub fn from_text(content: &str, path: &str) -> Result<Self, String> {
+        let mut lines = content.lines();
+
+        if lines.next() != Some(Self::HEADER) {
+            return Err(format!("{} is not a puzzle file", path));
+        }
+
+        let mut name = path.to_string();
+        let mut queue = Vec::new();
+        let mut hold = None;
+        let mut allow_hold = true;
+        let mut goals = Vec::new();
+        let mut piece_limit = None;
+
+        for line in lines.by_ref() {
+            if line == "board" {
+                break;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+
+            match key {
+                "name" => name = value.to_string(),
+                "queue" => {
+                    queue = value
+                        .split_whitespace()
+                        .map(parse_shape)
+                        .collect::<Result<_, _>>()?
+                }
+                "hold" if value == "-" => hold = None,
+                "hold" => hold = Some(parse_shape(value)?),
+                "allow_hold" => allow_hold = value == "true",
+                "goal" => goals.push(parse_goal(value)?),
+                "pieces" => {
+                    piece_limit = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid piece count \"{}\"", value))?,
+                    )
+                }
+                "" => {}
+                _ => return Err(format!("unknown key \"{}\" in puzzle", key)),
+            }
+        }
+
+        let rows: Vec<&str> = lines.filter(|line| !line.is_empty()).collect();
+
+        if rows.len() > 20 {
+            return Err("puzzle board is higher than the map".to_string());
+        }
+
+        let mut map = [[Cell {
+            color: None,
+            occupied: false,
+        }; 10]; 20];
+
+        let top = map.len() - rows.len();
+
+        for (row_index, row) in rows.iter().enumerate() {
+            if row.chars().count() != 10 {
+                return Err(format!("puzzle row \"{}\" is not ten cells wide", row));
+            }
+
+            for (cell, character) in map[top + row_index].iter_mut().zip(row.chars()) {
+                *cell = parse_cell(character)?;
+            }
+        }
+
+        if queue.is_empty() && !goals.is_empty() {
+            return Err("puzzle has no pieces in its queue".to_string());
+        }
+
+        Ok(Self {
+            path: path.to_string(),
+            name,
+            map,
+            queue,
+            hold,
+            allow_hold,
+            goals,
+            piece_limit,
+        })
+    }
+
+    pub fn empty(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            name: "Custom".to_string(),
+            map: [[Cell {
+                color: None,
+                occupied: false,
+            }; 10]; 20],
+            queue: Vec::new(),
+            hold: None,
+            allow_hold: true,
+            goals: Vec::new(),
+            piece_limit: None,
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        fs::write(path, self.to_text()).map_err(|e| e.to_string())
+    }
+
+    // the board is written from the highest row with anything in it down
+
+    pub fn to_text(&self) -> String {
+        let queue: Vec<&str> = self.queue.iter().map(shape_name).collect();
+
+        let mut content = format!("{}\n", Self::HEADER);
+        content += &format!("name {}\n", self.name);
+        content += &format!("queue {}\n", queue.join(" "));
+        content += &format!("hold {}\n", self.hold.as_ref().map_or("-", shape_name));
+        content += &format!("allow_hold {}\n", self.allow_hold);
+
+        for goal in self.goals.iter() {
+            content += &format!("goal {}\n", goal.id());
+        }
+
+        if let Some(piece_limit) = self.piece_limit {
+            content += &format!("pieces {}\n", piece_limit);
+        }
+
+        content += "board\n";
+
+        let top = self
+            .map
+            .iter()
+            .position(|row| row.iter().any(|cell| cell.occupied))
+            .unwrap_or(self.map.len());
+
+        for row in self.map.iter().skip(top) {
+            let row: String = row.iter().map(cell_character).collect();
+            content += &format!("{}\n", row);
+        }
+
+        content
+    }
+
+    // the puzzles that come with the game followed by the player's own, each sorted by file name.
+    // files that fail to load are left out
+
+    pub fn list() -> Vec<Puzzle> {
+        let mut directories = vec![paths::bundled_puzzles()];
+        let user_directory = PathBuf::from(paths::data_file(Self::DIRECTORY));
+
+        if !directories.contains(&user_directory) {
+            directories.push(user_directory);
+        }
+
+        let mut puzzles = Vec::new();
+
+        for directory in directories {
+            let mut paths: Vec<String> = fs::read_dir(directory)
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.path().to_str().map(|path| path.to_string()))
+                .collect();
+
+            paths.sort();
+            puzzles.extend(paths.iter().filter_map(|path| Self::load(path).ok()));
+        }
+
+        puzzles
+    }
+
+    pub fn goal_name(&self) -> String {
+        if self.goals.is_empty() {
+            return "Free Play".to_string();
+        }
+
+        self.goals
+            .iter()
+            .map(|goal| goal.name())
+            .collect::<Vec<String>>()
+            .join(" + ")
+    }
+}
+
+pub fn shape_name(shape: &Shape) -> &'static str {
+    match shape {
+        Shape::I => "I",
+        Shape::O => "O",
+        Shape::T => "T",
+        Shape::S => "S",
+        Shape::Z => "Z",
+        Shape::J => "J",
+        Shape::L => "L",
+    }
+}
+
+fn parse_shape(name: &str) -> Result<Shape, String> {
+    match name {
+        "I" => Ok(Shape::I),
+        "O" => Ok(Shape::O),
+        "T" => Ok(Shape::T),
+        "S" => Ok(Shape::S),
+        "Z" => Ok(Shape::Z),
+        "J" => Ok(Shape::J),
+        "L" => Ok(Shape::L),
+        _ => Err(format!("unknown piece \"{}\" in puzzle", name)),
+    }
+}
+
+fn parse_cell(character: char) -> Result<Cell, String> {
+    match character {
+        '.' => Ok(Cell {
+            color: None,
+            occupied: false,
+        }),
+        'X' => Ok(Cell {
+            color: Some(GARBAGE_COLOR),
+            occupied: true,
+        }),
+        _ => Ok(Cell {
+            color: Some(Tetromino::new(parse_shape(&character.to_string())?).color),
+            occupied: true,
+        }),
+    }
+}
+
+// cells are saved by the piece whose color they have, anything else is garbage
+
+fn cell_character(cell: &Cell) -> char {
+    if !cell.occupied {
+        return '.';
+    }
+
+    [
+        Shape::I,
+        Shape::O,
+        Shape::T,
+        Shape::S,
+        Shape::Z,
+        Shape::J,
+        Shape::L,
+    ]
+    .iter()
+    .find(|shape| Some(Tetromino::new((*shape).clone()).color) == cell.color)
+    .map_or('X', |shape| shape_name(shape).chars().next().unwrap())
+}
+
+// goals are written as "lines 4", "perfect_clear" or "clear" followed by the name of the clear as
+// it's shown in game, like "clear T-Spin Triple"
+
+fn parse_goal(value: &str) -> Result<PuzzleGoal, String> {
+    let (kind, argument) = value.split_once(' ').unwrap_or((value, ""));
+
+    match kind {
+        "lines" => argument
+            .parse()
+            .map(PuzzleGoal::Lines)
+            .map_err(|_| format!("invalid line count \"{}\"", argument)),
+        "perfect_clear" => Ok(PuzzleGoal::PerfectClear),
+        "clear" => {
+            let clear_types = (0..=4).flat_map(|lines| {
+                [TSpin::None, TSpin::Mini, TSpin::Full]
+                    .into_iter()
+                    .map(move |t_spin| ClearType {
+                        lines,
+                        t_spin,
+                        perfect_clear: false,
+                    })
+            });
+
+            clear_types
+                .filter(|clear_type| clear_type.lines > 0 || clear_type.t_spin != TSpin::None)
+                .find(|clear_type| clear_type.name().eq_ignore_ascii_case(argument))
+                .map(PuzzleGoal::Clear)
+                .ok_or(format!("unknown clear \"{}\" in puzzle", argument))
+        }
+        _ => Err(format!("unknown goal \"{}\" in puzzle", value)),
+    }
+}
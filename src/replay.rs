@@ -0,0 +1,650 @@
+use std::{fs, path::Path, time::Duration};
+
+use crate::engine::{Action, Engine, EngineConfig, GameEvent, Input};
+use crate::paths;
+use crate::puzzle::Puzzle;
+use crate::utilities::Gamemode;
+
+// a replay stores everything the engine needs to re-simulate a session: the config it was
+// started with, every input together with the tick it was applied on and any handling changes
+// made from the pause menu
+
+pub struct Replay {
+    pub game_mode: Gamemode,
+    pub config: EngineConfig,
+    pub inputs: Vec<(u64, Input)>,
+    pub config_changes: Vec<(u64, EngineConfig)>,
+    pub end_tick: u64,
+    cursor: usize,
+    config_cursor: usize,
+}
+
+impl Replay {
+    const HEADER: &'static str = "rustris-replay";
+    const VERSION: u32 = 2; // version 1 only kept the path of the puzzle
+    pub const DIRECTORY: &'static str = "replay";
+    pub const LAST: &'static str = "last.txt"; // the last session played, kept on every exit
+
+    pub fn new(game_mode: Gamemode, config: EngineConfig) -> Self {
+        Self {
+            game_mode,
+            config,
+            inputs: Vec::new(),
+            config_changes: Vec::new(),
+            end_tick: 0,
+            cursor: 0,
+            config_cursor: 0,
+        }
+    }
+
+    // the replay of the last session followed by the saved ones, sorted by file name
+
+    pub fn list() -> Vec<String> {
+        let mut paths: Vec<String> = fs::read_dir(paths::data_file(Self::DIRECTORY))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "txt"))
+            .filter_map(|entry| entry.path().to_str().map(|path| path.to_string()))
+            .collect();
+
+        paths.sort_by_key(|path| (!path.ends_with(Self::LAST), path.clone()));
+        paths
+    }
+
+    pub fn last_path() -> String {
+        paths::data_file(&format!("{}/{}", Self::DIRECTORY, Self::LAST))
+    }
+
+    pub fn record(&mut self, tick: u64, inputs: &[Input]) {
+        self.inputs
+            .extend(inputs.iter().map(|input| (tick, *input)));
+        self.end_tick = tick;
+    }
+
+    pub fn record_config(&mut self, tick: u64, config: &EngineConfig) {
+        self.config_changes.push((tick, config.clone()));
+    }
+
+    // advance the engine by one tick with whatever was recorded for it
+
+    pub fn step(&mut self, engine: &mut Engine) -> Vec<GameEvent> {
+        let tick = engine.ticks() + 1;
+
+        while let Some((change_tick, config)) = self.config_changes.get(self.config_cursor) {
+            if *change_tick > tick {
+                break;
+            }
+
+            engine.config = config.clone();
+            self.config_cursor += 1;
+        }
+
+        let inputs = self.inputs_at(tick);
+        engine.tick(&inputs)
+    }
+
+    // hand out the inputs recorded for the given tick, ticks have to be asked for in order
+
+    pub fn inputs_at(&mut self, tick: u64) -> Vec<Input> {
+        let mut inputs = Vec::new();
+
+        while let Some((input_tick, input)) = self.inputs.get(self.cursor) {
+            if *input_tick > tick {
+                break;
+            }
+
+            if *input_tick == tick {
+                inputs.push(*input);
+            }
+
+            self.cursor += 1;
+        }
+
+        inputs
+    }
+
+    // tick of the last quick reset, playback skips ahead to here so only the final game is shown
+
+    pub fn last_game_start(&self) -> u64 {
+        self.inputs
+            .iter()
+            .rev()
+            .find(|(_, input)| *input == Input::Press(Action::QuickReset))
+            .map(|(tick, _)| *tick)
+            .unwrap_or(0)
+    }
+
+    pub fn save(&self, file_path: &str) -> Result<(), String> {
+        if let Some(parent) = Path::new(file_path).parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        let header = [
+            ("mode", self.game_mode.id()),
+            ("seed", self.config.seed.to_string()),
+            ("fixed_seed", self.config.fixed_seed.to_string()),
+            ("insta_das", self.config.insta_das.to_string()),
+            ("insta_softdrop", self.config.insta_softdrop.to_string()),
+            (
+                "repeat_delay",
+                self.config.repeat_delay.as_millis().to_string(),
+            ),
+            (
+                "repeat_interval",
+                self.config.repeat_interval.as_millis().to_string(),
+            ),
+            (
+                "soft_drop_interval",
+                self.config.soft_drop_interval.as_millis().to_string(),
+            ),
+            (
+                "fall_interval",
+                self.config.fall_interval.as_millis().to_string(),
+            ),
+            ("init_level", self.config.init_level.to_string()),
+            ("dig_messiness", self.config.dig_messiness.to_string()),
+            ("dig_rise_pieces", self.config.dig_rise_pieces.to_string()),
+            (
+                "dig_rise_interval",
+                self.config.dig_rise_interval.as_millis().to_string(),
+            ),
+            ("end", self.end_tick.to_string()),
+        ];
+
+        let mut content = format!("{} {}\n", Self::HEADER, Self::VERSION);
+
+        for (key, value) in header.iter() {
+            content += &format!("{} {}\n", key, value);
+        }
+
+        // the whole puzzle is kept since its file can change or go away, the edited board is
+        // saved over the same file every time
+
+        if let Some(puzzle) = &self.config.puzzle {
+            let puzzle_text = puzzle.to_text();
+
+            content += &format!("puzzle {}\n", puzzle.path);
+            content += &format!("puzzle_lines {}\n", puzzle_text.lines().count());
+            content += &puzzle_text;
+        }
+
+        for (tick, input) in self.inputs.iter() {
+            let (kind, action) = match input {
+                Input::Press(action) => ("press", action),
+                Input::Release(action) => ("release", action),
+            };
+
+            content += &format!("{} {} {}\n", tick, kind, action.name());
+        }
+
+        for (tick, config) in self.config_changes.iter() {
+            content += &format!(
+                "{} config {} {} {} {} {}\n",
+                tick,
+                config.insta_das,
+                config.insta_softdrop,
+                config.repeat_delay.as_millis(),
+                config.repeat_interval.as_millis(),
+                config.soft_drop_interval.as_millis(),
+            );
+        }
+
+        fs::write(file_path, content).map_err(|e| e.to_string())
+    }
+
+    pub fn load(file_path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(file_path).map_err(|e| e.to_string())?;
+        let mut lines = content.lines().peekable();
+
+        let version: u32 = match lines.next().and_then(|line| line.split_once(' ')) {
+            Some((header, version)) if header == Self::HEADER => parse_value(version)?,
+            _ => return Err(format!("{} is not a replay file", file_path)),
+        };
+
+        if version > Self::VERSION {
+            return Err(format!(
+                "{} was saved by a newer version of the game",
+                file_path
+            ));
+        }
+
+        // read the header values in the order they were saved, values added in later versions
+        // may be missing from older replays
+
+        let mut value = |key: &str, default: Option<&str>| -> Result<String, String> {
+            let found = match lines.peek().and_then(|line| line.split_once(' ')) {
+                Some((found_key, value)) if found_key == key => Some(value.to_string()),
+                _ => None,
+            };
+
+            match (found, default) {
+                (Some(value), _) => {
+                    lines.next();
+                    Ok(value)
+                }
+                (None, Some(default)) => Ok(default.to_string()),
+                (None, None) => Err(format!("expected {} in replay", key)),
+            }
+        };
+
+        let game_mode = Gamemode::from_id(&value("mode", None)?)
+            .ok_or("unknown game mode in replay".to_string())?;
+        let seed = parse_value(&value("seed", None)?)?;
+        let fixed_seed = parse_value(&value("fixed_seed", None)?)?;
+        let insta_das = parse_value(&value("insta_das", None)?)?;
+        let insta_softdrop = parse_value(&value("insta_softdrop", None)?)?;
+        let repeat_delay = Duration::from_millis(parse_value(&value("repeat_delay", None)?)?);
+        let repeat_interval = Duration::from_millis(parse_value(&value("repeat_interval", None)?)?);
+        let soft_drop_interval =
+            Duration::from_millis(parse_value(&value("soft_drop_interval", None)?)?);
+        let fall_interval = Duration::from_millis(parse_value(&value("fall_interval", None)?)?);
+        let init_level = parse_value(&value("init_level", None)?)?;
+        let dig_messiness = parse_value(&value("dig_messiness", Some("0"))?)?;
+        let dig_rise_pieces = parse_value(&value("dig_rise_pieces", Some("0"))?)?;
+        let dig_rise_interval =
+            Duration::from_millis(parse_value(&value("dig_rise_interval", Some("0"))?)?);
+        let end_tick = parse_value(&value("end", None)?)?;
+        let puzzle_path = value("puzzle", Some(""))?;
+        let puzzle_lines: usize = parse_value(&value("puzzle_lines", Some("0"))?)?;
+
+        // older replays only have the path of the puzzle and load it from there
+
+        let puzzle = match (puzzle_path.as_str(), puzzle_lines) {
+            ("", _) => None,
+            (path, 0) => Some(Puzzle::load(path)?),
+            (path, count) => {
+                let puzzle_text: Vec<&str> = lines.by_ref().take(count).collect();
+                Some(Puzzle::from_text(&puzzle_text.join("\n"), path)?)
+            }
+        };
+
+        let config = EngineConfig {
+            insta_das,
+            insta_softdrop,
+            repeat_delay,
+            repeat_interval,
+            soft_drop_interval,
+            fall_interval,
+            init_level,
+            seed,
+            fixed_seed,
+            dig_messiness,
+            dig_rise_pieces,
+            dig_rise_interval,
+            puzzle,
+        };
+
+        let mut inputs = Vec::new();
+        let mut config_changes = Vec::new();
+
+        for line in lines {
+            let parts: Vec<&str> = line.split(' ').collect();
+
+            // handling changes only replace the values that can be changed while playing
+
+            if parts.len() == 7 && parts[1] == "config" {
+                let mut changed = config.clone();
+                changed.insta_das = parse_value(parts[2])?;
+                changed.insta_softdrop = parse_value(parts[3])?;
+                changed.repeat_delay = Duration::from_millis(parse_value(parts[4])?);
+                changed.repeat_interval = Duration::from_millis(parse_value(parts[5])?);
+                changed.soft_drop_interval = Duration::from_millis(parse_value(parts[6])?);
+
+                config_changes.push((parse_value(parts[0])?, changed));
+                continue;
+            }
+
+            if parts.len() != 3 {
+                return Err(format!("invalid replay input \"{}\"", line));
+            }
+
+            let tick: u64 = parse_value(parts[0])?;
+            let action = Action::from_name(parts[2])
+                .ok_or(format!("unknown action in replay \"{}\"", parts[2]))?;
+
+            let input = match parts[1] {
+                "press" => Input::Press(action),
+                "release" => Input::Release(action),
+                _ => return Err(format!("invalid replay input \"{}\"", line)),
+            };
+
+            inputs.push((tick, input));
+        }
+
+        Ok(Self {
+            game_mode,
+            config,
+            inputs,
+            config_changes,
+            end_tick,
+            cursor: 0,
+            config_cursor: 0,
+        })
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value \"{}\" in replay", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::TICK;
+    use std::env;
+
+    fn config() -> EngineConfig {
+        EngineConfig {
+            insta_das: false,
+            insta_softdrop: true,
+            repeat_delay: Duration::from_millis(120),
+            repeat_interval: Duration::from_millis(25),
+            soft_drop_interval: Duration::from_millis(20),
+            fall_interval: Duration::from_millis(1000),
+            init_level: 3,
+            seed: 99,
+            fixed_seed: false,
+            dig_messiness: 40,
+            dig_rise_pieces: 5,
+            dig_rise_interval: Duration::from_millis(7000),
+            puzzle: None,
+        }
+    }
+
+    // a file in the temporary directory that no other test run uses
+
+    fn temp_file(name: &str) -> String {
+        env::temp_dir()
+            .join(format!("rustris-test-{}-{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn board(engine: &Engine) -> Vec<String> {
+        engine
+            .state
+            .map
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| if cell.occupied { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // play a short session, pressing a different action every so often, and record it
+
+    fn play(engine: &mut Engine, replay: &mut Replay) {
+        let actions = [
+            Action::MoveLeft,
+            Action::RotateClockwise,
+            Action::HardDrop,
+            Action::MoveRight,
+            Action::MoveRight,
+            Action::Hold,
+            Action::Rotate180,
+            Action::SoftDrop,
+            Action::HardDrop,
+            Action::RotateCounterClockwise,
+            Action::HardDrop,
+        ];
+
+        for (index, action) in actions.iter().cycle().take(120).enumerate() {
+            for (inputs, wait) in [(Input::Press(*action), 40), (Input::Release(*action), 60)] {
+                let tick = engine.ticks() + 1;
+                replay.record(tick, &[inputs]);
+                engine.tick(&[inputs]);
+
+                for _ in 0..wait + index % 7 {
+                    engine.tick(&[]);
+                }
+            }
+
+            if index == 60 {
+                let mut changed = engine.config.clone();
+                changed.insta_das = true;
+                changed.repeat_delay = Duration::from_millis(90);
+
+                let tick = engine.ticks() + 1;
+                replay.record_config(tick, &changed);
+                engine.config = changed;
+            }
+        }
+
+        replay.end_tick = engine.ticks();
+    }
+
+    #[test]
+    fn saved_replays_load_the_same() {
+        let mut replay = Replay::new(Gamemode::Dig(12), config());
+        replay.record(5, &[Input::Press(Action::MoveLeft)]);
+        replay.record(
+            9,
+            &[Input::Release(Action::MoveLeft), Input::Press(Action::Hold)],
+        );
+        replay.record_config(
+            12,
+            &EngineConfig {
+                insta_das: true,
+                repeat_delay: Duration::from_millis(80),
+                ..config()
+            },
+        );
+        replay.end_tick = 30;
+
+        let file = temp_file("round_trip.txt");
+        replay.save(&file).unwrap();
+        let loaded = Replay::load(&file).unwrap();
+        let _ = fs::remove_file(&file);
+
+        assert!(loaded.game_mode == Gamemode::Dig(12));
+        assert_eq!(loaded.end_tick, 30);
+        assert_eq!(loaded.inputs, replay.inputs);
+
+        let config = &loaded.config;
+        assert_eq!(config.seed, 99);
+        assert!(!config.fixed_seed);
+        assert!(!config.insta_das);
+        assert!(config.insta_softdrop);
+        assert_eq!(config.repeat_delay, Duration::from_millis(120));
+        assert_eq!(config.repeat_interval, Duration::from_millis(25));
+        assert_eq!(config.soft_drop_interval, Duration::from_millis(20));
+        assert_eq!(config.fall_interval, Duration::from_millis(1000));
+        assert_eq!(config.init_level, 3);
+        assert_eq!(config.dig_messiness, 40);
+        assert_eq!(config.dig_rise_pieces, 5);
+        assert_eq!(config.dig_rise_interval, Duration::from_millis(7000));
+
+        assert_eq!(loaded.config_changes.len(), 1);
+
+        let (tick, changed) = &loaded.config_changes[0];
+        assert_eq!(*tick, 12);
+        assert!(changed.insta_das);
+        assert_eq!(changed.repeat_delay, Duration::from_millis(80));
+        assert_eq!(changed.seed, 99);
+    }
+
+    #[test]
+    fn broken_replays_are_rejected() {
+        let valid = "\
+rustris-replay 1
+mode zen
+seed 1
+fixed_seed false
+insta_das true
+insta_softdrop true
+repeat_delay 100
+repeat_interval 20
+soft_drop_interval 20
+fall_interval 20
+init_level 1
+end 50
+";
+        let cases = [
+            ("valid", format!("{}10 press hard_drop\n", valid), true),
+            (
+                "newer version",
+                valid.replace("replay 1", &format!("replay {}", Replay::VERSION + 1)),
+                false,
+            ),
+            (
+                "other file",
+                valid.replace("rustris-replay 1", "rustris-puzzle 1"),
+                false,
+            ),
+            (
+                "unknown mode",
+                valid.replace("mode zen", "mode tetris"),
+                false,
+            ),
+            ("missing seed", valid.replace("seed 1\n", ""), false),
+            ("bad number", valid.replace("end 50", "end soon"), false),
+            ("bad action", format!("{}10 press jump\n", valid), false),
+            ("bad input", format!("{}10 hold hard_drop\n", valid), false),
+            ("short line", format!("{}10 press\n", valid), false),
+        ];
+
+        for (name, content, loads) in cases {
+            let file = temp_file(&format!("broken_{}.txt", name.replace(' ', "_")));
+            fs::write(&file, content).unwrap();
+            let result = Replay::load(&file);
+            let _ = fs::remove_file(&file);
+
+            assert_eq!(result.is_ok(), loads, "{}", name);
+        }
+    }
+
+    #[test]
+    fn replays_play_back_the_same_game() {
+        let mut engine = Engine::new(config(), Gamemode::Zen);
+        let mut replay = Replay::new(Gamemode::Zen, config());
+
+        play(&mut engine, &mut replay);
+
+        assert!(engine.state.pieces_placed > 10);
+
+        let file = temp_file("playback.txt");
+        replay.save(&file).unwrap();
+        let mut loaded = Replay::load(&file).unwrap();
+        let _ = fs::remove_file(&file);
+
+        let mut playback = Engine::new(loaded.config.clone(), loaded.game_mode);
+
+        while playback.ticks() < loaded.end_tick {
+            loaded.step(&mut playback);
+        }
+
+        assert_eq!(playback.now(), loaded.end_tick as u32 * TICK);
+        assert_eq!(board(&playback), board(&engine));
+        assert_eq!(playback.state.score, engine.state.score);
+        assert_eq!(playback.state.pieces_placed, engine.state.pieces_placed);
+        assert_eq!(playback.state.lines_cleared, engine.state.lines_cleared);
+        assert!(playback.config.insta_das);
+    }
+
+    #[test]
+    fn playback_starts_at_the_last_reset() {
+        let mut replay = Replay::new(Gamemode::Classic, config());
+
+        assert_eq!(replay.last_game_start(), 0);
+
+        replay.record(10, &[Input::Press(Action::QuickReset)]);
+        replay.record(20, &[Input::Press(Action::HardDrop)]);
+        replay.record(35, &[Input::Press(Action::QuickReset)]);
+        replay.record(40, &[Input::Press(Action::Hold)]);
+
+        assert_eq!(replay.last_game_start(), 35);
+    }
+
+    #[test]
+    fn saving_to_an_unwritable_path_is_reported() {
+        let replay = Replay::new(Gamemode::Zen, config());
+
+        // the replay directory can't be made where a file is in the way
+
+        let blocker = temp_file("blocker");
+        fs::write(&blocker, "").unwrap();
+
+        let result = replay.save(&format!("{}/replay/zen.txt", blocker));
+        let _ = fs::remove_file(&blocker);
+
+        assert!(result.is_err());
+
+        // and the replay itself can't be written over a directory
+
+        let directory = temp_file("directory");
+        fs::create_dir_all(&directory).unwrap();
+
+        let result = replay.save(&directory);
+        let _ = fs::remove_dir(&directory);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn replays_keep_their_puzzle_when_its_file_changes() {
+        let puzzle_file = temp_file("puzzle.txt");
+        fs::copy("puzzles/02_t_spin_double.txt", &puzzle_file).unwrap();
+        let puzzle = Puzzle::load(&puzzle_file).unwrap();
+
+        let replay = Replay::new(
+            Gamemode::Puzzle,
+            EngineConfig {
+                puzzle: Some(puzzle.clone()),
+                ..config()
+            },
+        );
+        let file = temp_file("puzzle_replay.txt");
+        replay.save(&file).unwrap();
+
+        // the editor saves every board over the same file
+
+        Puzzle::empty(&puzzle_file).save(&puzzle_file).unwrap();
+
+        let loaded = Replay::load(&file);
+        let _ = fs::remove_file(&file);
+        let _ = fs::remove_file(&puzzle_file);
+
+        let loaded = loaded.unwrap().config.puzzle.unwrap();
+
+        assert_eq!(loaded.path, puzzle_file);
+        assert_eq!(loaded.name, "T-Spin Double");
+        assert_eq!(loaded.to_text(), puzzle.to_text());
+    }
+
+    #[test]
+    fn older_replays_load_their_puzzle_from_its_file() {
+        let content = "\
+rustris-replay 1
+mode puzzle
+seed 1
+fixed_seed true
+insta_das true
+insta_softdrop true
+repeat_delay 100
+repeat_interval 20
+soft_drop_interval 20
+fall_interval 20
+init_level 1
+end 50
+puzzle puzzles/01_tetris.txt
+10 press hard_drop
+";
+        let file = temp_file("version_1.txt");
+        fs::write(&file, content).unwrap();
+        let loaded = Replay::load(&file);
+        let _ = fs::remove_file(&file);
+
+        let puzzle = loaded.unwrap().config.puzzle.unwrap();
+
+        assert_eq!(puzzle.name, "Tetris");
+        assert_eq!(
+            puzzle.to_text(),
+            Puzzle::load("puzzles/01_tetris.txt").unwrap().to_text()
+        );
+    }
+}
@@ -1,19 +1,23 @@
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{self, BufRead},
     path::Path,
     usize,
 };
 
-use sdl2::{event::Event, keyboard::Scancode};
+use sdl2::event::Event;
 
-use crate::utilities::{render_bg, render_text, Theme};
+use crate::paths;
+use crate::utilities::{render_bg, render_text, Gamemode, KeyBindings, MenuInput, Theme};
 
 pub struct ScoreBoard<'a> {
+    gamemodes: Vec<Gamemode>,
     font: sdl2::ttf::Font<'a, 'static>,
+    small_font: sdl2::ttf::Font<'a, 'static>,
     canvas: &'a mut sdl2::render::Canvas<sdl2::video::Window>,
     event_pump: &'a mut sdl2::EventPump,
     theme: &'a Theme,
+    key_bindings: &'a KeyBindings,
 }
 
 impl<'a> ScoreBoard<'a> {
@@ -26,29 +30,145 @@ impl<'a> ScoreBoard<'a> {
         canvas: &'a mut sdl2::render::Canvas<sdl2::video::Window>,
         event_pump: &'a mut sdl2::EventPump,
         theme: &'a Theme,
+        key_bindings: &'a KeyBindings,
     ) -> Result<Self, String> {
-        let font_path = Path::new(&"assets/FreeMono.ttf");
-        let font = ttf_context.load_font(font_path, 22)?;
+        let font_path = paths::font();
+        let font = ttf_context.load_font(&font_path, 22)?;
+        let small_font = ttf_context.load_font(&font_path, 16)?;
 
         Ok(Self {
+            gamemodes: Self::gamemodes(),
             font,
+            small_font,
             canvas,
             event_pump,
             theme,
+            key_bindings,
         })
     }
 
-    // function for loading ten scors at a time in chronological order
+    // best value saved in a score file, used to tell if a finished game is a personal best
+
+    pub fn best_score(file_path: &str, lower_is_better: bool) -> Option<f64> {
+        let content = std::fs::read_to_string(file_path).ok()?;
+
+        let scores = content
+            .lines()
+            .filter_map(|line| line.split(',').nth(2))
+            .filter_map(|score| score.parse::<f64>().ok());
+
+        if lower_is_better {
+            scores.reduce(f64::min)
+        } else {
+            scores.reduce(f64::max)
+        }
+    }
 
-    fn load_scores(part: usize, gamemode: u8, out_of_range: bool) -> (Vec<String>, bool) {
-        let path = match gamemode {
-            0 => Path::new("score/classic.csv"),
+    // scores of 40 line games used to be saved as lines40.csv, move them to where sprints with
+    // a 40 line goal are saved now
 
-            1 => Path::new("score/lines40.csv"),
+    pub fn migrate_score_files() {
+        let old_path = paths::data_file("score/lines40.csv");
+        let new_path = Gamemode::Sprint(40).score_file();
 
-            _ => return (vec![], false),
+        if Path::new(&old_path).exists() && !Path::new(&new_path).exists() {
+            let _ = fs::rename(old_path, new_path);
+        }
+    }
+
+    // the ten best games of every leaderboard, or of a single one, printed to the terminal
+
+    pub fn print_leaderboards(gamemode: Option<Gamemode>) {
+        let gamemodes = match gamemode {
+            Some(gamemode) => vec![gamemode],
+            None => Self::gamemodes(),
         };
 
+        for (index, gamemode) in gamemodes.into_iter().enumerate() {
+            if index > 0 {
+                println!();
+            }
+
+            println!("{}", gamemode.name());
+
+            let content = fs::read_to_string(gamemode.score_file()).unwrap_or_default();
+
+            let mut scores: Vec<(f64, Vec<&str>)> = content
+                .lines()
+                .map(|line| line.trim().split(',').collect::<Vec<&str>>())
+                .filter_map(|parts| Some((parts.get(2)?.parse::<f64>().ok()?, parts)))
+                .collect();
+
+            if gamemode.lower_is_better() {
+                scores.sort_by(|a, b| a.0.total_cmp(&b.0));
+            } else {
+                scores.sort_by(|a, b| b.0.total_cmp(&a.0));
+            }
+
+            if scores.is_empty() {
+                println!("  no scores yet");
+            }
+
+            for (rank, (score, parts)) in scores.iter().take(10).enumerate() {
+                let details: Vec<String> = ["seed", "combo", "b2b"]
+                    .iter()
+                    .zip(parts.iter().skip(3))
+                    .map(|(name, value)| format!("{} {}", name, value))
+                    .collect();
+
+                println!(
+                    "  {:>2}. {:>10}  {}  {}",
+                    rank + 1,
+                    format_score(*score),
+                    parts[0].get(..16).unwrap_or(parts[0]),
+                    details.join("  ")
+                );
+            }
+        }
+    }
+
+    // every game mode that has a leaderboard, sprints with a custom goal only show up once a
+    // score has been saved for them
+
+    fn gamemodes() -> Vec<Gamemode> {
+        let mut gamemodes = vec![Gamemode::Classic];
+        gamemodes.extend(
+            Gamemode::SPRINT_GOALS
+                .iter()
+                .map(|goal| Gamemode::Sprint(*goal)),
+        );
+
+        let mut custom_goals: Vec<u32> = fs::read_dir(paths::data_file("score"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let file_name = entry.file_name().into_string().ok()?;
+                match Gamemode::from_id(file_name.strip_suffix(".csv")?)? {
+                    Gamemode::Sprint(goal) if !Gamemode::SPRINT_GOALS.contains(&goal) => Some(goal),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        custom_goals.sort();
+        gamemodes.extend(custom_goals.into_iter().map(Gamemode::Sprint));
+        gamemodes.extend(
+            Gamemode::ULTRA_MINUTES
+                .iter()
+                .map(|minutes| Gamemode::Ultra(*minutes)),
+        );
+        gamemodes.extend(Gamemode::DIG_GOALS.iter().map(|goal| Gamemode::Dig(*goal)));
+        gamemodes.push(Gamemode::Master);
+
+        gamemodes
+    }
+
+    // function for loading ten scors at a time in chronological order
+
+    fn load_scores(part: usize, gamemode: Gamemode, out_of_range: bool) -> (Vec<String>, bool) {
+        let path = gamemode.score_file();
+
         let mut scores = Vec::new();
 
         let file = match File::open(&path) {
@@ -90,7 +210,12 @@ impl<'a> ScoreBoard<'a> {
         (scores, out_of_range)
     }
 
-    fn render_scoreboard(&mut self, part: usize, gamemode: u8) -> bool {
+    fn render_scoreboard(&mut self, part: usize, gamemode_index: usize) -> bool {
+        let gamemode = match self.gamemodes.get(gamemode_index) {
+            Some(gamemode) => *gamemode,
+            None => return false,
+        };
+
         render_bg(
             self.canvas,
             self.theme.bg_color_1,
@@ -101,11 +226,7 @@ impl<'a> ScoreBoard<'a> {
         );
 
         // render header
-        let header_string = match gamemode {
-            0 => "Classic".to_string(),
-            1 => "40 Lines".to_string(),
-            _ => return false,
-        };
+        let header_string = gamemode.name();
 
         let _ = render_text(
             self.canvas,
@@ -127,11 +248,7 @@ impl<'a> ScoreBoard<'a> {
             let print_string = format!(
                 "{}: {}",
                 &parts[0][..16],
-                if parts[2].parse::<f64>().unwrap().fract() == 0.0 {
-                    format!("{}", parts[2].parse::<f64>().unwrap().trunc())
-                } else {
-                    format!("{:.2}", parts[2].parse::<f64>().unwrap())
-                }
+                format_score(parts[2].parse::<f64>().unwrap())
             );
 
             let _ = render_text(
@@ -143,12 +260,33 @@ impl<'a> ScoreBoard<'a> {
                 render_y,
             );
 
+            // older scores were saved without the seed, max combo and back to back chain so the
+            // details line only shows what is there
+
+            let details: Vec<String> = ["seed", "combo", "b2b"]
+                .iter()
+                .zip(parts.iter().skip(3))
+                .map(|(name, value)| format!("{} {}", name, value))
+                .collect();
+
+            if !details.is_empty() {
+                let _ = render_text(
+                    self.canvas,
+                    &self.small_font,
+                    self.theme.text_color,
+                    &details.join("  "),
+                    render_x + 20,
+                    render_y + 24,
+                );
+            }
+
             render_y += 50;
         }
         scores.1
     }
 
     pub fn run(&mut self) {
+        let last_scoreboard = self.gamemodes.len() - 1;
         let mut current_scoreboard = 0;
         let mut current_part: usize = 1;
         self.render_scoreboard(current_part, current_scoreboard);
@@ -157,40 +295,28 @@ impl<'a> ScoreBoard<'a> {
             let events: Vec<Event> = self.event_pump.poll_iter().collect();
 
             for event in events {
-                match event {
-                    Event::Quit { .. }
-                    | Event::KeyDown {
-                        scancode: Some(Scancode::Escape),
-                        ..
-                    } => break 'running,
-                    Event::KeyDown {
-                        scancode: Some(Scancode::Down),
-                        ..
-                    } => {
-                        current_scoreboard = (current_scoreboard + 1).clamp(0, 1);
+                if let Event::Quit { .. } = event {
+                    break 'running;
+                }
+
+                match self.key_bindings.menu_input(&event) {
+                    Some(MenuInput::Back) => break 'running,
+                    Some(MenuInput::Down) => {
+                        current_scoreboard = (current_scoreboard + 1).clamp(0, last_scoreboard);
                         self.render_scoreboard(current_part, current_scoreboard);
                     }
-                    Event::KeyDown {
-                        scancode: Some(Scancode::Up),
-                        ..
-                    } => {
-                        current_scoreboard = current_scoreboard.saturating_sub(1).clamp(0, 1);
+                    Some(MenuInput::Up) => {
+                        current_scoreboard = current_scoreboard.saturating_sub(1);
                         self.render_scoreboard(current_part, current_scoreboard);
                     }
-                    Event::KeyDown {
-                        scancode: Some(Scancode::Right),
-                        ..
-                    } => {
+                    Some(MenuInput::Right) => {
                         current_part += 1;
                         let out_of_range = self.render_scoreboard(current_part, current_scoreboard);
                         if out_of_range {
                             current_part -= 1;
                         }
                     }
-                    Event::KeyDown {
-                        scancode: Some(Scancode::Left),
-                        ..
-                    } => {
+                    Some(MenuInput::Left) => {
                         current_part = current_part.saturating_sub(1).clamp(1, usize::MAX);
                         self.render_scoreboard(current_part, current_scoreboard);
                     }
@@ -200,3 +326,13 @@ impl<'a> ScoreBoard<'a> {
         }
     }
 }
+
+// whole scores without a fraction, times with two decimals
+
+fn format_score(score: f64) -> String {
+    if score.fract() == 0.0 {
+        format!("{}", score.trunc())
+    } else {
+        format!("{:.2}", score)
+    }
+}
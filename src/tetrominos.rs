@@ -1,10 +1,12 @@
 use crate::utilities::{has_colided, Cell};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 use sdl2::pixels::Color;
 use std::collections::HashMap;
 use std::collections::VecDeque;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub enum Shape {
     I,
     O,
@@ -22,6 +24,14 @@ pub struct Tetromino {
     pub position: [i32; 2], // position x y in array with two slots
     pub pivot: usize,
     pub rotation: i8,
+    pub last_kick: Option<usize>, // wall kick test used by the last move, none if it wasn't a rotation
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum TSpin {
+    None,
+    Mini,
+    Full,
 }
 
 impl Tetromino {
@@ -63,11 +73,13 @@ impl Tetromino {
             position: [0, 0],
             pivot,
             rotation: 0,
+            last_kick: None,
         }
     }
 
     pub fn fall(&mut self) {
         self.position[1] += 1;
+        self.last_kick = None;
     }
 
     pub fn left(&mut self, map: &[[Cell; 10]; 20]) {
@@ -75,6 +87,7 @@ impl Tetromino {
 
         if !has_colided(&self.grid, &(new_position[0], new_position[1]), map) {
             self.position = new_position;
+            self.last_kick = None;
         }
     }
 
@@ -83,6 +96,33 @@ impl Tetromino {
 
         if !has_colided(&self.grid, &(new_position[0], new_position[1]), map) {
             self.position = new_position;
+            self.last_kick = None;
+        }
+    }
+
+    // where the tetromino enters the map
+
+    pub fn spawn_position(&self) -> [i32; 2] {
+        let position_x = match self.shape {
+            Shape::O => 4,
+            _ => 3,
+        };
+
+        let position_y = match self.shape {
+            Shape::I => 0,
+            _ => -1,
+        };
+
+        [position_x, position_y]
+    }
+
+    // move the tetromino to a new position, a move that changes the position is no longer a
+    // rotation for t-spin detection
+
+    pub fn move_to(&mut self, position: [i32; 2]) {
+        if self.position != position {
+            self.position = position;
+            self.last_kick = None;
         }
     }
 
@@ -181,9 +221,9 @@ impl Tetromino {
             _ => wall_kicks.get(&rotation_state).unwrap(),
         };
 
-        let mut test_success: bool = false;
+        let mut test_success: Option<usize> = None;
 
-        for test in tests.iter() {
+        for (index, test) in tests.iter().enumerate() {
             let pos_x = rotated.position[0] + test.0;
             let pos_y = rotated.position[1] + test.1;
 
@@ -192,15 +232,16 @@ impl Tetromino {
             }
 
             rotated.position = [pos_x, pos_y];
-            test_success = true;
+            test_success = Some(index);
             break;
         }
 
-        if test_success {
+        if test_success.is_some() {
             self.grid = rotated.grid;
             self.position = rotated.position;
             self.pivot = rotated.pivot;
             self.rotation = rotated.rotation;
+            self.last_kick = test_success;
             success = true;
         }
 
@@ -222,23 +263,139 @@ impl Tetromino {
         let success = self.srs_rotate(true, map);
         success
     }
+
+    // three corner rule, a t that was rotated into place with three of the four corners around
+    // its center blocked is a t-spin. it's a mini unless both corners next to the point of the t
+    // are blocked or the last kick test was used.
+
+    pub fn t_spin(&self, map: &[[Cell; 10]; 20]) -> TSpin {
+        if self.shape != Shape::T || self.last_kick.is_none() {
+            return TSpin::None;
+        }
+
+        let center = self.grid[self.pivot];
+
+        // the walls and the floor count as blocked, the space above the map does not
+
+        let is_blocked = |x: i32, y: i32| {
+            if !(0..10).contains(&x) || y >= 20 {
+                return true;
+            }
+            y >= 0 && map[y as usize][x as usize].occupied
+        };
+
+        let corners = [[-1, -1], [1, -1], [-1, 1], [1, 1]];
+        let blocked_corners = corners
+            .iter()
+            .filter(|corner| {
+                is_blocked(
+                    self.position[0] + center[0] + corner[0],
+                    self.position[1] + center[1] + corner[1],
+                )
+            })
+            .count();
+
+        if blocked_corners < 3 {
+            return TSpin::None;
+        }
+
+        // the point of the t is the only cell without a cell on the opposite side of the center
+
+        let point = self
+            .grid
+            .iter()
+            .map(|cell| [cell[0] - center[0], cell[1] - center[1]])
+            .find(|offset| {
+                *offset != [0, 0]
+                    && !self.grid.iter().any(|cell| {
+                        [cell[0] - center[0], cell[1] - center[1]] == [-offset[0], -offset[1]]
+                    })
+            })
+            .unwrap();
+
+        let front_corners = [
+            [point[0] + point[1], point[1] + point[0]],
+            [point[0] - point[1], point[1] - point[0]],
+        ];
+
+        let front_blocked = front_corners.iter().all(|corner| {
+            is_blocked(
+                self.position[0] + center[0] + corner[0],
+                self.position[1] + center[1] + corner[1],
+            )
+        });
+
+        if front_blocked || self.last_kick == Some(4) {
+            TSpin::Full
+        } else {
+            TSpin::Mini
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct Bag {
     pub queue: VecDeque<Tetromino>,
+    pub seed: u64,
+    rng: StdRng,
+    fixed: bool, // a fixed bag only hands out the pieces it was made with
 }
 
 impl Bag {
-    pub fn new() -> Self {
+    // bags built from the same seed always hand out the same sequence of tetrominos
+
+    pub fn new(seed: u64) -> Self {
         let mut bag = Self {
             queue: VecDeque::new(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            fixed: false,
         };
 
         bag.refill();
         bag
     }
 
+    pub fn fixed(shapes: &[Shape]) -> Self {
+        Self {
+            queue: shapes.iter().cloned().map(Tetromino::new).collect(),
+            seed: 0,
+            rng: StdRng::seed_from_u64(0),
+            fixed: true,
+        }
+    }
+
+    // a bag that hands out the given pieces first and random ones after
+
+    pub fn starting_with(shapes: &[Shape], seed: u64) -> Self {
+        let mut bag = Self::new(seed);
+
+        for shape in shapes.iter().rev() {
+            bag.queue.push_front(Tetromino::new(shape.clone()));
+        }
+
+        bag
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn random_seed() -> u64 {
+        rand::thread_rng().gen_range(0..u32::MAX as u64)
+    }
+
+    // draw a seed for the bag that follows this one
+
+    pub fn next_seed(&mut self) -> u64 {
+        self.rng.gen_range(0..u32::MAX as u64)
+    }
+
     fn refill(&mut self) {
+        if self.fixed {
+            return;
+        }
+
         let mut shapes = vec![
             Shape::I,
             Shape::O,
@@ -248,7 +405,7 @@ impl Bag {
             Shape::J,
             Shape::L,
         ];
-        shapes.shuffle(&mut rand::thread_rng());
+        shapes.shuffle(&mut self.rng);
 
         self.queue.extend(shapes.into_iter().map(Tetromino::new));
     }
@@ -259,16 +416,7 @@ impl Bag {
             self.refill();
         }
 
-        let position_x = match self.queue[0].shape {
-            Shape::O => 4,
-            _ => 3,
-        };
-
-        self.queue[0].position[0] = position_x;
-        self.queue[0].position[1] = match self.queue[0].shape {
-            Shape::I => 0,
-            _ => -1,
-        };
+        self.queue[0].position = self.queue[0].spawn_position();
         self.queue.pop_front().unwrap()
     }
 
@@ -1,11 +1,12 @@
-use std::{
-    fs,
-    time::{Duration, Instant},
-    usize,
-};
+use std::{collections::HashMap, fs, path::Path, time::Duration, usize};
 
+use crate::engine::{Action, Input};
+use crate::network::Connection;
+use crate::paths;
 use crate::tetrominos::Tetromino;
-use sdl2::{keyboard::Scancode, pixels::Color, rect::Rect};
+use sdl2::{controller::Button, event::Event, keyboard::Scancode, pixels::Color, rect::Rect};
+
+pub const GARBAGE_COLOR: Color = Color::RGB(128, 128, 128);
 
 #[derive(Clone, Copy)]
 pub struct Cell {
@@ -19,10 +20,11 @@ pub struct Theme {
     pub text_color: Color,
 }
 
+#[derive(Default)]
 pub struct Keystate {
     pub is_pressed: bool,
-    pub first_press_time: Instant,
-    pub last_repeat_time: Instant,
+    pub first_press_time: Duration,
+    pub last_repeat_time: Duration,
 }
 
 pub struct Settings {
@@ -33,196 +35,838 @@ pub struct Settings {
     pub repeat_interval: Duration,
     pub fall_interval: Duration,
     pub init_level: u32,
+    pub seed: Option<u64>,       // chosen per session in the menu, never saved
+    pub puzzle: Option<String>,  // path of the puzzle picked in the menu, never saved
+    pub replay: Option<String>,  // path of the replay picked in the menu, never saved
+    pub network_address: String, // where to join a network match
+    pub network_port: u16,
+    pub dig_messiness: u32,
+    pub dig_rise_pieces: u32,
+    pub dig_rise_interval: Duration,
+    pub cpu_speed: u32, // pieces per second of the cpu player in tenths
+    pub placement_hint: bool,
     pub key_bindings: KeyBindings,
+    file: String, // the settings file of the profile in use, relative to the config directory
+    session_values: HashMap<String, (String, String)>, // saved and session value of each key
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            bright_mode: false,
+            insta_das: true,
+            insta_softdrop: true,
+            repeat_delay: Duration::from_millis(100),
+            repeat_interval: Duration::from_millis(20),
+            fall_interval: Duration::from_millis(20),
+            init_level: 1,
+            seed: None,
+            puzzle: None,
+            replay: None,
+            network_address: "127.0.0.1".to_string(),
+            network_port: Connection::DEFAULT_PORT,
+            dig_messiness: 30,
+            dig_rise_pieces: 0,
+            dig_rise_interval: Duration::ZERO,
+            cpu_speed: 15,
+            placement_hint: false,
+            key_bindings: KeyBindings::default(),
+            file: Self::FILE.to_string(),
+            session_values: HashMap::new(),
+        }
+    }
 }
 
 impl Settings {
-    pub fn new() -> Result<Self, String> {
-        if std::path::Path::new("settings/keybinds.txt").exists()
-            && std::path::Path::new("settings/options.txt").exists()
+    const FILE: &'static str = "settings.txt";
+    const VERSION: u32 = 1;
+
+    // the problems found the last time the settings were read, in the data directory since the
+    // game has no console to print them to on windows
+
+    const LOG_FILE: &'static str = "settings.log";
+
+    // settings used to be kept in two files with a value per line in a fixed order, they are
+    // read once to move them over to the new file
+
+    const LEGACY_OPTIONS_FILE: &'static str = "options.txt";
+    const LEGACY_KEYBINDS_FILE: &'static str = "keybinds.txt";
+
+    const LEGACY_OPTIONS: [&'static str; 12] = [
+        "bright_mode",
+        "insta_das",
+        "insta_softdrop",
+        "repeat_delay",
+        "repeat_interval",
+        "soft_drop_interval",
+        "init_level",
+        "dig_messiness",
+        "dig_rise_pieces",
+        "dig_rise_interval",
+        "cpu_speed",
+        "placement_hint",
+    ];
+
+    const LEGACY_KEYBINDS: [Action; 10] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::RotateClockwise,
+        Action::RotateCounterClockwise,
+        Action::Rotate180,
+        Action::HardDrop,
+        Action::SoftDrop,
+        Action::Hold,
+        Action::QuickReset,
+        Action::Undo,
+    ];
+
+    // anything missing from the file or that can't be read keeps its default, the problems are
+    // reported instead of stopping the game. a profile keeps its settings in a file of its own
+    // and starts from the defaults, the old files only ever belonged to the default settings
+
+    pub fn new(profile: Option<&str>) -> Self {
+        let file = match profile {
+            Some(profile) => format!("profiles/{}.txt", profile),
+            None => Self::FILE.to_string(),
+        };
+
+        let (mut settings, problems) = if Path::new(&paths::config_file(&file)).exists() {
+            Self::load(&file)
+        } else if profile.is_none()
+            && Path::new(&paths::config_file(Self::LEGACY_OPTIONS_FILE)).exists()
         {
-            Ok(Self::load()?)
+            Self::migrate()
         } else {
-            let bright_mode = false;
-            let insta_das = true;
-            let insta_softdrop = true;
-            let repeat_delay = Duration::from_millis(100);
-            let repeat_interval = Duration::from_millis(20);
-            let fall_interval = Duration::from_millis(20);
-
-            let move_left = Scancode::Left;
-            let move_right = Scancode::Right;
-            let rotate_clockwise = Scancode::Z;
-            let rotate_counter_clockwise = Scancode::X;
-            let rotate_180 = Scancode::V;
-            let hard_drop = Scancode::Space;
-            let soft_drop = Scancode::Down;
-            let hold = Scancode::C;
-            let quick_reset = Scancode::R;
-
-            let key_bindings = KeyBindings {
-                move_left,
-                move_right,
-                rotate_clockwise,
-                rotate_counter_clockwise,
-                rotate_180,
-                hard_drop,
-                soft_drop,
-                hold,
-                quick_reset,
+            (Self::default(), Vec::new())
+        };
+
+        settings.file = file;
+        Self::log_problems(&problems);
+
+        settings
+    }
+
+    // a log left over from an earlier start is removed once the settings read without problems
+
+    fn log_problems(problems: &[String]) {
+        let log_file = paths::data_file(Self::LOG_FILE);
+
+        if problems.is_empty() {
+            let _ = fs::remove_file(log_file);
+            return;
+        }
+
+        if let Some(parent) = Path::new(&log_file).parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+
+        if fs::write(&log_file, problems.join("\n") + "\n").is_ok() {
+            eprintln!("Problems with the settings were written to {}", log_file);
+        }
+    }
+
+    pub fn save(&self) {
+        let path = paths::config_file(&self.file);
+
+        if let Some(parent) = Path::new(&path).parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+
+        let _ = fs::write(path, self.to_text());
+    }
+
+    // change a setting until the game is closed, the file keeps the saved value unless the
+    // setting is changed again in the menu
+
+    pub fn set_for_session(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let saved = self.value(key);
+        self.set(key, value)?;
+
+        if let (Some(saved), Some(session)) = (saved, self.value(key)) {
+            let saved = match self.session_values.remove(key) {
+                Some((saved, _)) => saved,
+                None => saved,
             };
 
-            Ok(Self {
-                bright_mode,
-                insta_das,
-                insta_softdrop,
-                repeat_delay,
-                repeat_interval,
-                fall_interval,
-                init_level: 1,
-                key_bindings,
+            self.session_values
+                .insert(key.to_string(), (saved, session));
+        }
+
+        Ok(())
+    }
+
+    fn value(&self, key: &str) -> Option<String> {
+        self.values()
+            .into_iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    // one "key = value" line per setting, the seed and puzzle are picked per session and left out
+
+    fn to_text(&self) -> String {
+        let lines: Vec<String> = self
+            .values()
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match self.session_values.get(&key) {
+                    Some((saved, session)) if *session == value => saved.clone(),
+                    _ => value,
+                };
+
+                format!("{} = {}", key, value).trim_end().to_string()
             })
+            .collect();
+
+        lines.join("\n") + "\n"
+    }
+
+    // every saved setting as its key and the text written after it
+
+    fn values(&self) -> Vec<(String, String)> {
+        let mut values = vec![
+            ("version", Self::VERSION.to_string()),
+            ("bright_mode", self.bright_mode.to_string()),
+            ("insta_das", self.insta_das.to_string()),
+            ("insta_softdrop", self.insta_softdrop.to_string()),
+            ("repeat_delay", self.repeat_delay.as_millis().to_string()),
+            (
+                "repeat_interval",
+                self.repeat_interval.as_millis().to_string(),
+            ),
+            (
+                "soft_drop_interval",
+                self.fall_interval.as_millis().to_string(),
+            ),
+            ("init_level", self.init_level.to_string()),
+            ("dig_messiness", self.dig_messiness.to_string()),
+            ("dig_rise_pieces", self.dig_rise_pieces.to_string()),
+            (
+                "dig_rise_interval",
+                self.dig_rise_interval.as_secs().to_string(),
+            ),
+            ("cpu_speed", self.cpu_speed.to_string()),
+            ("placement_hint", self.placement_hint.to_string()),
+            ("network_address", self.network_address.clone()),
+            ("network_port", self.network_port.to_string()),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect::<Vec<_>>();
+
+        for binding in Binding::ALL {
+            let keys = self.key_bindings.keys(binding);
+
+            for (prefix, key) in ["key", "alt_key"].into_iter().zip(keys) {
+                values.push((
+                    format!("{}.{}", prefix, binding.name()),
+                    key.map_or(String::new(), |key| key.name().to_string()),
+                ));
+            }
+
+            values.push((
+                format!("button.{}", binding.name()),
+                self.key_bindings
+                    .button(binding)
+                    .map_or(String::new(), |button| button.string()),
+            ));
         }
+
+        values
     }
 
-    pub fn save(&self) {
-        let _ = fs::create_dir_all("settings");
-
-        // save settings to txt
-        let mut file_path = "settings/options.txt";
-        let mut content = format!(
-            "{}\n{}\n{}\n{}\n{}\n{}\n{}",
-            self.bright_mode,
-            self.insta_das,
-            self.insta_softdrop,
-            self.repeat_delay.as_millis(),
-            self.repeat_interval.as_millis(),
-            self.fall_interval.as_millis(),
-            self.init_level.to_string(),
+    fn load(file: &str) -> (Self, Vec<String>) {
+        let file = paths::config_file(file);
+
+        match fs::read_to_string(&file) {
+            Ok(text) => Self::from_text(&text, &file),
+            Err(e) => (
+                Self::default(),
+                vec![format!("could not read {}: {}", file, e)],
+            ),
+        }
+    }
+
+    // the settings written in a file, the problems name the file they were found in
+
+    fn from_text(text: &str, file: &str) -> (Self, Vec<String>) {
+        let mut settings = Self::default();
+        let mut problems = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let result = match line.split_once('=') {
+                Some((key, value)) => settings.set(key.trim(), value.trim()),
+                None => Err(format!("expected \"key = value\" but found \"{}\"", line)),
+            };
+
+            if let Err(e) = result {
+                problems.push(format!("{} line {}: {}", file, index + 1, e));
+            }
+        }
+
+        (settings, problems)
+    }
+
+    // the old files are read value by value so a broken line only loses that value, they are
+    // kept with an .old extension once the new file is written
+
+    fn migrate() -> (Self, Vec<String>) {
+        let options_file = paths::config_file(Self::LEGACY_OPTIONS_FILE);
+        let keybinds_file = paths::config_file(Self::LEGACY_KEYBINDS_FILE);
+
+        let (settings, problems) = Self::from_legacy(
+            &fs::read_to_string(&options_file).unwrap_or_default(),
+            &fs::read_to_string(&keybinds_file).unwrap_or_default(),
         );
 
-        let _ = fs::write(file_path, content);
+        settings.save();
 
-        // save keybinds to txt
-        file_path = "settings/keybinds.txt";
-        let scancode_names: [String; 9] = self
-            .key_bindings
-            .all_scancodes()
-            .map(|s| s.name().to_string());
-
-        content = scancode_names.join("\n");
-        let _ = fs::write(file_path, content);
-    }
-
-    fn load() -> Result<Self, String> {
-        // load options
-        let options_text_file = fs::read_to_string("settings/options.txt").unwrap();
-        let mut lines = options_text_file.lines();
-
-        let bright_mode: bool = lines.next().unwrap().parse().unwrap();
-        let insta_das: bool = lines.next().unwrap().parse().unwrap();
-        let insta_softdrop: bool = lines.next().unwrap().parse().unwrap();
-        let repeat_delay: Duration = Duration::from_millis(lines.next().unwrap().parse().unwrap());
-        let repeat_interval: Duration =
-            Duration::from_millis(lines.next().unwrap().parse().unwrap());
-        let fall_interval: Duration = Duration::from_millis(lines.next().unwrap().parse().unwrap());
-        let init_level: u32 = lines.next().unwrap().parse().unwrap();
-
-        // load keybindings
-        let scancodes_text_file = fs::read_to_string("settings/keybinds.txt").unwrap();
-        let scancodes: Vec<Scancode> = scancodes_text_file
-            .lines()
-            .filter_map(|line| Scancode::from_name(line))
-            .collect();
+        for file in [options_file, keybinds_file] {
+            let _ = fs::rename(&file, format!("{}.old", file));
+        }
 
-        let key_bindings = KeyBindings {
-            move_left: scancodes[0],
-            move_right: scancodes[1],
-            rotate_clockwise: scancodes[2],
-            rotate_counter_clockwise: scancodes[3],
-            rotate_180: scancodes[4],
-            hard_drop: scancodes[5],
-            soft_drop: scancodes[6],
-            hold: scancodes[7],
-            quick_reset: scancodes[8],
-        };
+        (settings, problems)
+    }
+
+    // the settings in the text of the old options and keybinds files
 
-        Ok(Settings {
-            bright_mode,
-            insta_das,
-            insta_softdrop,
-            repeat_delay,
-            repeat_interval,
-            fall_interval,
-            init_level,
-            key_bindings,
-        })
+    fn from_legacy(options: &str, keybinds: &str) -> (Self, Vec<String>) {
+        let mut settings = Self::default();
+        let mut problems = Vec::new();
+
+        for (key, value) in Self::LEGACY_OPTIONS.iter().zip(options.lines()) {
+            if let Err(e) = settings.set(key, value.trim()) {
+                problems.push(format!("{}: {}", Self::LEGACY_OPTIONS_FILE, e));
+            }
+        }
+
+        for (action, value) in Self::LEGACY_KEYBINDS.iter().zip(keybinds.lines()) {
+            if let Err(e) = settings.set(&format!("key.{}", action.name()), value.trim()) {
+                problems.push(format!("{}: {}", Self::LEGACY_KEYBINDS_FILE, e));
+            }
+        }
+
+        (settings, problems)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "version" => {
+                let version: u32 = parse_setting(key, value)?;
+
+                if version > Self::VERSION {
+                    return Err(format!(
+                        "the file is from a newer version ({}), settings this version doesn't know \
+                         are ignored",
+                        version
+                    ));
+                }
+            }
+            "bright_mode" => self.bright_mode = parse_setting(key, value)?,
+            "insta_das" => self.insta_das = parse_setting(key, value)?,
+            "insta_softdrop" => self.insta_softdrop = parse_setting(key, value)?,
+            "repeat_delay" => self.repeat_delay = Duration::from_millis(parse_setting(key, value)?),
+            "repeat_interval" => {
+                self.repeat_interval = Duration::from_millis(parse_setting(key, value)?)
+            }
+            "soft_drop_interval" => {
+                self.fall_interval = Duration::from_millis(parse_setting(key, value)?)
+            }
+            "init_level" => self.init_level = parse_setting(key, value)?,
+            "dig_messiness" => self.dig_messiness = parse_setting(key, value)?,
+            "dig_rise_pieces" => self.dig_rise_pieces = parse_setting(key, value)?,
+            "dig_rise_interval" => {
+                self.dig_rise_interval = Duration::from_secs(parse_setting(key, value)?)
+            }
+            "cpu_speed" => self.cpu_speed = parse_setting(key, value)?,
+            "placement_hint" => self.placement_hint = parse_setting(key, value)?,
+            "network_address" => self.network_address = value.to_string(),
+            "network_port" => self.network_port = parse_setting(key, value)?,
+            _ => {
+                let (slot, name) = match key.split_once('.') {
+                    Some(("key", name)) => (Some(0), name),
+                    Some(("alt_key", name)) => (Some(1), name),
+                    Some(("button", name)) => (None, name),
+                    _ => return Err(format!("unknown setting \"{}\"", key)),
+                };
+
+                let binding =
+                    Binding::from_name(name).ok_or(format!("unknown setting \"{}\"", key))?;
+
+                // an empty value leaves the binding without that key or button
+
+                let Some(slot) = slot else {
+                    let button = match value {
+                        "" => None,
+                        _ => Some(
+                            Button::from_string(value)
+                                .ok_or(format!("unknown button \"{}\" for {}", value, key))?,
+                        ),
+                    };
+
+                    self.key_bindings.set_button(binding, button);
+                    return Ok(());
+                };
+
+                let scancode = match value {
+                    "" => None,
+                    _ => Some(
+                        Scancode::from_name(value)
+                            .ok_or(format!("unknown key \"{}\" for {}", value, key))?,
+                    ),
+                };
+
+                self.key_bindings.set_key(binding, slot, scancode);
+            }
+        }
+
+        Ok(())
     }
 }
 
+fn parse_setting<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value \"{}\" for {}", value, key))
+}
+
+// everything a key can be bound to, the game's actions and the keys that pause and move through
+// menus
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Game(Action),
+    Pause,
+    MenuConfirm,
+    MenuBack,
+}
+
+impl Binding {
+    pub const ALL: [Binding; 13] = [
+        Binding::Game(Action::MoveLeft),
+        Binding::Game(Action::MoveRight),
+        Binding::Game(Action::SoftDrop),
+        Binding::Game(Action::HardDrop),
+        Binding::Game(Action::RotateClockwise),
+        Binding::Game(Action::RotateCounterClockwise),
+        Binding::Game(Action::Rotate180),
+        Binding::Game(Action::Hold),
+        Binding::Game(Action::QuickReset),
+        Binding::Game(Action::Undo),
+        Binding::Pause,
+        Binding::MenuConfirm,
+        Binding::MenuBack,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Binding::Game(action) => action.name(),
+            Binding::Pause => "pause",
+            Binding::MenuConfirm => "menu_confirm",
+            Binding::MenuBack => "menu_back",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Binding> {
+        Binding::ALL
+            .into_iter()
+            .find(|binding| binding.name() == name)
+    }
+
+    fn index(&self) -> usize {
+        Binding::ALL
+            .iter()
+            .position(|binding| binding == self)
+            .unwrap_or(0)
+    }
+
+    // the menu keys are only read in menus and the rest only while playing, so a key can be used
+    // once in each
+
+    fn is_menu(&self) -> bool {
+        matches!(self, Binding::MenuConfirm | Binding::MenuBack)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MenuInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Back,
+}
+
+// every binding has a key, an optional second one and a controller button
+
 pub struct KeyBindings {
-    pub move_left: Scancode,
-    pub move_right: Scancode,
-    pub rotate_clockwise: Scancode,
-    pub rotate_counter_clockwise: Scancode,
-    pub rotate_180: Scancode,
-    pub hard_drop: Scancode,
-    pub soft_drop: Scancode,
-    pub hold: Scancode,
-    pub quick_reset: Scancode,
+    pub move_left: [Option<Scancode>; 2],
+    pub move_right: [Option<Scancode>; 2],
+    pub rotate_clockwise: [Option<Scancode>; 2],
+    pub rotate_counter_clockwise: [Option<Scancode>; 2],
+    pub rotate_180: [Option<Scancode>; 2],
+    pub hard_drop: [Option<Scancode>; 2],
+    pub soft_drop: [Option<Scancode>; 2],
+    pub hold: [Option<Scancode>; 2],
+    pub quick_reset: [Option<Scancode>; 2],
+    pub undo: [Option<Scancode>; 2],
+    pub pause: [Option<Scancode>; 2],
+    pub menu_confirm: [Option<Scancode>; 2],
+    pub menu_back: [Option<Scancode>; 2],
+    pub buttons: [Option<Button>; 13], // in the order of Binding::ALL
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            move_left: [Some(Scancode::Left), None],
+            move_right: [Some(Scancode::Right), None],
+            rotate_clockwise: [Some(Scancode::Z), None],
+            rotate_counter_clockwise: [Some(Scancode::X), None],
+            rotate_180: [Some(Scancode::V), None],
+            hard_drop: [Some(Scancode::Space), None],
+            soft_drop: [Some(Scancode::Down), None],
+            hold: [Some(Scancode::C), None],
+            quick_reset: [Some(Scancode::R), None],
+            undo: [Some(Scancode::U), None],
+            pause: [Some(Scancode::Escape), None],
+            menu_confirm: [Some(Scancode::Space), Some(Scancode::Return)],
+            menu_back: [Some(Scancode::Escape), None],
+            buttons: [
+                Some(Button::DPadLeft),
+                Some(Button::DPadRight),
+                Some(Button::DPadDown),
+                Some(Button::DPadUp),
+                Some(Button::A),
+                Some(Button::B),
+                Some(Button::Y),
+                Some(Button::LeftShoulder),
+                Some(Button::Back),
+                Some(Button::X),
+                Some(Button::Start),
+                Some(Button::A),
+                Some(Button::B),
+            ],
+        }
+    }
 }
 
 impl KeyBindings {
-    pub fn update_binding(&mut self, key: &str, new_scancode: Scancode) {
-        match key {
-            "move_left" => self.move_left = new_scancode,
-            "move_right" => self.move_right = new_scancode,
-            "rotate_clockwise" => self.rotate_clockwise = new_scancode,
-            "rotate_counter_clockwise" => self.rotate_counter_clockwise = new_scancode,
-            "rotate_180" => self.rotate_180 = new_scancode,
-            "hard_drop" => self.hard_drop = new_scancode,
-            "soft_drop" => self.soft_drop = new_scancode,
-            "hold" => self.hold = new_scancode,
-            _ => println!("Invalid key binding name: {}", key),
-        }
-    }
-    pub fn contains_scancode(&self, scancode: Scancode) -> bool {
-        self.move_left == scancode
-            || self.move_right == scancode
-            || self.rotate_clockwise == scancode
-            || self.rotate_counter_clockwise == scancode
-            || self.rotate_180 == scancode
-            || self.hard_drop == scancode
-            || self.soft_drop == scancode
-            || self.hold == scancode
-    }
-
-    pub fn all_scancodes(&self) -> [Scancode; 9] {
-        [
-            self.move_left,
-            self.move_right,
-            self.rotate_clockwise,
-            self.rotate_counter_clockwise,
-            self.rotate_180,
-            self.hard_drop,
-            self.soft_drop,
-            self.hold,
-            self.quick_reset,
-        ]
+    // the arrows and the d-pad always move through menus and can't be given to the menu keys
+
+    const MENU_ARROWS: [Scancode; 4] = [
+        Scancode::Up,
+        Scancode::Down,
+        Scancode::Left,
+        Scancode::Right,
+    ];
+
+    const MENU_DPAD: [Button; 4] = [
+        Button::DPadUp,
+        Button::DPadDown,
+        Button::DPadLeft,
+        Button::DPadRight,
+    ];
+
+    pub fn keys(&self, binding: Binding) -> [Option<Scancode>; 2] {
+        match binding {
+            Binding::Game(Action::MoveLeft) => self.move_left,
+            Binding::Game(Action::MoveRight) => self.move_right,
+            Binding::Game(Action::SoftDrop) => self.soft_drop,
+            Binding::Game(Action::HardDrop) => self.hard_drop,
+            Binding::Game(Action::RotateClockwise) => self.rotate_clockwise,
+            Binding::Game(Action::RotateCounterClockwise) => self.rotate_counter_clockwise,
+            Binding::Game(Action::Rotate180) => self.rotate_180,
+            Binding::Game(Action::Hold) => self.hold,
+            Binding::Game(Action::QuickReset) => self.quick_reset,
+            Binding::Game(Action::Undo) => self.undo,
+            Binding::Pause => self.pause,
+            Binding::MenuConfirm => self.menu_confirm,
+            Binding::MenuBack => self.menu_back,
+        }
+    }
+
+    fn keys_mut(&mut self, binding: Binding) -> &mut [Option<Scancode>; 2] {
+        match binding {
+            Binding::Game(Action::MoveLeft) => &mut self.move_left,
+            Binding::Game(Action::MoveRight) => &mut self.move_right,
+            Binding::Game(Action::SoftDrop) => &mut self.soft_drop,
+            Binding::Game(Action::HardDrop) => &mut self.hard_drop,
+            Binding::Game(Action::RotateClockwise) => &mut self.rotate_clockwise,
+            Binding::Game(Action::RotateCounterClockwise) => &mut self.rotate_counter_clockwise,
+            Binding::Game(Action::Rotate180) => &mut self.rotate_180,
+            Binding::Game(Action::Hold) => &mut self.hold,
+            Binding::Game(Action::QuickReset) => &mut self.quick_reset,
+            Binding::Game(Action::Undo) => &mut self.undo,
+            Binding::Pause => &mut self.pause,
+            Binding::MenuConfirm => &mut self.menu_confirm,
+            Binding::MenuBack => &mut self.menu_back,
+        }
+    }
+
+    pub fn set_key(&mut self, binding: Binding, slot: usize, scancode: Option<Scancode>) {
+        self.keys_mut(binding)[slot] = scancode;
+    }
+
+    // a new key goes first and the old first key becomes the second one
+
+    pub fn bind(&mut self, binding: Binding, scancode: Scancode) {
+        let keys = self.keys_mut(binding);
+
+        if keys[0] != Some(scancode) {
+            *keys = [Some(scancode), keys[0]];
+        }
+    }
+
+    pub fn clear_second_key(&mut self, binding: Binding) {
+        self.keys_mut(binding)[1] = None;
+    }
+
+    // whether another binding that is read at the same time already uses the key
+
+    pub fn is_taken(&self, binding: Binding, scancode: Scancode) -> bool {
+        if binding.is_menu() && Self::MENU_ARROWS.contains(&scancode) {
+            return true;
+        }
+
+        Binding::ALL
+            .into_iter()
+            .filter(|other| *other != binding && other.is_menu() == binding.is_menu())
+            .any(|other| self.matches(other, scancode))
+    }
+
+    pub fn matches(&self, binding: Binding, scancode: Scancode) -> bool {
+        self.keys(binding).contains(&Some(scancode))
+    }
+
+    pub fn button(&self, binding: Binding) -> Option<Button> {
+        self.buttons[binding.index()]
+    }
+
+    pub fn set_button(&mut self, binding: Binding, button: Option<Button>) {
+        self.buttons[binding.index()] = button;
+    }
+
+    pub fn is_button_taken(&self, binding: Binding, button: Button) -> bool {
+        if binding.is_menu() && Self::MENU_DPAD.contains(&button) {
+            return true;
+        }
+
+        Binding::ALL
+            .into_iter()
+            .filter(|other| *other != binding && other.is_menu() == binding.is_menu())
+            .any(|other| self.button(other) == Some(button))
+    }
+
+    // whether the event is a key or button of the binding being pressed
+
+    pub fn is_pressed(&self, binding: Binding, event: &Event) -> bool {
+        match event {
+            Event::KeyDown {
+                scancode: Some(scancode),
+                ..
+            } => self.matches(binding, *scancode),
+            Event::ControllerButtonDown { button, .. } => self.button(binding) == Some(*button),
+            _ => false,
+        }
+    }
+
+    pub fn action(&self, scancode: Scancode) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|action| self.matches(Binding::Game(*action), scancode))
+    }
+
+    pub fn button_action(&self, button: Button) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|action| self.button(Binding::Game(*action)) == Some(button))
+    }
+
+    // the engine input of a key or button, held buttons repeat moves the same way held keys do
+    // since the engine does the repeating
+
+    pub fn input(&self, event: &Event) -> Option<Input> {
+        match event {
+            Event::KeyDown {
+                scancode: Some(scancode),
+                repeat: false,
+                ..
+            } => self.action(*scancode).map(Input::Press),
+            Event::KeyUp {
+                scancode: Some(scancode),
+                ..
+            } => self.action(*scancode).map(Input::Release),
+            Event::ControllerButtonDown { button, .. } => {
+                self.button_action(*button).map(Input::Press)
+            }
+            Event::ControllerButtonUp { button, .. } => {
+                self.button_action(*button).map(Input::Release)
+            }
+            _ => None,
+        }
+    }
+
+    // what a key or button press does in a menu
+
+    pub fn menu_input(&self, event: &Event) -> Option<MenuInput> {
+        match event {
+            Event::KeyDown {
+                scancode: Some(Scancode::Up),
+                ..
+            }
+            | Event::ControllerButtonDown {
+                button: Button::DPadUp,
+                ..
+            } => Some(MenuInput::Up),
+            Event::KeyDown {
+                scancode: Some(Scancode::Down),
+                ..
+            }
+            | Event::ControllerButtonDown {
+                button: Button::DPadDown,
+                ..
+            } => Some(MenuInput::Down),
+            Event::KeyDown {
+                scancode: Some(Scancode::Left),
+                ..
+            }
+            | Event::ControllerButtonDown {
+                button: Button::DPadLeft,
+                ..
+            } => Some(MenuInput::Left),
+            Event::KeyDown {
+                scancode: Some(Scancode::Right),
+                ..
+            }
+            | Event::ControllerButtonDown {
+                button: Button::DPadRight,
+                ..
+            } => Some(MenuInput::Right),
+            _ if self.is_pressed(Binding::MenuConfirm, event) => Some(MenuInput::Confirm),
+            _ if self.is_pressed(Binding::MenuBack, event) => Some(MenuInput::Back),
+            _ => None,
+        }
+    }
+
+    // the bound keys and button for the menu, like "Z / X (a)"
+
+    pub fn names(&self, binding: Binding) -> String {
+        let names: Vec<&str> = self
+            .keys(binding)
+            .iter()
+            .flatten()
+            .map(|scancode| scancode.name())
+            .collect();
+
+        let keys = if names.is_empty() {
+            "None".to_string()
+        } else {
+            names.join(" / ")
+        };
+
+        match self.button(binding) {
+            Some(button) => format!("{} ({})", keys, button.string()),
+            None => keys,
+        }
     }
 }
 
 pub struct Lockdelay {
-    pub lock_delay_timer: Instant,
+    pub lock_delay_timer: Duration,
     pub lock_delay_duration: Duration,
     pub is_in_delay: bool,
     pub moves_done: u8,
     pub ammount_fallen: u8,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum Gamemode {
     Classic,
-    Lines40,
+    Sprint(u32), // number of lines to clear
+    Ultra(u32),  // minutes to score as much as possible in
+    Dig(u32),    // number of garbage lines to clear
+    Master,
+    Zen,
+    Puzzle,
+    Versus,  // two boards sending garbage to each other
+    Finesse, // starts over on every finesse fault
+}
+
+impl Gamemode {
+    pub const SPRINT_GOALS: [u32; 4] = [20, 40, 100, 1000];
+    pub const ULTRA_MINUTES: [u32; 2] = [2, 3];
+    pub const DIG_GOALS: [u32; 3] = [10, 18, 100];
+
+    pub fn name(&self) -> String {
+        match self {
+            Gamemode::Classic => "Classic".to_string(),
+            Gamemode::Sprint(goal) => format!("Sprint {}", goal),
+            Gamemode::Ultra(minutes) => format!("Ultra {} Min", minutes),
+            Gamemode::Dig(goal) => format!("Dig {}", goal),
+            Gamemode::Master => "Master".to_string(),
+            Gamemode::Zen => "Zen".to_string(),
+            Gamemode::Puzzle => "Puzzle".to_string(),
+            Gamemode::Versus => "Versus".to_string(),
+            Gamemode::Finesse => "Finesse".to_string(),
+        }
+    }
+
+    // short identifier used in file names and replays
+
+    pub fn id(&self) -> String {
+        match self {
+            Gamemode::Classic => "classic".to_string(),
+            Gamemode::Sprint(goal) => format!("sprint{}", goal),
+            Gamemode::Ultra(minutes) => format!("ultra{}", minutes),
+            Gamemode::Dig(goal) => format!("dig{}", goal),
+            Gamemode::Master => "master".to_string(),
+            Gamemode::Zen => "zen".to_string(),
+            Gamemode::Puzzle => "puzzle".to_string(),
+            Gamemode::Versus => "versus".to_string(),
+            Gamemode::Finesse => "finesse".to_string(),
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "classic" => Some(Gamemode::Classic),
+            "master" => Some(Gamemode::Master),
+            "zen" => Some(Gamemode::Zen),
+            "puzzle" => Some(Gamemode::Puzzle),
+            "versus" => Some(Gamemode::Versus),
+            "finesse" => Some(Gamemode::Finesse),
+            "lines40" => Some(Gamemode::Sprint(40)), // name used before sprints had other goals
+            _ if id.starts_with("sprint") => id[6..].parse().ok().map(Gamemode::Sprint),
+            _ if id.starts_with("ultra") => id[5..].parse().ok().map(Gamemode::Ultra),
+            _ if id.starts_with("dig") => id[3..].parse().ok().map(Gamemode::Dig),
+            _ => None,
+        }
+    }
+
+    pub fn score_file(&self) -> String {
+        paths::data_file(&format!("score/{}.csv", self.id()))
+    }
+
+    // sprints and digs are ranked by time, everything else by score
+
+    pub fn lower_is_better(&self) -> bool {
+        matches!(self, Gamemode::Sprint(_) | Gamemode::Dig(_))
+    }
+
+    pub fn time_limit(&self) -> Option<Duration> {
+        match self {
+            Gamemode::Ultra(minutes) => Some(Duration::from_secs(*minutes as u64 * 60)),
+            _ => None,
+        }
+    }
 }
 
 pub fn has_colided(grid: &Vec<[i32; 2]>, position: &(i32, i32), map: &[[Cell; 10]; 20]) -> bool {
@@ -266,6 +910,12 @@ pub fn lowest_avaliable_position(
         result.position[1] += 1;
     }
 
+    // dropping without moving keeps the last rotation for t-spin detection
+
+    if result.position == current_tetromino.position {
+        result.last_kick = current_tetromino.last_kick;
+    }
+
     result
 }
 
@@ -338,11 +988,29 @@ pub fn render_text<'a>(
     print_string: &String,
     x: i32,
     y: i32,
+) -> Result<(), String> {
+    draw_text(canvas, font, text_color, print_string, x, y)?;
+
+    canvas.present();
+
+    Ok(())
+}
+
+// same as render_text but leaves presenting to the caller, for screens that draw a whole frame
+// before showing it
+
+pub fn draw_text<'a>(
+    canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+    font: &sdl2::ttf::Font<'a, 'static>,
+    text_color: Color,
+    print_string: &str,
+    x: i32,
+    y: i32,
 ) -> Result<(), String> {
     let texture_creator = canvas.texture_creator();
 
     let surface = font
-        .render(&print_string)
+        .render(print_string)
         .blended(text_color)
         .map_err(|e| e.to_string())?;
 
@@ -357,7 +1025,162 @@ pub fn render_text<'a>(
 
     canvas.copy(&texture, None, Some(target_rect))?;
 
-    canvas.present();
-
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_read_back_what_was_written() {
+        let mut settings = Settings {
+            bright_mode: true,
+            repeat_delay: Duration::from_millis(85),
+            dig_rise_interval: Duration::from_secs(4),
+            cpu_speed: 32,
+            network_address: "192.168.1.20".to_string(),
+            network_port: 4000,
+            ..Settings::default()
+        };
+
+        settings
+            .key_bindings
+            .set_key(Binding::Game(Action::Hold), 1, Some(Scancode::A));
+        settings
+            .key_bindings
+            .set_key(Binding::Game(Action::MoveLeft), 0, None);
+        settings
+            .key_bindings
+            .set_button(Binding::Game(Action::Hold), None);
+
+        let text = settings.to_text();
+        let (read, problems) = Settings::from_text(&text, "settings.txt");
+
+        assert!(text.starts_with("version = 1\n"));
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(read.values(), settings.values());
+    }
+
+    #[test]
+    fn settings_skip_comments_and_keep_defaults_for_missing_keys() {
+        let text = "version = 1\n\n# faster das\nrepeat_delay = 70\n  insta_das=false  \n";
+        let (settings, problems) = Settings::from_text(text, "settings.txt");
+
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(settings.repeat_delay, Duration::from_millis(70));
+        assert!(!settings.insta_das);
+        assert_eq!(settings.init_level, Settings::default().init_level);
+    }
+
+    #[test]
+    fn settings_report_bad_lines_and_keep_the_rest() {
+        let text = "\
+version = 1
+insta_das = maybe
+repeat_delay = -5
+init_level = 7
+key.move_left = Not A Key
+button.hold = trigger
+colour = blue
+no equals sign
+";
+        let (settings, problems) = Settings::from_text(text, "settings.txt");
+
+        assert_eq!(
+            problems,
+            [
+                "settings.txt line 2: invalid value \"maybe\" for insta_das",
+                "settings.txt line 3: invalid value \"-5\" for repeat_delay",
+                "settings.txt line 5: unknown key \"Not A Key\" for key.move_left",
+                "settings.txt line 6: unknown button \"trigger\" for button.hold",
+                "settings.txt line 7: unknown setting \"colour\"",
+                "settings.txt line 8: expected \"key = value\" but found \"no equals sign\"",
+            ]
+        );
+
+        let defaults = Settings::default();
+
+        assert_eq!(settings.init_level, 7);
+        assert_eq!(settings.insta_das, defaults.insta_das);
+        assert_eq!(settings.repeat_delay, defaults.repeat_delay);
+        assert_eq!(
+            settings.key_bindings.keys(Binding::Game(Action::MoveLeft)),
+            defaults.key_bindings.keys(Binding::Game(Action::MoveLeft))
+        );
+        assert_eq!(
+            settings.key_bindings.button(Binding::Game(Action::Hold)),
+            defaults.key_bindings.button(Binding::Game(Action::Hold))
+        );
+    }
+
+    #[test]
+    fn settings_from_a_newer_version_are_still_read() {
+        let (settings, problems) =
+            Settings::from_text("version = 2\ncpu_speed = 40\n", "settings.txt");
+
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("newer version (2)"));
+        assert_eq!(settings.cpu_speed, 40);
+    }
+
+    #[test]
+    fn legacy_settings_are_migrated_in_order() {
+        let options = "true\nfalse\ntrue\n120\n15\n10\n5\n";
+        let keybinds = "Left\nRight\nUp\nZ\nA\nSpace\nDown\nC\nR\n";
+
+        let (settings, problems) = Settings::from_legacy(options, keybinds);
+
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert!(settings.bright_mode);
+        assert!(!settings.insta_das);
+        assert!(settings.insta_softdrop);
+        assert_eq!(settings.repeat_delay, Duration::from_millis(120));
+        assert_eq!(settings.repeat_interval, Duration::from_millis(15));
+        assert_eq!(settings.fall_interval, Duration::from_millis(10));
+        assert_eq!(settings.init_level, 5);
+
+        let key = |action| settings.key_bindings.keys(Binding::Game(action))[0];
+
+        assert_eq!(key(Action::MoveLeft), Some(Scancode::Left));
+        assert_eq!(key(Action::RotateClockwise), Some(Scancode::Up));
+        assert_eq!(key(Action::Rotate180), Some(Scancode::A));
+        assert_eq!(key(Action::HardDrop), Some(Scancode::Space));
+        assert_eq!(key(Action::QuickReset), Some(Scancode::R));
+
+        // the old files had no undo key, it keeps the default
+
+        assert_eq!(
+            settings.key_bindings.keys(Binding::Game(Action::Undo)),
+            Settings::default()
+                .key_bindings
+                .keys(Binding::Game(Action::Undo))
+        );
+
+        // what was migrated is written in the new format
+
+        let (read, problems) = Settings::from_text(&settings.to_text(), "settings.txt");
+
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(read.values(), settings.values());
+    }
+
+    #[test]
+    fn legacy_settings_only_lose_the_values_that_are_broken() {
+        let (settings, problems) = Settings::from_legacy("yes\nfalse\n", "Left\nNowhere\n");
+
+        assert_eq!(
+            problems,
+            [
+                "options.txt: invalid value \"yes\" for bright_mode",
+                "keybinds.txt: unknown key \"Nowhere\" for key.move_right",
+            ]
+        );
+        assert_eq!(settings.bright_mode, Settings::default().bright_mode);
+        assert!(!settings.insta_das);
+        assert_eq!(
+            settings.key_bindings.keys(Binding::Game(Action::MoveLeft))[0],
+            Some(Scancode::Left)
+        );
+    }
+}
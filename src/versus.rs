@@ -0,0 +1,1024 @@
+use crate::bot::Bot;
+use crate::engine::{Action, ClearType, Engine, EngineConfig, GameEvent, Input};
+use crate::network::{Connection, Message};
+use crate::paths;
+use crate::tetrominos::{Bag, TSpin, Tetromino};
+use crate::utilities::{
+    draw_text, lowest_avaliable_position, Binding, Gamemode, MenuInput, Settings, Theme,
+};
+use sdl2::controller::Button;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Scancode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// two players each with their own board, sending garbage to each other. both can play on one
+// keyboard or with a controller each, one of them can be on another computer or the second board can be played by the cpu
+
+pub struct Versus<'a> {
+    sdl_context: &'a sdl2::Sdl,
+    font: sdl2::ttf::Font<'a, 'static>,
+    small_font: sdl2::ttf::Font<'a, 'static>,
+    canvas: &'a mut sdl2::render::Canvas<sdl2::video::Window>,
+    event_pump: &'a mut sdl2::EventPump,
+    theme: &'a Theme,
+    settings: &'a Settings,
+    opponent: Opponent,
+    players: [Engine; 2],
+    wins: [u32; 2],
+    callouts: [Option<(String, Instant)>; 2], // last special clear of each player
+    redraw: bool,
+    connection: Option<Connection>,
+    local_player: Option<usize>, // the board played with the normal key bindings
+    controllers: Vec<u32>,       // controllers in the order they took a board in a local match
+    bot: Option<Bot>,            // plays the second board against the cpu
+    step: u64,                   // next step both engines will be advanced by
+    sent_step: u64,              // next step the local inputs will be sent for
+    local_inputs: Vec<Input>,    // inputs not yet sent to the opponent
+    step_inputs: [HashMap<u64, Vec<Input>>; 2],
+    waiting: bool, // the opponent's inputs for the next step haven't arrived yet
+    rematch_requested: bool, // the opponent asked for a rematch while this side was still playing
+}
+
+pub enum Opponent {
+    Local,
+    Cpu(f64), // pieces per second
+    Host(u16),
+    Join(String, u16),
+}
+
+enum Outcome {
+    Winner(usize),
+    Draw, // both players topped out on the same tick
+    Disconnected,
+    Quit,
+}
+
+enum ResultsChoice {
+    Rematch,
+    Quit,
+}
+
+impl<'a> Versus<'a> {
+    const CELL_SIZE: u32 = 30;
+    const PREVIEW_CELL_SIZE: u32 = 15;
+    const GRID_WIDTH: u32 = 10;
+    const GRID_HEIGHT: u32 = 20;
+    const PREVIEWS: usize = 5;
+
+    const CALLOUT_DURATION: Duration = Duration::from_secs(2);
+
+    const GARBAGE_METER_COLOR: Color = Color::RGB(220, 40, 40);
+
+    // network matches advance in steps of a few ticks, the inputs of a step are sent a few steps
+    // before they're used so they arrive in time
+
+    const STEP_TICKS: u64 = 16;
+    const INPUT_DELAY: u64 = 3;
+
+    // the keyboard is split in two, the left player plays on the letters and the right player on
+    // the arrows
+
+    const KEYS: [[(Scancode, Action); 8]; 2] = [
+        [
+            (Scancode::A, Action::MoveLeft),
+            (Scancode::D, Action::MoveRight),
+            (Scancode::S, Action::SoftDrop),
+            (Scancode::W, Action::HardDrop),
+            (Scancode::E, Action::RotateClockwise),
+            (Scancode::Q, Action::RotateCounterClockwise),
+            (Scancode::R, Action::Rotate180),
+            (Scancode::LShift, Action::Hold),
+        ],
+        [
+            (Scancode::Left, Action::MoveLeft),
+            (Scancode::Right, Action::MoveRight),
+            (Scancode::Down, Action::SoftDrop),
+            (Scancode::Up, Action::HardDrop),
+            (Scancode::Period, Action::RotateClockwise),
+            (Scancode::Comma, Action::RotateCounterClockwise),
+            (Scancode::Slash, Action::Rotate180),
+            (Scancode::RShift, Action::Hold),
+        ],
+    ];
+
+    const KEY_HINTS: [&'static str; 2] = [
+        "A/D move  S/W drop  Q/E/R rotate  LShift hold",
+        "Arrows move/drop  ,/./ rotate  RShift hold",
+    ];
+
+    pub fn new(
+        sdl_context: &'a sdl2::Sdl,
+        ttf_context: &'a sdl2::ttf::Sdl2TtfContext,
+        canvas: &'a mut sdl2::render::Canvas<sdl2::video::Window>,
+        event_pump: &'a mut sdl2::EventPump,
+        theme: &'a Theme,
+        settings: &'a Settings,
+        opponent: Opponent,
+    ) -> Result<Self, String> {
+        let font_path = paths::font();
+        let font = ttf_context.load_font(&font_path, 22)?;
+        let small_font = ttf_context.load_font(&font_path, 13)?;
+
+        let config = Self::config(settings);
+
+        Ok(Self {
+            sdl_context,
+            font,
+            small_font,
+            canvas,
+            event_pump,
+            theme,
+            settings,
+            opponent,
+            players: Self::new_players([config.clone(), config]),
+            wins: [0, 0],
+            callouts: [None, None],
+            redraw: true,
+            connection: None,
+            local_player: None,
+            controllers: Vec::new(),
+            bot: None,
+            step: 0,
+            sent_step: 0,
+            local_inputs: Vec::new(),
+            step_inputs: [HashMap::new(), HashMap::new()],
+            waiting: false,
+            rematch_requested: false,
+        })
+    }
+
+    fn config(settings: &Settings) -> EngineConfig {
+        EngineConfig {
+            insta_das: settings.insta_das,
+            insta_softdrop: settings.insta_softdrop,
+            repeat_delay: settings.repeat_delay,
+            repeat_interval: settings.repeat_interval,
+            soft_drop_interval: settings.fall_interval,
+            fall_interval: Engine::calculate_fall_duration(settings.init_level),
+            init_level: settings.init_level,
+            seed: settings.seed.unwrap_or_else(Bag::random_seed),
+            fixed_seed: settings.seed.is_some(),
+            dig_messiness: 0,
+            dig_rise_pieces: 0,
+            dig_rise_interval: Duration::ZERO,
+            puzzle: None,
+        }
+    }
+
+    // both players get the same pieces so neither is luckier than the other
+
+    fn new_players(configs: [EngineConfig; 2]) -> [Engine; 2] {
+        configs.map(|config| Engine::new(config, Gamemode::Versus))
+    }
+
+    pub fn run(&mut self) {
+        match self.connect() {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                self.show_message(&format!("Connection failed: {}", e));
+                return;
+            }
+        }
+
+        loop {
+            self.start_match();
+
+            let outcome = self.play();
+
+            if let Outcome::Winner(player) = outcome {
+                self.wins[player] += 1;
+            }
+
+            let title = match outcome {
+                Outcome::Winner(player) if Some(player) == self.local_player => {
+                    "You Win!".to_string()
+                }
+                Outcome::Winner(_) if self.local_player.is_some() => "You Lose".to_string(),
+                Outcome::Winner(player) => format!("Player {} Wins!", player + 1),
+                Outcome::Draw => "Draw".to_string(),
+                Outcome::Disconnected => {
+                    self.show_message("Opponent Left");
+                    break;
+                }
+                Outcome::Quit => {
+                    self.send(Message::Quit);
+                    break;
+                }
+            };
+
+            match self.results(&title) {
+                ResultsChoice::Rematch => {
+                    if !self.wait_for_rematch() {
+                        self.show_message("Opponent Left");
+                        break;
+                    }
+
+                    // the next seed comes from the bag both sides share so they agree on it
+                    // without asking
+
+                    let seed = self.players[0].state.bag.next_seed();
+                    let configs = self.players.each_ref().map(|engine| {
+                        let mut config = engine.config.clone();
+
+                        if !config.fixed_seed {
+                            config.seed = seed;
+                        }
+
+                        config
+                    });
+
+                    self.players = Self::new_players(configs);
+                    self.callouts = [None, None];
+
+                    if let Some(bot) = &mut self.bot {
+                        bot.reset();
+                    }
+                }
+                ResultsChoice::Quit => {
+                    self.send(Message::Quit);
+                    break;
+                }
+            }
+        }
+    }
+
+    // find the opponent of a network match and agree on the game with them, returns false if
+    // waiting was cancelled
+
+    fn connect(&mut self) -> Result<bool, String> {
+        let connection = match &self.opponent {
+            Opponent::Local => return Ok(true),
+            Opponent::Cpu(pieces_per_second) => {
+                // the cpu plays each piece in one go which needs instant soft drop
+
+                let config = Self::config(self.settings);
+                let mut bot_config = config.clone();
+                bot_config.insta_softdrop = true;
+
+                self.local_player = Some(0);
+                self.bot = Some(Bot::new(*pieces_per_second));
+                self.players = Self::new_players([config, bot_config]);
+
+                return Ok(true);
+            }
+            Opponent::Host(port) => {
+                let port = *port;
+                let listener = Connection::listen(port)?;
+
+                let back_keys = self.settings.key_bindings.names(Binding::MenuBack);
+
+                self.render_message(
+                    "Waiting for opponent",
+                    &format!("Hosting on port {}, {} to cancel", port, back_keys),
+                );
+
+                loop {
+                    for event in self.event_pump.poll_iter() {
+                        if let Event::Quit { .. } = event {
+                            return Ok(false);
+                        }
+
+                        if self.settings.key_bindings.menu_input(&event) == Some(MenuInput::Back) {
+                            return Ok(false);
+                        }
+                    }
+
+                    if let Some(connection) = Connection::accept(&listener)? {
+                        break connection;
+                    }
+
+                    ::std::thread::sleep(Duration::from_millis(50));
+                }
+            }
+            Opponent::Join(address, port) => {
+                let (address, port) = (address.clone(), *port);
+
+                self.render_message("Connecting", &format!("Joining {}:{}", address, port));
+
+                Connection::join(&address, port)?
+            }
+        };
+
+        self.render_message("Connecting", "Starting the match");
+
+        let mut connection = connection;
+        let configs = connection.handshake(Self::config(self.settings))?;
+
+        self.local_player = Some(if connection.is_host { 0 } else { 1 });
+        self.players = Self::new_players(configs);
+        self.connection = Some(connection);
+
+        Ok(true)
+    }
+
+    // both engines start at step zero, the first few steps have no inputs since nothing could
+    // have been sent for them yet
+
+    fn start_match(&mut self) {
+        self.step = 0;
+        self.sent_step = Self::INPUT_DELAY;
+        self.local_inputs.clear();
+        self.waiting = false;
+
+        for step_inputs in self.step_inputs.iter_mut() {
+            step_inputs.clear();
+
+            for step in 0..Self::INPUT_DELAY {
+                step_inputs.insert(step, Vec::new());
+            }
+        }
+    }
+
+    // run frames until one of the players tops out, both engines are stepped on the same ticks
+    // and garbage is exchanged after every tick
+
+    fn play(&mut self) -> Outcome {
+        let target_frame_duration: i32 = 1000 / 60;
+
+        let mut clock = Instant::now();
+        let mut start_time = self.players[0].now();
+
+        self.render();
+
+        loop {
+            let frame_start_time = self.sdl_context.timer().unwrap().ticks();
+
+            let (mut inputs, pause, quit) = self.handle_input();
+
+            if quit {
+                return Outcome::Quit;
+            }
+
+            if pause {
+                if !self.pause() {
+                    return Outcome::Quit;
+                }
+
+                // keys released while paused were never seen by the engines
+
+                for player_inputs in inputs.iter_mut() {
+                    player_inputs.extend(Action::ALL.into_iter().map(Input::Release));
+                }
+
+                clock = Instant::now();
+                start_time = self.players[0].now();
+                self.redraw = true;
+            }
+
+            match self.connection.as_ref().and(self.local_player) {
+                None => loop {
+                    if let Some(bot) = &mut self.bot {
+                        inputs[1].extend(bot.inputs(&self.players[1]));
+                    }
+
+                    if let Some(outcome) = self.tick(&mut inputs) {
+                        return outcome;
+                    }
+
+                    if self.players[0].now() - start_time >= clock.elapsed() {
+                        break;
+                    }
+                },
+                Some(local_player) => {
+                    self.local_inputs.append(&mut inputs[local_player]);
+
+                    let waiting = loop {
+                        if let Err(outcome) = self.exchange_inputs() {
+                            return outcome;
+                        }
+
+                        let Some(mut step_inputs) = self.take_step_inputs() else {
+                            break true;
+                        };
+
+                        for _ in 0..Self::STEP_TICKS {
+                            if let Some(outcome) = self.tick(&mut step_inputs) {
+                                return outcome;
+                            }
+                        }
+
+                        self.step += 1;
+
+                        if self.players[0].now() - start_time >= clock.elapsed() {
+                            break false;
+                        }
+                    };
+
+                    // time spent waiting for the opponent isn't caught up on afterwards
+
+                    if waiting {
+                        clock = Instant::now();
+                        start_time = self.players[0].now();
+                    }
+
+                    if waiting != self.waiting {
+                        self.waiting = waiting;
+                        self.redraw = true;
+                    }
+                }
+            }
+
+            for callout in self.callouts.iter_mut() {
+                if let Some((_, time)) = callout {
+                    if time.elapsed() >= Self::CALLOUT_DURATION {
+                        *callout = None;
+                        self.redraw = true;
+                    }
+                }
+            }
+
+            if self.redraw {
+                self.render();
+            }
+
+            let frame_end_time = self.sdl_context.timer().unwrap().ticks();
+            let frame_duration: i32 = (frame_end_time - frame_start_time) as i32;
+            let sleep_time = target_frame_duration.saturating_sub(frame_duration);
+
+            if sleep_time > 0 {
+                ::std::thread::sleep(Duration::from_millis(sleep_time as u64));
+            }
+        }
+    }
+
+    // advance both engines by one tick and hand the garbage they sent to the other player,
+    // returns how the match ended once it did
+
+    fn tick(&mut self, inputs: &mut [Vec<Input>; 2]) -> Option<Outcome> {
+        let mut attacks = [0, 0];
+
+        for (player, engine) in self.players.iter_mut().enumerate() {
+            for event in engine.tick(&inputs[player]) {
+                self.redraw = true;
+
+                match event {
+                    GameEvent::Attack(lines) => attacks[1 - player] += lines,
+                    GameEvent::Cleared(clear_type) => {
+                        if let Some(name) = Self::callout(engine, clear_type) {
+                            self.callouts[player] = Some((name, Instant::now()));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            inputs[player].clear();
+        }
+
+        for (engine, lines) in self.players.iter_mut().zip(attacks) {
+            engine.receive_garbage(lines);
+        }
+
+        match (self.players[0].state.run, self.players[1].state.run) {
+            (true, true) => None,
+            (false, false) => Some(Outcome::Draw),
+            (false, true) => Some(Outcome::Winner(1)),
+            (true, false) => Some(Outcome::Winner(0)),
+        }
+    }
+
+    // send the local inputs for the step that's the input delay ahead and collect whatever the
+    // opponent has sent so far
+
+    fn exchange_inputs(&mut self) -> Result<(), Outcome> {
+        let (Some(connection), Some(local_player)) = (&mut self.connection, self.local_player)
+        else {
+            return Ok(());
+        };
+
+        while self.sent_step <= self.step + Self::INPUT_DELAY {
+            let inputs = std::mem::take(&mut self.local_inputs);
+
+            connection
+                .send(&Message::Step(self.sent_step, inputs.clone()))
+                .map_err(|_| Outcome::Disconnected)?;
+
+            self.step_inputs[local_player].insert(self.sent_step, inputs);
+            self.sent_step += 1;
+        }
+
+        loop {
+            match connection.receive() {
+                Ok(Some(Message::Step(step, inputs))) => {
+                    self.step_inputs[1 - local_player].insert(step, inputs);
+                }
+                Ok(Some(Message::Rematch)) => self.rematch_requested = true,
+                Ok(None) => return Ok(()),
+                Ok(Some(Message::Quit)) | Err(_) => return Err(Outcome::Disconnected),
+            }
+        }
+    }
+
+    // the inputs of both players for the next step once both have arrived
+
+    fn take_step_inputs(&mut self) -> Option<[Vec<Input>; 2]> {
+        if !self
+            .step_inputs
+            .iter()
+            .all(|step_inputs| step_inputs.contains_key(&self.step))
+        {
+            return None;
+        }
+
+        Some(
+            self.step_inputs
+                .each_mut()
+                .map(|step_inputs| step_inputs.remove(&self.step).unwrap_or_default()),
+        )
+    }
+
+    // a network rematch only starts once both players asked for it, steps still on their way
+    // from the last match are skipped
+
+    fn wait_for_rematch(&mut self) -> bool {
+        let Some(connection) = &mut self.connection else {
+            return true;
+        };
+
+        if connection.send(&Message::Rematch).is_err() {
+            return false;
+        }
+
+        // the opponent may have finished the match first and asked already
+
+        if std::mem::take(&mut self.rematch_requested) {
+            return true;
+        }
+
+        let title = format!(
+            "You {} - {} Opponent",
+            self.wins[self.local_player.unwrap_or(0)],
+            self.wins[1 - self.local_player.unwrap_or(0)]
+        );
+        self.render_message(&title, "Waiting for opponent");
+
+        let Some(connection) = &mut self.connection else {
+            return true;
+        };
+
+        loop {
+            for event in self.event_pump.poll_iter() {
+                if let Event::Quit { .. } = event {
+                    return false;
+                }
+
+                if self.settings.key_bindings.menu_input(&event) == Some(MenuInput::Back) {
+                    return false;
+                }
+            }
+
+            match connection.receive() {
+                Ok(Some(Message::Rematch)) => return true,
+                Ok(Some(Message::Step(..))) => {}
+                Ok(None) => ::std::thread::sleep(Duration::from_millis(10)),
+                Ok(Some(Message::Quit)) | Err(_) => return false,
+            }
+        }
+    }
+
+    fn send(&mut self, message: Message) {
+        if let Some(connection) = &mut self.connection {
+            let _ = connection.send(&message);
+        }
+    }
+
+    // tetrises, t-spins and perfect clears are announced above the board
+
+    fn callout(engine: &Engine, clear_type: ClearType) -> Option<String> {
+        if clear_type.lines < 4 && clear_type.t_spin == TSpin::None && !clear_type.perfect_clear {
+            return None;
+        }
+
+        let name = if clear_type.perfect_clear {
+            "Perfect Clear".to_string()
+        } else {
+            clear_type.name()
+        };
+
+        match engine.state.back_to_back {
+            Some(chain) if chain > 0 && clear_type.is_difficult() => Some(format!("B2B {}", name)),
+            _ => Some(name),
+        }
+    }
+
+    // sort the keys of this frame out to the player they belong to. a network match is played
+    // with the normal key bindings and doesn't pause when the window loses focus so both sides
+    // can be tried on one computer
+
+    fn handle_input(&mut self) -> ([Vec<Input>; 2], bool, bool) {
+        let mut inputs: [Vec<Input>; 2] = [Vec::new(), Vec::new()];
+        let mut pause = false;
+        let mut quit = false;
+
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+
+        for event in events {
+            match event {
+                Event::Quit { .. } => quit = true,
+                _ if self
+                    .settings
+                    .key_bindings
+                    .is_pressed(Binding::Pause, &event) =>
+                {
+                    pause = true
+                }
+                Event::Window {
+                    win_event: WindowEvent::FocusLost,
+                    ..
+                } => pause |= self.connection.is_none(),
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some((player, action)) = self.action(scancode) {
+                        inputs[player].push(Input::Press(action));
+                    }
+                }
+                Event::KeyUp {
+                    scancode: Some(scancode),
+                    ..
+                } => {
+                    if let Some((player, action)) = self.action(scancode) {
+                        inputs[player].push(Input::Release(action));
+                    }
+                }
+                Event::ControllerButtonDown { which, button, .. } => {
+                    if let Some((player, action)) = self.button_action(which, button) {
+                        inputs[player].push(Input::Press(action));
+                    }
+                }
+                Event::ControllerButtonUp { which, button, .. } => {
+                    if let Some((player, action)) = self.button_action(which, button) {
+                        inputs[player].push(Input::Release(action));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        (inputs, pause, quit)
+    }
+
+    fn action(&self, scancode: Scancode) -> Option<(usize, Action)> {
+        if let Some(local_player) = self.local_player {
+            return match self.settings.key_bindings.action(scancode) {
+                Some(Action::QuickReset | Action::Undo) | None => None,
+                Some(action) => Some((local_player, action)),
+            };
+        }
+
+        Self::KEYS.iter().enumerate().find_map(|(player, keys)| {
+            keys.iter()
+                .find(|(key, _)| *key == scancode)
+                .map(|(_, action)| (player, *action))
+        })
+    }
+
+    // controllers use the bound buttons on either board. on one computer the first controller
+    // used plays the left board and the second one the right
+
+    fn button_action(&mut self, controller: u32, button: Button) -> Option<(usize, Action)> {
+        let action = match self.settings.key_bindings.button_action(button) {
+            Some(Action::QuickReset | Action::Undo) | None => return None,
+            Some(action) => action,
+        };
+
+        if let Some(local_player) = self.local_player {
+            return Some((local_player, action));
+        }
+
+        let player = match self.controllers.iter().position(|id| *id == controller) {
+            Some(player) => player,
+            None if self.controllers.len() < 2 => {
+                self.controllers.push(controller);
+                self.controllers.len() - 1
+            }
+            None => return None,
+        };
+
+        Some((player, action))
+    }
+
+    // returns whether the match goes on, a network match keeps waiting for the inputs of this
+    // side while paused
+
+    fn pause(&mut self) -> bool {
+        let options = ["Resume", "Quit"];
+        let mut index = 0;
+
+        loop {
+            self.render_menu("Paused", &options, index);
+
+            let event = self.event_pump.wait_event();
+            let key_bindings = &self.settings.key_bindings;
+
+            // the pause key resumes the match as well as the menu's back key
+
+            match event {
+                Event::Quit { .. } => return false,
+                _ if key_bindings.is_pressed(Binding::Pause, &event) => return true,
+                _ => {}
+            }
+
+            match key_bindings.menu_input(&event) {
+                Some(MenuInput::Back) => return true,
+                Some(MenuInput::Up) => {
+                    index = index.saturating_sub(1);
+                }
+                Some(MenuInput::Down) => {
+                    index = (index + 1).min(options.len() - 1);
+                }
+                Some(MenuInput::Confirm) => return options[index] == "Resume",
+                _ => {}
+            }
+        }
+    }
+
+    fn results(&mut self, title: &str) -> ResultsChoice {
+        let options = ["Rematch", "Menu"];
+        let mut index = 0;
+
+        loop {
+            self.render_menu(title, &options, index);
+
+            let event = self.event_pump.wait_event();
+
+            if let Event::Quit { .. } = event {
+                return ResultsChoice::Quit;
+            }
+
+            match self.settings.key_bindings.menu_input(&event) {
+                Some(MenuInput::Back) => return ResultsChoice::Quit,
+                Some(MenuInput::Up) => {
+                    index = index.saturating_sub(1);
+                }
+                Some(MenuInput::Down) => {
+                    index = (index + 1).min(options.len() - 1);
+                }
+                Some(MenuInput::Confirm) => match options[index] {
+                    "Rematch" => return ResultsChoice::Rematch,
+                    _ => return ResultsChoice::Quit,
+                },
+                _ => {}
+            }
+        }
+    }
+
+    // show a message until any key is pressed
+
+    fn show_message(&mut self, title: &str) {
+        self.render_message(title, "Press any key");
+
+        loop {
+            match self.event_pump.wait_event() {
+                Event::Quit { .. } | Event::KeyDown { .. } | Event::ControllerButtonDown { .. } => {
+                    return
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn render_message(&mut self, title: &str, message: &str) {
+        self.canvas.set_draw_color(self.theme.bg_color_1);
+        self.canvas.clear();
+
+        let _ = draw_text(
+            self.canvas,
+            &self.font,
+            self.theme.text_color,
+            title,
+            320,
+            50,
+        );
+        let _ = draw_text(
+            self.canvas,
+            &self.font,
+            self.theme.text_color,
+            message,
+            320,
+            300,
+        );
+
+        self.canvas.present();
+    }
+
+    fn render_menu(&mut self, title: &str, options: &[&str], index: usize) {
+        self.canvas.set_draw_color(self.theme.bg_color_1);
+        self.canvas.clear();
+
+        let score = format!("Player 1  {} - {}  Player 2", self.wins[0], self.wins[1]);
+
+        let _ = draw_text(
+            self.canvas,
+            &self.font,
+            self.theme.text_color,
+            title,
+            320,
+            50,
+        );
+        let _ = draw_text(
+            self.canvas,
+            &self.font,
+            self.theme.text_color,
+            &score,
+            320,
+            100,
+        );
+
+        let mut options_y = 300;
+
+        for (option_index, option) in options.iter().enumerate() {
+            let prefix = if option_index == index { "> " } else { "  " };
+
+            let _ = draw_text(
+                self.canvas,
+                &self.font,
+                self.theme.text_color,
+                &format!("{}{}", prefix, option),
+                320,
+                options_y,
+            );
+            options_y += 50;
+        }
+
+        self.canvas.present();
+    }
+
+    // the whole frame is drawn before it's shown since both boards change at once
+
+    fn render(&mut self) {
+        self.canvas.set_draw_color(self.theme.bg_color_1);
+        self.canvas.clear();
+
+        for player in 0..self.players.len() {
+            self.render_player(player);
+        }
+
+        if self.waiting {
+            let _ = draw_text(
+                self.canvas,
+                &self.small_font,
+                self.theme.text_color,
+                "Waiting for opponent...",
+                420,
+                5,
+            );
+        }
+
+        self.canvas.present();
+        self.redraw = false;
+    }
+
+    fn render_player(&mut self, player: usize) {
+        let half_width = self.canvas.window().size().0 / 2;
+        let box_width: u32 = Self::CELL_SIZE * Self::GRID_WIDTH;
+        let box_height: u32 = Self::CELL_SIZE * Self::GRID_HEIGHT;
+        let x_offset: i32 = (half_width * player as u32 + (half_width - box_width) / 2) as i32;
+        let y_offset: i32 = (self.canvas.window().size().1 - box_height) as i32 - 20;
+
+        self.canvas.set_draw_color(self.theme.bg_color_2);
+        let _ = self
+            .canvas
+            .fill_rect(Rect::new(x_offset, y_offset, box_width, box_height));
+
+        let engine = &self.players[player];
+
+        for (y, row) in engine.state.map.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                if cell.occupied {
+                    self.canvas.set_draw_color(cell.color.unwrap());
+
+                    let pos_x: i32 = x as i32 * Self::CELL_SIZE as i32 + x_offset;
+                    let pos_y: i32 = y as i32 * Self::CELL_SIZE as i32 + y_offset;
+
+                    let rect: Rect = Rect::new(pos_x, pos_y, Self::CELL_SIZE, Self::CELL_SIZE);
+
+                    let _ = self.canvas.fill_rect(rect);
+                }
+            }
+        }
+
+        let current_tetromino = engine.state.current_tetromino.clone();
+        let mut ghost = lowest_avaliable_position(&current_tetromino, &engine.state.map);
+        ghost.color.a = 100;
+
+        let incoming = engine.incoming_garbage().min(Self::GRID_HEIGHT);
+        let hold = engine.state.hold.clone();
+        let previews = engine.state.bag.preview(Self::PREVIEWS);
+
+        for tetromino in [&ghost, &current_tetromino] {
+            let x = x_offset + tetromino.position[0] * Self::CELL_SIZE as i32;
+            let y = y_offset + tetromino.position[1] * Self::CELL_SIZE as i32;
+            self.render_tetromino(tetromino, x, y, Self::CELL_SIZE, y_offset);
+        }
+
+        // incoming garbage is shown as a bar along the left edge of the board
+
+        let meter_height = incoming * Self::CELL_SIZE;
+
+        self.canvas.set_draw_color(Self::GARBAGE_METER_COLOR);
+        let _ = self.canvas.fill_rect(Rect::new(
+            x_offset - 8,
+            y_offset + (box_height - meter_height) as i32,
+            6,
+            meter_height,
+        ));
+
+        // hold on the left and the next pieces on the right, drawn smaller than the board
+
+        let side_width = (Self::PREVIEW_CELL_SIZE * 4) as i32;
+
+        if let Some(hold) = hold {
+            let x = x_offset - side_width - 16;
+            self.render_tetromino(&hold, x, y_offset, Self::PREVIEW_CELL_SIZE, y_offset);
+        }
+
+        let mut preview_y = y_offset;
+
+        for tetromino in previews.iter() {
+            let x = x_offset + box_width as i32 + 10;
+            self.render_tetromino(tetromino, x, preview_y, Self::PREVIEW_CELL_SIZE, y_offset);
+            preview_y += (Self::PREVIEW_CELL_SIZE * 3) as i32;
+        }
+
+        let engine = &self.players[player];
+
+        let name = match self.local_player {
+            Some(local_player) if local_player == player => "You".to_string(),
+            Some(_) if self.bot.is_some() => "CPU".to_string(),
+            Some(_) => "Opponent".to_string(),
+            None => format!("Player {}", player + 1),
+        };
+
+        let lines = [
+            format!("{}   Wins: {}", name, self.wins[player]),
+            format!(
+                "Sent: {}   Lines: {}",
+                engine.state.lines_sent, engine.state.lines_cleared
+            ),
+            self.callouts[player]
+                .as_ref()
+                .map_or(String::new(), |(name, _)| name.clone()),
+        ];
+
+        let mut y = 20;
+
+        for line in lines.iter().filter(|line| !line.is_empty()) {
+            let _ = draw_text(
+                self.canvas,
+                &self.font,
+                self.theme.text_color,
+                line,
+                x_offset,
+                y,
+            );
+            y += 30;
+        }
+
+        // network and cpu matches are played on the normal key bindings
+
+        if self.local_player.is_none() {
+            let _ = draw_text(
+                self.canvas,
+                &self.small_font,
+                self.theme.text_color,
+                Self::KEY_HINTS[player],
+                x_offset - side_width,
+                y_offset - 30,
+            );
+        }
+    }
+
+    // draw a tetromino with its top left at x and y, cells above the top of the board are left
+    // out
+
+    fn render_tetromino(
+        &mut self,
+        tetromino: &Tetromino,
+        x: i32,
+        y: i32,
+        cell_size: u32,
+        top: i32,
+    ) {
+        self.canvas.set_draw_color(tetromino.color);
+
+        for point in tetromino.grid.iter() {
+            let pos_x = x + point[0] * cell_size as i32;
+            let pos_y = y + point[1] * cell_size as i32;
+
+            if pos_y < top {
+                continue;
+            }
+
+            let _ = self
+                .canvas
+                .fill_rect(Rect::new(pos_x, pos_y, cell_size, cell_size));
+        }
+    }
+}